@@ -0,0 +1,86 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2023 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Benchmarks for the stages of the pipeline, on synthetic inputs.
+//!
+//! Run with `cargo bench`. To run only some scenarios, pass a substring of
+//! their name, e.g. `cargo bench -- parse`.
+
+mod synthetic;
+
+use rcl::loader::{Loader, SandboxMode};
+use rcl::source::DocId;
+use rcl::tracer::VoidTracer;
+use synthetic::bench;
+
+fn main() {
+    // Cargo passes `--bench` to benchmark binaries, skip flags like that.
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let enabled = |name: &str| filters.is_empty() || filters.iter().any(|f| name.contains(f));
+
+    let record = synthetic::large_record(2_000);
+    let comprehension = synthetic::list_comprehension(150);
+    let chain = synthetic::import_chain("pipeline", 50);
+    let chain_dir = chain.parent().unwrap().to_str().unwrap().to_string();
+    let chain_entry = synthetic::read(&chain);
+
+    if enabled("lex_large_record") {
+        bench("lex_large_record", || {
+            rcl::lexer::lex(DocId(0), &record).unwrap()
+        });
+    }
+
+    if enabled("parse_large_record") {
+        let tokens = rcl::lexer::lex(DocId(0), &record).unwrap();
+        bench("parse_large_record", || {
+            rcl::parser::parse(DocId(0), &record, &tokens).unwrap()
+        });
+    }
+
+    if enabled("format_large_record") {
+        let tokens = rcl::lexer::lex(DocId(0), &record).unwrap();
+        let (_span, cst) = rcl::parser::parse(DocId(0), &record, &tokens).unwrap();
+        let cfg = rcl::pprint::Config { width: 80 };
+        bench("format_large_record", || {
+            rcl::fmt_cst::format_expr(&record, &cst)
+                .println(&cfg)
+                .to_string_no_markup()
+        });
+    }
+
+    if enabled("eval_large_record") {
+        bench("eval_large_record", || {
+            Loader::new().evaluate_str("record", &record).unwrap()
+        });
+    }
+
+    if enabled("eval_list_comprehension") {
+        bench("eval_list_comprehension", || {
+            Loader::new()
+                .evaluate_str("comprehension", &comprehension)
+                .unwrap()
+        });
+    }
+
+    if enabled("eval_import_chain") {
+        bench("eval_import_chain", || {
+            let mut loader = Loader::new();
+            loader
+                .initialize_filesystem(SandboxMode::Workdir, Some(&chain_dir), &[])
+                .unwrap();
+            let id = loader.load_string(chain_entry.clone());
+            let mut type_env = rcl::typecheck::prelude();
+            let mut value_env = rcl::runtime::prelude();
+            loader
+                .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+                .unwrap()
+        });
+    }
+}
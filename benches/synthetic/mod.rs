@@ -0,0 +1,122 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2023 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Generators for large synthetic inputs, and a minimal timing harness.
+//!
+//! The generated documents resemble real configurations: records with string
+//! and number fields, comprehensions over lists of records, and chains of
+//! imports. The output is deterministic, so timings are comparable across
+//! runs and commits.
+
+use std::fmt::Write;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Generate a record with `n` fields, each holding a small nested record.
+pub fn large_record(n: usize) -> String {
+    let mut out = String::from("{\n");
+    for i in 0..n {
+        writeln!(
+            out,
+            "  service_{i} = {{ name = \"svc-{i}\", port = {}, tags = [\"a\", \"b\"], enabled = {} }},",
+            8000 + i % 1000,
+            i % 3 != 0,
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Generate a nested list comprehension that yields `n * n` elements.
+///
+/// The body uses a let-binding, an f-string, a field access, and a condition,
+/// so it exercises the environment and the common operators.
+pub fn list_comprehension(n: usize) -> String {
+    format!(
+        r#"
+let hosts = [for i in std.range(0, {n}): {{ name = f"host-{{i}}", zone = i & 3 }}];
+let ports = std.range(0, {n});
+[
+  for host in hosts:
+  for port in ports:
+  let id = (host.zone * 1000) + port;
+  if (id & 1) == 0:
+  f"{{host.name}}:{{id}}"
+]
+"#
+    )
+}
+
+/// Write a chain of `depth` documents where each one imports the next.
+///
+/// Returns the path of the first document in the chain. The documents are
+/// written to a fresh directory named after `name` in the system temporary
+/// directory.
+pub fn import_chain(name: &str, depth: usize) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcl-bench-{name}"));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create benchmark directory.");
+    for i in 0..depth {
+        let body = if i + 1 < depth {
+            format!(
+                "let next = import \"chain_{}.rcl\";\n{{ level = {i}, sum = next.sum + {i}, next = next }}\n",
+                i + 1
+            )
+        } else {
+            format!("{{ level = {i}, sum = {i} }}\n")
+        };
+        std::fs::write(dir.join(format!("chain_{i}.rcl")), body)
+            .expect("Failed to write benchmark input.");
+    }
+    dir.join("chain_0.rcl")
+}
+
+/// Read a generated file back, for scenarios that need the source as a string.
+pub fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).expect("Failed to read benchmark input.")
+}
+
+/// Time `f` and print the median and fastest duration per iteration.
+///
+/// The number of iterations per sample is chosen such that a sample takes
+/// at least a few milliseconds, so that timer resolution does not matter.
+pub fn bench<T, F: FnMut() -> T>(name: &str, mut f: F) {
+    let min_sample = Duration::from_millis(20);
+    let num_samples = 15;
+
+    // Warm up, and find out how many iterations go in one sample.
+    let mut iters: u32 = 1;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iters {
+            black_box(f());
+        }
+        if start.elapsed() >= min_sample || iters >= 1 << 20 {
+            break;
+        }
+        iters *= 2;
+    }
+
+    let mut samples: Vec<Duration> = (0..num_samples)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..iters {
+                black_box(f());
+            }
+            start.elapsed() / iters
+        })
+        .collect();
+    samples.sort();
+
+    println!(
+        "{name:<28} median {:>12.3?}   min {:>12.3?}   ({iters} iters/sample)",
+        samples[num_samples / 2],
+        samples[0],
+    );
+}
@@ -0,0 +1,43 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2023 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+#![no_main]
+
+//! This fuzzer tests that the lexer and parser never crash on arbitrary input.
+//!
+//! Unlike `fuzz_source`, the input does not need a mode line, and it does not
+//! need to be valid UTF-8. The input is parsed both as a document and as a
+//! type expression (as for `--schema`), and when either fails, the error is
+//! rendered too, because that slices the input at the spans in the error.
+
+use libfuzzer_sys::fuzz_target;
+
+use rcl::error::Error;
+use rcl::loader::{Loader, VoidFilesystem};
+use rcl::pprint;
+
+fn render(loader: &Loader, err: Error) {
+    let inputs = loader.as_inputs();
+    let cfg = pprint::Config { width: 80 };
+    let _ = err.report(&inputs).println(&cfg);
+}
+
+fuzz_target!(|input: &[u8]| {
+    let input = String::from_utf8_lossy(input).into_owned();
+    let mut loader = Loader::new();
+    loader.set_filesystem(Box::new(VoidFilesystem));
+
+    let doc = loader.load_string(input.clone());
+    if let Err(err) = loader.get_cst(doc) {
+        render(&loader, *err);
+    }
+
+    let doc = loader.load_string(input);
+    if let Err(err) = loader.get_type(doc) {
+        render(&loader, *err);
+    }
+});
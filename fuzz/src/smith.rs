@@ -33,19 +33,25 @@ const BUILTINS: &[&str] = &[
     "ends_with",
     "enumerate",
     "except",
+    "fetch",
     "filter",
     "flat_map",
     "fold",
     "get",
+    "get_or_else",
     "group_by",
+    "has",
+    "index_of",
     "join",
     "key_by",
     "keys",
     "len",
     "map",
+    "map_values",
     "parse_int",
     "remove_prefix",
     "remove_suffix",
+    "repeat",
     "replace",
     "reverse",
     "split",
@@ -54,15 +60,27 @@ const BUILTINS: &[&str] = &[
     "sum",
     "to_lowercase",
     "to_uppercase",
+    "unique",
     "values",
     // Stdlib and its functions
+    "abs",
+    "concat",
+    "count",
+    "format_int",
+    "get_path",
+    "keys_sorted_by",
+    "or_default",
+    "partition",
     "range",
     "read_file_utf8",
+    "reduce",
     "std",
+    "to_string",
+    "zip",
 ];
 
 const BUILTIN_TYPES: &[&str] = &[
-    "Any", "Bool", "Dict", "Int", "List", "Null", "Set", "String", "Union", "Void",
+    "Any", "Bool", "Dict", "Int", "List", "Null", "Optional", "Set", "String", "Union", "Void",
 ];
 
 const LITERALS: &[&str] = &["true", "false", "null"];
@@ -70,7 +88,8 @@ const LITERALS: &[&str] = &["true", "false", "null"];
 const UNOPS: &[&str] = &["not", "-"];
 
 const BINOPS: &[&str] = &[
-    "and", "or", "|", "+", "-", "*", "/", "<", ">", "<=", ">=", "==", "!=",
+    "and", "or", "xor", "|", "&", "^", "<<", ">>", "**", "+", "-", "*", "/", "<", ">", "<=", ">=",
+    "==", "!=", "..", "..=",
 ];
 
 /// Return a copy of the nth last element of the array, clamping to the first.
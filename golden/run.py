@@ -36,24 +36,76 @@ OPTIONS
   RCL_BIN            Set this environment variable to override the binary to
                      execute, defaults to "target/debug/rcl".
 
+  RCL_PATH           Cleared before running the binary, so it does not affect
+                     how the goldens resolve imports.
+
 """
 
 import difflib
 import os
-import re
+import shutil
+import stat
 import subprocess
 import sys
+import tempfile
 import tomllib
 
 from typing import Iterable, Iterator, List, Optional
 
 
-STRIP_ESCAPES = re.compile("\x1b[^m]+m")
 RED = "\x1b[31m"
 GREEN = "\x1b[32m"
 RESET = "\x1b[0m"
 
 
+def prepare_output_dir(workdir: str, test_name: str) -> None:
+    """
+    Set up the files that exist before an `output` test writes out.json.
+    """
+    if test_name.startswith("replace_"):
+        out_path = os.path.join(workdir, "out.json")
+        with open(out_path, "w", encoding="utf-8") as f:
+            f.write("Previous contents.\n")
+        os.chmod(out_path, 0o640)
+
+    elif test_name.startswith("symlink_"):
+        with open(os.path.join(workdir, "target.json"), "w", encoding="utf-8") as f:
+            f.write("Previous contents.\n")
+        os.symlink("target.json", os.path.join(workdir, "out.json"))
+
+
+def prepare_tree_dir(workdir: str, fname: str, input_lines: List[str]) -> None:
+    """
+    Copy the tree that `tree_` tests build to src, with the input as input.rcl.
+    """
+    src_dir = os.path.join(workdir, "src")
+    shutil.copytree(os.path.join(os.path.dirname(fname), "tree"), src_dir)
+    with open(os.path.join(src_dir, "input.rcl"), "w", encoding="utf-8") as f:
+        f.write("".join(input_lines))
+
+
+def list_written_files(workdir: str) -> Iterator[str]:
+    """
+    Print the files in the directory, with their permissions and contents.
+
+    The inputs of tree builds in the src directory are not printed.
+    """
+    for root, dirs, files in os.walk(workdir):
+        if root == workdir and "src" in dirs:
+            dirs.remove("src")
+        dirs.sort()
+        for fname in sorted(files):
+            path = os.path.join(root, fname)
+            rel_path = os.path.relpath(path, workdir)
+            if os.path.islink(path):
+                yield f"==> {rel_path} -> {os.readlink(path)} <=="
+                continue
+            mode = stat.S_IMODE(os.stat(path).st_mode)
+            yield f"==> {rel_path} ({mode:o}) <=="
+            with open(path, "r", encoding="utf-8") as f:
+                yield from f.read().splitlines()
+
+
 def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Optional[str]:
     """
     Run the given golden test, return `None` if it was successful,
@@ -79,14 +131,43 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
     # Run with RUST_BACKTRACE=1 so we get a backtrace if the process panics.
     os.putenv("RUST_BACKTRACE", "1")
 
+    # Imports may resolve through RCL_PATH, so a value set in the developer's
+    # environment would make the outcome depend on where the tests run.
+    os.unsetenv("RCL_PATH")
+
+    # Files that the tests write are listed with their permissions, which must
+    # not depend on the umask of the developer's shell.
+    os.umask(0o022)
+
     # Allow overriding the binary that we run.
     rcl_bin = os.getenv("RCL_BIN", default="target/debug/rcl")
 
+    # Tests run in the directory of the test file, so they can import files
+    # next to it. Tests that write files run in a temporary directory instead,
+    # and then we check the files in that directory in addition to the output.
+    workdir = os.path.dirname(fname)
+    scratch: Optional[tempfile.TemporaryDirectory] = None
+
     # Decide which subcommand to test based on the test directory.
     match os.path.basename(os.path.dirname(fname)):
+        case "build" if os.path.basename(fname).startswith("tree_"):
+            scratch = tempfile.TemporaryDirectory()
+            workdir = scratch.name
+            prepare_tree_dir(workdir, fname, input_lines)
+            cmd = ["build", "--tree", "--format=json", "src", "out"]
+
         case "build":
             cmd = ["build", "--dry-run"]
 
+        case "color":
+            cmd = ["--color=ansi", "eval"]
+
+        case "csv":
+            cmd = ["eval", "--format=csv"]
+
+        case "env":
+            cmd = ["eval", "--format=env"]
+
         case "error" | "types":
             cmd = ["eval"]
 
@@ -102,15 +183,57 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
         case "json":
             cmd = ["eval", "--format=json"]
 
+        case "json_stream":
+            cmd = ["eval", "--format=json", "--stream"]
+
+        case "highlight":
+            cmd = ["--color=html", "highlight"]
+
         case "html":
             cmd = ["format", "--color=html"]
 
+        case "include":
+            cmd = ["eval", "-I", "lib", "-I", "../json"]
+
+        case "rcl_path":
+            # The first entry does not exist, and should be skipped.
+            os.putenv("RCL_PATH", "does-not-exist:../include/lib")
+            cmd = ["eval"]
+
+        case "multi":
+            # Evaluate a fixed file first, and then the test input.
+            cmd = ["eval", "--format=ndjson", "first.rcl"]
+
+        case "multi_concat":
+            cmd = ["eval", "--format=json", "--concat", "first.rcl"]
+
+        case "multi_error":
+            cmd = ["eval", "--format=ndjson", "broken.rcl"]
+
+        case "multi_fail_fast":
+            cmd = ["eval", "--format=ndjson", "--fail-fast", "broken.rcl"]
+
+        case "output":
+            scratch = tempfile.TemporaryDirectory()
+            workdir = scratch.name
+            prepare_output_dir(workdir, os.path.basename(fname))
+            cmd = ["eval", "--format=json", "--output=out.json"]
+
+        case "ndjson":
+            cmd = ["eval", "--format=ndjson"]
+
         case "raw":
             cmd = ["eval", "--format=raw"]
 
         case "rcl":
             cmd = ["eval", "--format=rcl"]
 
+        case "strict":
+            cmd = ["eval", "--strict"]
+
+        case "schema":
+            cmd = ["eval", "--schema", "schema.rcl"]
+
         case "toml":
             cmd = ["eval", "--format=toml"]
             # For TOML, when the test case is not an error, we additionally test
@@ -129,19 +252,26 @@ def test_one(fname: str, fname_friendly: str, *, rewrite_output: bool) -> Option
             raise ValueError(f"No command-line known for {unknown}.")
 
     result = subprocess.run(
-        [rcl_bin, "-C", os.path.dirname(fname), *cmd, "-"],
+        # Tree builds read their inputs from the tree, the others from stdin.
+        [rcl_bin, "-C", workdir, *cmd, *([] if "--tree" in cmd else ["-"])],
         input="".join(input_lines),
         capture_output=True,
         encoding="utf-8",
     )
     common_root = os.path.dirname(__file__)
     output_lines = [
-        # Strip ANSI escape codes from the output. Also replace references to
-        # absolute paths with a known path to make the test results portable.
-        STRIP_ESCAPES.sub("", line).replace(common_root, "/WORKDIR")
+        # Make ANSI escape codes visible, so the goldens confirm that they are
+        # absent, except in the tests that ask for color. Also replace references
+        # to absolute paths with a known path to make the test results portable.
+        line.replace("\x1b", "␛").replace(common_root, "/WORKDIR")
         for line in result.stdout.splitlines() + result.stderr.splitlines()
     ]
 
+    if scratch is not None:
+        output_lines.append(f"Exit code: {result.returncode}")
+        output_lines.extend(list_written_files(scratch.name))
+        scratch.cleanup()
+
     report_lines: List[str] = []
 
     for diff_line in difflib.unified_diff(
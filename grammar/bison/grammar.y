@@ -86,6 +86,7 @@ call_args
 
 expr_term
   : '{' seqs '}'
+  | '{' ':' '}'
   | '[' seqs ']'
   | '(' expr ')'
   | FSTRING_OPEN fstring
@@ -100,7 +101,7 @@ fstring
   ;
 
 stmt
-  : "let" IDENT optional_type_hint '=' expr ';'
+  : "let" binding optional_type_hint '=' expr ';'
   | "assert" expr ',' expr ';'
   | "trace" expr ';'
   ;
@@ -127,14 +128,31 @@ seq
   | "if" expr_op ':' seq
   ;
 
-idents: IDENT | idents ',' IDENT;
+idents: binding | idents ',' binding;
+
+binding: IDENT | '[' bindings ']';
+
+bindings
+  : %empty
+  | binding
+  | binding ',' bindings
+  ;
 
 type_expr
   // Note, we could allow single-argument function types without the parens as
   // well, but let's keep it simple for now and require the parens on function
   // types.
   : '(' types ')' "->" type_expr
-  | type_term '[' types ']'
+  | type_union
+  ;
+
+type_union
+  : type_simple
+  | type_union '|' type_simple
+  ;
+
+type_simple
+  : type_term '[' types ']'
   | type_term
   ;
 
@@ -22,7 +22,8 @@ __all__ = ["RclLexer"]
 # Therefore we extract the common tokens here.
 _root_base = [
     (r"#!.*?$", token.Comment.Hashbang),
-    (r"//.*?$", token.Comment),
+    (r"(//|#).*?$", token.Comment),
+    (r"/\*", token.Comment.Multiline, "block_comment"),
     (r'f"""', token.String, "format_triple"),
     (r'"""', token.String, "string_triple"),
     (r'f"', token.String, "format_double"),
@@ -47,11 +48,14 @@ _root_base = [
                 "import",
                 "in",
                 "let",
+                "match",
                 "not",
                 "null",
                 "or",
                 "trace",
                 "true",
+                "unique",
+                "xor",
             ),
             suffix=r"\b",
         ),
@@ -65,19 +69,25 @@ _root_base = [
                 "ends_with",
                 "enumerate",
                 "except",
+                "fetch",
                 "filter",
                 "flat_map",
                 "fold",
                 "get",
+                "get_or_else",
                 "group_by",
+                "has",
+                "index_of",
                 "join",
                 "key_by",
                 "keys",
                 "len",
                 "map",
+                "map_values",
                 "parse_int",
                 "remove_prefix",
                 "remove_suffix",
+                "repeat",
                 "replace",
                 "reverse",
                 "split",
@@ -87,7 +97,9 @@ _root_base = [
                 "sum",
                 "to_lowercase",
                 "to_uppercase",
+                "unique",
                 "values",
+                "zip",
             ),
             suffix=r"\b",
         ),
@@ -102,6 +114,7 @@ _root_base = [
                 "Int",
                 "List",
                 "Null",
+                "Optional",
                 "Set",
                 "String",
                 "Union",
@@ -119,7 +132,7 @@ _root_base = [
     # In the Rust lexer the punctuation is split out, and then further
     # into digraphs and monographs. Here we instead split them out by
     # token type.
-    (r"<=|>=|==|!=|=>|<|>|\+|-|\*|/|\|", token.Operator),
+    (r"\.\.=|\.\.|<<|>>|\*\*|<=|>=|==|!=|=>|<|>|\+|-|\*|/|\||&|\^", token.Operator),
     (r"[)(\]\[=,.:;]", token.Token),
     (r"{", token.Token, "in_brace"),
     (r"#", token.Error),
@@ -177,6 +190,13 @@ class RclLexer(RegexLexer):
             (r'""|"', token.String),
             (r"\\", token.String.Escape, "escape"),
         ],
+        "block_comment": [
+            (r"[^/*]+", token.Comment.Multiline),
+            # Block comments nest, so an inner opening pushes the state again.
+            (r"/\*", token.Comment.Multiline, "#push"),
+            (r"\*/", token.Comment.Multiline, "#pop"),
+            (r"[/*]", token.Comment.Multiline),
+        ],
         "escape": [
             (r'["\\/bfnrt}{]', token.String.Escape, "#pop"),
             (r"u\{[0-9a-fA-F]+\}", token.String.Escape, "#pop"),
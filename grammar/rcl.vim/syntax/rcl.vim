@@ -7,9 +7,9 @@
 
 syn keyword rclNull         null
 syn keyword rclBoolean      true false
-syn keyword rclConditional  if else
+syn keyword rclConditional  if else match
 syn keyword rclRepeat       for
-syn keyword rclOperator     and not or
+syn keyword rclOperator     and not or xor
 syn keyword rclKeyword      in let import
 syn keyword rclException    assert trace
 syn cluster rclKeyword      contains=rclBoolean,rclConditional,rclRepeat,rclOperator,rclKeyword,rclException
@@ -21,13 +21,20 @@ syn match rclOperator '!='
 syn match rclOperator '=>'
 syn match rclOperator '->'
 syn match rclOperator '|'
+syn match rclOperator '&'
+syn match rclOperator '\^'
+syn match rclOperator '<<'
+syn match rclOperator '>>'
 syn match rclOperator '+'
 syn match rclOperator '-'
 syn match rclOperator '*'
+syn match rclOperator '\*\*'
 syn match rclOperator '/'
+syn match rclOperator '\.\.=\?'
 
 syn keyword rclTodo FIXME NOTE TODO HACK contained
-syn match   rclComment '//.*' contains=rclTodo,@Spell
+syn match   rclComment '\(//\|#\).*' contains=rclTodo,@Spell
+syn region  rclBlockComment start='/\*' end='\*/' contains=rclBlockComment,rclTodo,@Spell
 
 " TODO: Something in the float is not yet working right.
 syn match   rclFloat       '\<[0-9_]\+\.[0-9_]\+\([eE][+-]\?[0-9_]\+\)\?\>'
@@ -45,9 +52,9 @@ syn region  rclFormatTriple  start='f"""' end='"""' skip='\\"\|\\{' contains=rcl
 
 " See also https://vi.stackexchange.com/questions/5966/ for why the `contains`
 " needs to end in `[]`.
-syn keyword rclBuiltin chars contains[] ends_with except filter flat_map fold get group_by join key_by keys len map parse_int remove_prefix remove_suffix replace reverse split split_lines starts_with std sum to_lowercase to_uppercase values
+syn keyword rclBuiltin chars contains[] ends_with except fetch filter flat_map fold get get_or_else group_by has index_of join key_by keys len map map_values parse_int remove_prefix remove_suffix repeat replace reverse split split_lines starts_with std sum to_lowercase to_uppercase unique values zip
 
-syn match   rclType '\<\(Any\|Bool\|Dict\|Int\|List\|Null\|Set\|String\|Void\)\>'
+syn match   rclType '\<\(Any\|Bool\|Dict\|Int\|List\|Null\|Optional\|Set\|String\|Void\)\>'
 
 syn cluster rclString contains=rclStringDouble,rclStringTriple,rclFormatDouble,rclFormatTriple
 highlight link rclStringDouble rclString
@@ -55,7 +62,7 @@ highlight link rclStringTriple rclString
 highlight link rclFormatDouble rclString
 highlight link rclFormatTriple rclString
 
-syn cluster rclExpr contains=@rclKeyword,rclOperator,@rclNumber,rclComment,rclBuiltin,rclType,@rclString
+syn cluster rclExpr contains=@rclKeyword,rclOperator,@rclNumber,rclComment,rclBlockComment,rclBuiltin,rclType,@rclString
 
 highlight link rclBoolean     Boolean
 highlight link rclConditional Conditional
@@ -65,6 +72,7 @@ highlight link rclNull        Keyword
 highlight link rclKeyword     Keyword
 highlight link rclException   Keyword
 highlight link rclComment     Comment
+highlight link rclBlockComment Comment
 highlight link rclTodo        Todo
 highlight link rclBuiltin     Function
 highlight link rclType        Type
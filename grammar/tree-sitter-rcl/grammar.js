@@ -26,7 +26,14 @@ module.exports = grammar({
   rules: {
     source_file: $ => $._expr,
 
-    comment: $ => /\/\/[^\n]*\n/,
+    // Note, RCL block comments nest, but a regex cannot express that. Doing
+    // so would require an external scanner, so we only match non-nested ones.
+    comment: $ => choice(
+      /\/\/[^\n]*\n/,
+      // A `#` comment, but not a shebang, which starts with `#!`.
+      /#([^!\n][^\n]*)?\n/,
+      /\/\*([^*]|\*+[^*\/])*\*+\//,
+    ),
     shebang: $ => /#![^\n]*\n/,
 
     ident: $ => /[_A-Za-z][-_A-Za-z0-9]*/,
@@ -98,12 +105,17 @@ module.exports = grammar({
     bool: $ => choice("true", "false"),
 
     unop_keyword: $ => choice("not"),
-    binop_keyword: $ => choice("and", "or"),
+    binop_keyword: $ => choice("and", "or", "xor"),
 
     unop: $ => choice($.unop_keyword, "-"),
     binop: $ => choice(
       $.binop_keyword,
       "|",
+      "&",
+      "^",
+      "<<",
+      ">>",
+      "**",
       "*",
       "+",
       "-",
@@ -114,6 +126,8 @@ module.exports = grammar({
       ">=",
       "==",
       "!=",
+      "..",
+      "..=",
     ),
 
     _expr: $ => choice(
@@ -125,6 +139,7 @@ module.exports = grammar({
     _expr_op: $ => choice(
       $.expr_import,
       $.expr_if,
+      $.expr_match,
       $.expr_function,
       $.expr_unop,
       $.expr_binop,
@@ -143,6 +158,32 @@ module.exports = grammar({
       field("else", $._expr),
     ),
 
+    expr_match: $ => seq(
+      "match",
+      field("scrutinee", $._expr_op),
+      "{",
+      $._match_arms,
+      optional(","),
+      "}",
+    ),
+    _match_arms: $ => choice(
+      $.match_arm,
+      seq($._match_arms, ",", $.match_arm),
+    ),
+    match_arm: $ => seq(
+      field("pattern", $._pattern),
+      "=>",
+      field("body", $._expr),
+    ),
+    _pattern: $ => choice(
+      $.string,
+      $.number,
+      seq("-", $.number),
+      $.ident,
+      $.bool,
+      $["null"],
+    ),
+
     expr_function: $ => seq(
       field("args", $.function_args),
       "=>",
@@ -171,6 +212,7 @@ module.exports = grammar({
       $._expr_term,
       $.expr_call,
       $.expr_index,
+      $.expr_slice,
       $.expr_field,
     ),
     expr_call: $ => seq(
@@ -185,6 +227,14 @@ module.exports = grammar({
       field("index", $._expr),
       "]",
     ),
+    expr_slice: $ => seq(
+      field("collection", $._expr_not_op),
+      "[",
+      field("start", optional($._expr)),
+      ":",
+      field("end", optional($._expr)),
+      "]",
+    ),
     expr_field: $ => seq(
       field("inner", $._expr_not_op),
       ".",
@@ -207,14 +257,14 @@ module.exports = grammar({
       $.bool,
       $["null"],
     ),
-    expr_term_braces:   $ => seq("{", optional($._seqs), "}"),
+    expr_term_braces:   $ => seq("{", optional(choice($._seqs, ":")), "}"),
     expr_term_brackets: $ => seq("[", optional($._seqs), "]"),
     expr_term_parens:   $ => seq("(", $._expr, ")"),
 
     _stmt: $ => choice($.stmt_let, $.stmt_assert, $.stmt_trace),
     stmt_let: $ => seq(
       "let",
-      field("ident", $.ident),
+      field("ident", $._binding),
       optional(seq(":", field("type", $._type_expr))),
       "=",
       field("value", $._expr),
@@ -274,13 +324,25 @@ module.exports = grammar({
       field("body", $._seq),
     ),
 
-    // One or more identifiers separated by comma, no trailing comma allowed.
-    _idents: $ => seq($.ident, repeat(seq(",", $.ident))),
+    // One or more bindings separated by comma, no trailing comma allowed.
+    _idents: $ => seq($._binding, repeat(seq(",", $._binding))),
+
+    // A binding is either a name, or a list of bindings that unpacks a list.
+    _binding: $ => choice($.ident, $.binding_list),
+    binding_list: $ => seq(
+      "[",
+      optional(seq($._binding, repeat(seq(",", $._binding)), optional(","))),
+      "]",
+    ),
 
     _type_expr: $ => choice(
+      $._type_simple,
+      $.type_union,
+      $.type_function,
+    ),
+    _type_simple: $ => choice(
       $.type_term,
       $.type_apply,
-      $.type_function,
     ),
     type_term: $ => $.ident,
     type_apply: $ => seq(
@@ -297,6 +359,11 @@ module.exports = grammar({
       field("result", $._type_expr),
     ),
 
+    type_union: $ => seq(
+      field("member", $._type_simple),
+      repeat1(seq("|", field("member", $._type_simple))),
+    ),
+
     _types: $ => seq(
       $._type_expr,
       repeat(seq(",", $._type_expr)),
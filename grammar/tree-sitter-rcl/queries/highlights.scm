@@ -1,6 +1,6 @@
 ["for" "in"] @keyword
 ["assert" "trace"] @keyword
-["if" "else"] @keyword
+["if" "else" "match"] @keyword
 ["import"] @keyword
 ["let"] @keyword
 (unop_keyword) @keyword
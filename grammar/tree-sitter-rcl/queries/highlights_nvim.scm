@@ -1,6 +1,6 @@
 ["for" "in"] @keyword.repeat
 ["assert" "trace"] @keyword.debug
-["if" "else"] @keyword.conditional
+["if" "else" "match"] @keyword.conditional
 ["import"] @keyword.import
 ["let"] @keyword.storage
 (unop_keyword) @keyword.operator
@@ -9,6 +9,7 @@ use pyo3::prelude::*;
 use rcl::cli::Target;
 use rcl::error::Result;
 use rcl::loader::{Loader, SandboxMode};
+use rcl::markup::{MarkupMode, Theme};
 use rcl::runtime::{self, Value};
 use rcl::source::DocId;
 use rcl::tracer::StderrTracer;
@@ -16,9 +17,10 @@ use rcl::typecheck;
 
 fn evaluate<F: FnOnce(&mut Loader) -> Result<DocId>>(load: F) -> Result<Value> {
     let mut loader = Loader::new();
-    loader.initialize_filesystem(SandboxMode::Workdir, None)?;
+    loader.initialize_filesystem(SandboxMode::Workdir, None, &[])?;
     let doc = load(&mut loader)?;
-    let mut tracer = StderrTracer::new(None);
+    let stderr = std::io::stderr();
+    let mut tracer = StderrTracer::new(MarkupMode::default_for_fd(&stderr, Theme::Dark));
     let mut type_env = typecheck::prelude();
     let mut value_env = runtime::prelude();
     loader.evaluate(&mut type_env, &mut value_env, doc, &mut tracer)
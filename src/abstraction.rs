@@ -13,13 +13,23 @@
 //! * Converting literals in the source code into values in the runtime.
 //! * Removing syntactical differences (e.g. converting `k = v;` into `"k": v`).
 
+use std::rc::Rc;
+
 use crate::ast::{
-    CallArg, Expr as AExpr, Expr, FormatFragment, Seq as ASeq, Stmt as AStmt, Type as AType, Yield,
+    Binding as ABinding, CallArg, Expr as AExpr, Expr, FormatFragment, MatchArm as AMatchArm,
+    Pattern as APattern, Seq as ASeq, Stmt as AStmt, Type as AType, Yield,
+};
+use crate::cst::{
+    Binding as CBinding, Chain, Expr as CExpr, MatchArm as CMatchArm, Pattern as CPattern,
+    Seq as CSeq, Stmt as CStmt, StringPart, Type as CType,
 };
-use crate::cst::{Chain, Expr as CExpr, Seq as CSeq, Stmt as CStmt, StringPart, Type as CType};
+use crate::env::Env;
 use crate::error::{IntoError, Result};
-use crate::lexer::QuoteStyle;
-use crate::source::Span;
+use crate::lexer::{self, QuoteStyle};
+use crate::parser;
+use crate::runtime::BuiltinMethod;
+use crate::source::{DocId, Span};
+use crate::stdlib;
 use crate::string;
 
 /// Abstract an expression.
@@ -27,6 +37,11 @@ pub fn abstract_expr(input: &str, expr: &CExpr) -> Result<AExpr> {
     Abstractor::new(input).expr(expr)
 }
 
+/// Abstract a type expression in the given document.
+pub fn abstract_type(input: &str, type_: &CType) -> Result<AType> {
+    Abstractor::new(input).type_expr(type_)
+}
+
 /// The abstractor can convert CST nodes to AST nodes for a given document.
 struct Abstractor<'a> {
     input: &'a str,
@@ -103,18 +118,31 @@ impl<'a> Abstractor<'a> {
         }
     }
 
+    /// Abstract the left-hand side of a let-binding or loop variable.
+    pub fn binding(&self, binding: &CBinding) -> ABinding {
+        match binding {
+            CBinding::Ident(span) => ABinding::Ident {
+                span: *span,
+                ident: span.resolve(self.input).into(),
+            },
+            CBinding::List { span, elements } => ABinding::List {
+                span: *span,
+                elements: elements.iter().map(|b| self.binding(b)).collect(),
+            },
+        }
+    }
+
     /// Abstract a statement.
     pub fn stmt(&self, stmt: &CStmt) -> Result<AStmt> {
         let result = match stmt {
             CStmt::Let {
-                ident,
+                binding,
                 type_,
                 value_span,
                 value,
                 ..
             } => AStmt::Let {
-                ident_span: *ident,
-                ident: ident.resolve(self.input).into(),
+                binding: self.binding(binding),
                 type_: match type_ {
                     None => None,
                     Some(t) => Some(Box::new(self.type_expr(t)?)),
@@ -173,13 +201,32 @@ impl<'a> Abstractor<'a> {
                 path: Box::new(self.expr(path)?),
             },
 
-            CExpr::BraceLit { open, elements, .. } => AExpr::BraceLit {
+            CExpr::BraceLit { open, elements, .. } => {
+                // In a collection that contains key-value pairs, a bare
+                // identifier `x` is shorthand for `x = x`. Without key-value
+                // pairs, `{ x, y }` remains a set.
+                let is_dict = elements.elements.iter().any(|e| !e.inner.is_inner_elem());
+                AExpr::BraceLit {
+                    open: *open,
+                    elements: elements
+                        .elements
+                        .iter()
+                        .map(|elem| match &elem.inner {
+                            CSeq::Elem { value, .. } if is_dict => match value.as_ref() {
+                                CExpr::Var(span) => Ok(self.seq_shorthand(*span)),
+                                _ => self.seq(&elem.inner),
+                            },
+                            seq => self.seq(seq),
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                }
+            }
+
+            // The empty dict does not need the typechecker to decide whether
+            // it is a set or a dict, so it becomes a `DictLit` right away.
+            CExpr::EmptyDictLit { open, .. } => AExpr::DictLit {
                 open: *open,
-                elements: elements
-                    .elements
-                    .iter()
-                    .map(|elem| self.seq(&elem.inner))
-                    .collect::<Result<Vec<_>>>()?,
+                elements: Vec::new(),
             },
 
             CExpr::BracketLit { open, elements, .. } => AExpr::BracketLit {
@@ -252,6 +299,24 @@ impl<'a> Abstractor<'a> {
                 body_else: Box::new(self.expr(else_body)?),
             },
 
+            CExpr::Match {
+                match_span,
+                scrutinee_span,
+                scrutinee,
+                arms,
+            } => {
+                let mut result_arms = Vec::with_capacity(arms.elements.len());
+                for arm in arms.elements.iter() {
+                    result_arms.push(self.match_arm(&arm.inner)?);
+                }
+                AExpr::Match {
+                    match_span: *match_span,
+                    scrutinee_span: *scrutinee_span,
+                    scrutinee: Box::new(self.expr(scrutinee)?),
+                    arms: result_arms,
+                }
+            }
+
             CExpr::Var(span) => AExpr::Var {
                 span: *span,
                 ident: span.resolve(self.input).into(),
@@ -311,6 +376,44 @@ impl<'a> Abstractor<'a> {
         Ok(result)
     }
 
+    /// Abstract an arm of a match expression.
+    fn match_arm(&self, arm: &CMatchArm) -> Result<AMatchArm> {
+        let pattern = match &arm.pattern {
+            CPattern::Wildcard(..) => APattern::Wildcard,
+            CPattern::Literal { span, value } => match self.expr(value)? {
+                AExpr::Format(..) => {
+                    return span
+                        .error("Format strings cannot be used as patterns.")
+                        .with_help("Patterns must be literals, try a string without holes.")
+                        .err()
+                }
+                value => APattern::Literal(value),
+            },
+        };
+        let result = AMatchArm {
+            pattern_span: arm.pattern.span(),
+            pattern,
+            body_span: arm.body_span,
+            body: self.expr(&arm.body)?,
+        };
+        Ok(result)
+    }
+
+    /// Abstract a record field shorthand `x` into `x = x`.
+    fn seq_shorthand(&self, span: Span) -> ASeq {
+        let ident = span.resolve(self.input);
+        ASeq::Yield(Yield::Assoc {
+            op_span: span,
+            key_span: span,
+            value_span: span,
+            key: Box::new(AExpr::StringLit(ident.into())),
+            value: Box::new(AExpr::Var {
+                span,
+                ident: ident.into(),
+            }),
+        })
+    }
+
     /// Abstract a sequence element.
     pub fn seq(&self, seq: &CSeq) -> Result<ASeq> {
         let result = match seq {
@@ -358,20 +461,17 @@ impl<'a> Abstractor<'a> {
             },
 
             CSeq::For {
-                idents,
+                bindings,
                 collection_span,
                 collection,
                 body,
             } => ASeq::For {
-                idents_span: idents
+                bindings_span: bindings
                     .iter()
-                    .copied()
+                    .map(|b| b.span())
                     .reduce(|x, y| x.union(y))
-                    .expect("Parser should have produced at least one ident."),
-                idents: idents
-                    .iter()
-                    .map(|span| span.resolve(self.input).into())
-                    .collect(),
+                    .expect("Parser should have produced at least one binding."),
+                bindings: bindings.iter().map(|b| self.binding(b)).collect(),
                 collection_span: *collection_span,
                 collection: Box::new(self.expr(collection)?),
                 body: Box::new(self.seq(&body.inner)?),
@@ -436,6 +536,26 @@ impl<'a> Abstractor<'a> {
                 index_span: *index_span,
                 index: Box::new(self.expr(index)?),
             },
+
+            Chain::Slice {
+                open,
+                close,
+                start,
+                end,
+            } => AExpr::Slice {
+                open: *open,
+                close: *close,
+                collection_span: inner_span,
+                collection: Box::new(inner),
+                start: match start {
+                    None => None,
+                    Some((span, expr)) => Some((*span, Box::new(self.expr(expr)?))),
+                },
+                end: match end {
+                    None => None,
+                    Some((span, expr)) => Some((*span, Box::new(self.expr(expr)?))),
+                },
+            },
         };
 
         Ok(result)
@@ -466,7 +586,397 @@ impl<'a> Abstractor<'a> {
                     .collect::<Result<Box<_>>>()?,
                 result: Box::new(self.type_expr(result)?),
             },
+            // A union written with `|` is the same as applying `Union`.
+            CType::Union { span, members } => AType::Apply {
+                span: *span,
+                name: "Union".into(),
+                args: members
+                    .iter()
+                    .map(|member| self.type_expr(member))
+                    .collect::<Result<Box<_>>>()?,
+            },
         };
         Ok(result)
     }
 }
+
+/// A candidate for autocompletion.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Completion {
+    /// A variable that is in scope at the cursor.
+    Variable(Rc<str>),
+    /// A key of a dict literal that can be accessed as a field.
+    Field(Rc<str>),
+    /// A builtin method of the receiver.
+    Method(&'static BuiltinMethod),
+}
+
+impl Completion {
+    /// The text to insert for this completion.
+    pub fn name(&self) -> &str {
+        match self {
+            Completion::Variable(name) => name,
+            Completion::Field(name) => name,
+            Completion::Method(method) => match method.name.split_once('.') {
+                Some((_type_name, method_name)) => method_name,
+                None => method.name,
+            },
+        }
+    }
+}
+
+/// What we know about the value of an expression without evaluating it.
+///
+/// This is purely syntactic, it only needs to be good enough to suggest fields
+/// and methods.
+#[derive(Clone, Debug)]
+enum Shape {
+    Unknown,
+    Dict(Vec<Rc<str>>),
+    List,
+    Set,
+    String,
+}
+
+/// The placeholder that we put at the cursor to be able to parse the document.
+const COMPLETION_PLACEHOLDER: &str = "__rcl_completion__";
+
+/// Return completion candidates for the identifier at byte offset `cursor`.
+///
+/// After a `.`, the candidates are the fields and methods of the expression
+/// before it, insofar as they can be determined without evaluating anything.
+/// Anywhere else, they are the variables in scope. Only candidates that start
+/// with the partial identifier before the cursor are returned.
+pub fn completions(input: &str, cursor: usize) -> Vec<Completion> {
+    if cursor > input.len() || !input.is_char_boundary(cursor) {
+        return Vec::new();
+    }
+
+    // Find the partial identifier that ends at the cursor, and replace it with
+    // a placeholder. This makes a document that ends in e.g. `xs.` parse.
+    let before = &input[..cursor];
+    let prefix_len = before
+        .bytes()
+        .rev()
+        .take_while(|&ch| ch.is_ascii_alphanumeric() || ch == b'_' || ch == b'-')
+        .count();
+    let prefix_start = cursor - prefix_len;
+    let prefix = &input[prefix_start..cursor];
+    let patched = format!(
+        "{}{}{}",
+        &input[..prefix_start],
+        COMPLETION_PLACEHOLDER,
+        &input[cursor..]
+    );
+
+    let doc = DocId(0);
+    let expr = match lexer::lex(doc, &patched)
+        .and_then(|tokens| parser::parse(doc, &patched, &tokens))
+        .and_then(|(_span, cst)| abstract_expr(&patched, &cst))
+    {
+        Ok(expr) => expr,
+        Err(..) => return Vec::new(),
+    };
+
+    let mut env = Env::new();
+    env.push("std".into(), Shape::Unknown);
+    let mut result = match find_completions(&mut env, prefix_start, &expr) {
+        Some(candidates) => candidates,
+        None => return Vec::new(),
+    };
+    result.retain(|c| c.name().starts_with(prefix) && c.name() != COMPLETION_PLACEHOLDER);
+    result.sort_by(|a, b| a.name().cmp(b.name()));
+    result.dedup_by(|a, b| a.name() == b.name());
+    result
+}
+
+/// Determine the shape of an expression, as far as we can tell.
+fn shape_of(env: &mut Env<Shape>, expr: &Expr) -> Shape {
+    match expr {
+        Expr::BraceLit { elements, .. } | Expr::DictLit { elements, .. } => {
+            let mut keys = Vec::new();
+            let mut is_dict = elements.is_empty();
+            for seq in elements {
+                let mut seq = seq;
+                // Look through comprehensions to find out whether the
+                // collection is a dict, but only record keys at the top level.
+                let mut is_top_level = true;
+                loop {
+                    match seq {
+                        ASeq::Yield(Yield::Assoc { key, .. }) => {
+                            is_dict = true;
+                            if let (true, Expr::StringLit(k)) = (is_top_level, key.as_ref()) {
+                                keys.push(k.clone());
+                            }
+                            break;
+                        }
+                        ASeq::Yield(Yield::Elem { .. }) => break,
+                        ASeq::Stmt { body, .. }
+                        | ASeq::For { body, .. }
+                        | ASeq::If { body, .. } => {
+                            is_top_level = false;
+                            seq = body;
+                        }
+                    }
+                }
+            }
+            if is_dict {
+                Shape::Dict(keys)
+            } else {
+                Shape::Set
+            }
+        }
+        Expr::SetLit { .. } => Shape::Set,
+        Expr::BracketLit { .. } => Shape::List,
+        Expr::StringLit(..) | Expr::Format(..) => Shape::String,
+        Expr::Var { ident, .. } => env.lookup(ident).cloned().unwrap_or(Shape::Unknown),
+        Expr::Stmt { stmt, body, .. } => {
+            let ck = env.checkpoint();
+            if let AStmt::Let { binding, value, .. } = stmt {
+                let shape = shape_of(env, value);
+                push_binding_shape(env, binding, shape);
+            }
+            let shape = shape_of(env, body);
+            env.pop(ck);
+            shape
+        }
+        Expr::CheckType { body, .. } => shape_of(env, body),
+        _ => Shape::Unknown,
+    }
+}
+
+/// Walk the expression to find the placeholder, return candidates when found.
+fn find_completions(env: &mut Env<Shape>, target: usize, expr: &Expr) -> Option<Vec<Completion>> {
+    match expr {
+        Expr::Var { span, .. } if span.start() == target => {
+            let variables = env
+                .iter()
+                .map(|(ident, _shape)| Completion::Variable(ident.0.clone()))
+                .collect();
+            Some(variables)
+        }
+        Expr::Field {
+            inner, field_span, ..
+        } if field_span.start() == target => {
+            let methods = match shape_of(env, inner) {
+                Shape::Dict(keys) => {
+                    let mut result: Vec<_> = keys.into_iter().map(Completion::Field).collect();
+                    result.extend(stdlib::DICT_METHODS.iter().map(|m| Completion::Method(m)));
+                    return Some(result);
+                }
+                Shape::List => stdlib::LIST_METHODS,
+                Shape::Set => stdlib::SET_METHODS,
+                Shape::String => stdlib::STRING_METHODS,
+                Shape::Unknown => &[],
+            };
+            Some(methods.iter().map(|m| Completion::Method(m)).collect())
+        }
+        Expr::Stmt { stmt, body, .. } => {
+            let ck = env.checkpoint();
+            let result = find_completions_stmt(env, target, stmt)
+                .or_else(|| find_completions(env, target, body));
+            env.pop(ck);
+            result
+        }
+        Expr::Import { path, .. } => find_completions(env, target, path),
+        Expr::BraceLit { elements, .. }
+        | Expr::BracketLit { elements, .. }
+        | Expr::SetLit { elements, .. }
+        | Expr::DictLit { elements, .. } => elements
+            .iter()
+            .find_map(|seq| find_completions_seq(env, target, seq)),
+        Expr::Format(fragments) => fragments
+            .iter()
+            .find_map(|fragment| find_completions(env, target, &fragment.body)),
+        Expr::IfThenElse {
+            condition,
+            body_then,
+            body_else,
+            ..
+        } => find_completions(env, target, condition)
+            .or_else(|| find_completions(env, target, body_then))
+            .or_else(|| find_completions(env, target, body_else)),
+        Expr::Match {
+            scrutinee, arms, ..
+        } => find_completions(env, target, scrutinee).or_else(|| {
+            arms.iter()
+                .find_map(|arm| find_completions(env, target, &arm.body))
+        }),
+        Expr::Field { inner, .. } => find_completions(env, target, inner),
+        Expr::Function { args, body, .. } => {
+            let ck = env.checkpoint();
+            for (_span, arg) in args {
+                env.push(arg.clone(), Shape::Unknown);
+            }
+            let result = find_completions(env, target, body);
+            env.pop(ck);
+            result
+        }
+        Expr::Call { function, args, .. } => {
+            find_completions(env, target, function).or_else(|| {
+                args.iter()
+                    .find_map(|arg| find_completions(env, target, &arg.value))
+            })
+        }
+        Expr::Index {
+            collection, index, ..
+        } => find_completions(env, target, collection)
+            .or_else(|| find_completions(env, target, index)),
+        Expr::Slice {
+            collection,
+            start,
+            end,
+            ..
+        } => find_completions(env, target, collection).or_else(|| {
+            start
+                .iter()
+                .chain(end.iter())
+                .find_map(|(_span, bound)| find_completions(env, target, bound))
+        }),
+        Expr::UnOp { body, .. } => find_completions(env, target, body),
+        Expr::BinOp { lhs, rhs, .. } => {
+            find_completions(env, target, lhs).or_else(|| find_completions(env, target, rhs))
+        }
+        Expr::CheckType { body, .. } | Expr::TypedFunction { body, .. } => {
+            find_completions(env, target, body)
+        }
+        Expr::NullLit
+        | Expr::BoolLit(..)
+        | Expr::StringLit(..)
+        | Expr::IntegerLit(..)
+        | Expr::Var { .. } => None,
+    }
+}
+
+/// Bring the names of a binding in scope.
+///
+/// Only a plain name gets the shape of the value, we don't know the shape of
+/// the elements that a list binding unpacks.
+fn push_binding_shape(env: &mut Env<Shape>, binding: &ABinding, shape: Shape) {
+    match binding {
+        ABinding::Ident { ident, .. } => {
+            env.push(ident.clone(), shape);
+        }
+        ABinding::List { .. } => binding.for_each_ident(&mut |ident| {
+            env.push(ident.clone(), Shape::Unknown);
+        }),
+    }
+}
+
+/// Look for the placeholder in a statement, and bring any let binding in scope.
+fn find_completions_stmt(
+    env: &mut Env<Shape>,
+    target: usize,
+    stmt: &AStmt,
+) -> Option<Vec<Completion>> {
+    match stmt {
+        AStmt::Let { binding, value, .. } => {
+            let result = find_completions(env, target, value);
+            let shape = shape_of(env, value);
+            push_binding_shape(env, binding, shape);
+            result
+        }
+        AStmt::Assert {
+            condition, message, ..
+        } => find_completions(env, target, condition)
+            .or_else(|| find_completions(env, target, message)),
+        AStmt::Trace { message, .. } => find_completions(env, target, message),
+    }
+}
+
+/// Look for the placeholder in a comprehension.
+fn find_completions_seq(
+    env: &mut Env<Shape>,
+    target: usize,
+    seq: &ASeq,
+) -> Option<Vec<Completion>> {
+    match seq {
+        ASeq::Yield(Yield::Elem { value, .. }) => find_completions(env, target, value),
+        ASeq::Yield(Yield::Assoc { key, value, .. }) => {
+            find_completions(env, target, key).or_else(|| find_completions(env, target, value))
+        }
+        ASeq::Stmt { stmt, body } => {
+            let ck = env.checkpoint();
+            let result = find_completions_stmt(env, target, stmt)
+                .or_else(|| find_completions_seq(env, target, body));
+            env.pop(ck);
+            result
+        }
+        ASeq::For {
+            bindings,
+            collection,
+            body,
+            ..
+        } => find_completions(env, target, collection).or_else(|| {
+            let ck = env.checkpoint();
+            for binding in bindings {
+                push_binding_shape(env, binding, Shape::Unknown);
+            }
+            let result = find_completions_seq(env, target, body);
+            env.pop(ck);
+            result
+        }),
+        ASeq::If {
+            condition, body, ..
+        } => find_completions(env, target, condition)
+            .or_else(|| find_completions_seq(env, target, body)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::completions;
+
+    fn names(input_with_cursor: &str) -> Vec<String> {
+        let cursor = input_with_cursor.find('|').unwrap();
+        let input = input_with_cursor.replacen('|', "", 1);
+        completions(&input, cursor)
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn completions_after_dot_on_dict_literal() {
+        let result = names(r#"{ name = "pear", color = "green" }.|"#);
+        for expected in ["keys", "values", "get", "contains", "name", "color"] {
+            assert!(
+                result.iter().any(|n| n == expected),
+                "Missing {expected}: {result:?}"
+            );
+        }
+        // Methods of other types should not be listed.
+        assert!(!result.iter().any(|n| n == "map"));
+    }
+
+    #[test]
+    fn completions_after_dot_on_empty_dict_literal() {
+        let result = names("{:}.|");
+        assert!(
+            result.iter().any(|n| n == "keys"),
+            "Missing keys: {result:?}"
+        );
+        assert!(!result.iter().any(|n| n == "map"));
+    }
+
+    #[test]
+    fn completions_after_dot_filter_by_prefix() {
+        assert_eq!(
+            names(r#"let xs = [1, 2]; xs.f|"#),
+            ["filter", "flat_map", "fold"]
+        );
+        assert_eq!(names(r#""abc".to_u|"#), ["to_uppercase"]);
+    }
+
+    #[test]
+    fn completions_in_let_body() {
+        assert_eq!(names("let answer = 42;\n|"), ["answer", "std"]);
+        assert_eq!(
+            names("let answer = 42;\nlet f = x => a|;\nf(1)"),
+            ["answer"]
+        );
+        // The binding is not in scope in its own definition.
+        assert_eq!(names("let answer = a|; 42"), Vec::<String>::new());
+        assert_eq!(names("[for item in [1]: i|]"), ["item"]);
+    }
+}
@@ -62,8 +62,7 @@ pub struct FormatFragment {
 pub enum Stmt {
     /// A let-binding.
     Let {
-        ident_span: Span,
-        ident: Ident,
+        binding: Binding,
         type_: Option<Box<Type>>,
         value_span: Span,
         value: Box<Expr>,
@@ -85,6 +84,56 @@ pub enum Stmt {
     },
 }
 
+/// The left-hand side of a let-binding, or a loop variable.
+#[derive(Clone, Debug)]
+pub enum Binding {
+    /// Bind the value to a single name.
+    Ident { span: Span, ident: Ident },
+
+    /// Unpack a list of exactly this many elements.
+    List { span: Span, elements: Vec<Binding> },
+}
+
+impl Binding {
+    pub fn span(&self) -> Span {
+        match self {
+            Binding::Ident { span, .. } => *span,
+            Binding::List { span, .. } => *span,
+        }
+    }
+
+    /// Call `f` for every name that this binding binds, in order.
+    pub fn for_each_ident<F: FnMut(&Ident)>(&self, f: &mut F) {
+        match self {
+            Binding::Ident { ident, .. } => f(ident),
+            Binding::List { elements, .. } => {
+                for element in elements {
+                    element.for_each_ident(f);
+                }
+            }
+        }
+    }
+}
+
+/// A pattern in an arm of a match expression.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// The wildcard `_`, which matches any value.
+    Wildcard,
+
+    /// A literal, which matches values equal to it.
+    Literal(Expr),
+}
+
+/// An arm `pattern => body` of a match expression.
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern_span: Span,
+    pub pattern: Pattern,
+    pub body_span: Span,
+    pub body: Expr,
+}
+
 /// An argument provided to a function call.
 #[derive(Clone, Debug)]
 pub struct CallArg<T> {
@@ -152,6 +201,14 @@ pub enum Expr {
         body_else: Box<Expr>,
     },
 
+    /// Choose the first arm whose pattern matches the scrutinee.
+    Match {
+        match_span: Span,
+        scrutinee_span: Span,
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+
     /// Access a variable.
     Var { span: Span, ident: Ident },
 
@@ -196,6 +253,18 @@ pub enum Expr {
         index: Box<Expr>,
     },
 
+    /// Take a slice of a collection as `collection[start:end]`.
+    Slice {
+        /// The opening bracket.
+        open: Span,
+        /// The closing bracket.
+        close: Span,
+        collection_span: Span,
+        collection: Box<Expr>,
+        start: Option<(Span, Box<Expr>)>,
+        end: Option<(Span, Box<Expr>)>,
+    },
+
     /// Apply a unary operator.
     UnOp {
         op_span: Span,
@@ -273,10 +342,10 @@ pub enum Seq {
     /// because associations are not first-class values.
     Stmt { stmt: Stmt, body: Box<Seq> },
 
-    /// Loop over the collection, binding the values to `idents`.
+    /// Loop over the collection, binding the values to `bindings`.
     For {
-        idents_span: Span,
-        idents: Vec<Ident>,
+        bindings_span: Span,
+        bindings: Vec<Binding>,
         collection_span: Span,
         collection: Box<Expr>,
         body: Box<Seq>,
@@ -12,8 +12,9 @@ use std::str::FromStr;
 use crate::cli_utils::{match_option, parse_option, Arg, ArgIter};
 use crate::cmd_build::BuildMode;
 use crate::error::{Error, Result};
-use crate::loader::SandboxMode;
-use crate::markup::{Markup, MarkupMode};
+use crate::fmt_rcl::IntBase;
+use crate::loader::{SandboxMode, SearchPath};
+use crate::markup::{ColorLevel, Markup, MarkupMode, Theme};
 use crate::pprint::{concat, Doc};
 
 const USAGE_MAIN: &str = r#"
@@ -27,6 +28,7 @@ Commands:
   evaluate     Evaluate a document to an output format.
   format       Auto-format an RCL document.
   highlight    Print a document with syntax highlighting.
+  lsp          Run a language server that communicates over stdio.
   query        Evaluate an expression against an input document.
 
 Command shorthands:
@@ -42,20 +44,28 @@ Global options:
   --version             Show version.
   --color <mode>        Set how output is colored, see modes below.
   -C --directory <dir>  Change the working directory.
+  --theme <theme>       Colors to use for ANSI output, 'dark' or 'light'.
+                        Defaults to 'dark'.
 
 Color modes:
-  ansi    Always color output using ANSI escape codes.
-  auto    Use ANSI if the output file is a TTY and the NO_COLOR environment
-          variable is not set to a non-empty string. This is the default.
-  html    Output HTML tags in the same style as Pandoc.
-  none    Do not color output at all.
+  ansi       Always color output using the 16 basic ANSI colors.
+  ansi256    Always color output using the 256-color ANSI palette.
+  auto       Use ANSI if the output file is a TTY and the NO_COLOR environment
+             variable is not set to a non-empty string. This is the default.
+             The color depth is detected from COLORTERM and TERM.
+  html       Output HTML tags in the same style as Pandoc.
+  none       Do not color output at all.
+  truecolor  Always color output using 24-bit ANSI colors.
+
+The modes 'always' and 'never' are accepted as aliases for 'ansi' and 'none'.
 "#;
 
 const USAGE_BUILD: &str = r##"
 RCL -- A reasonable configuration language.
 
 Usage:
-  rcl [<options>] build [<buildfile>]
+  rcl [<options>] build [<options>] [<buildfile>]
+  rcl [<options>] build [<options>] --tree <indir> <outdir>
 
 The 'build' command writes formatted values to files. It can be used to update
 many generated files in one command, similar to a build tool like Make or Ninja,
@@ -63,16 +73,39 @@ but with the build targets specified in RCL rather than a makefile. The build
 file is an RCL document that should evaluate to a dict that maps output file
 paths to targets. Targets are dicts with fields as described below.
 
+With --tree, the 'build' command instead evaluates every .rcl file in <indir>
+and its subdirectories, and writes the result to the same relative path in
+<outdir>, with the extension of the output format. Files whose name starts with
+an underscore are libraries to import, they are not evaluated by themselves.
+When some files fail to evaluate, the others are still written, and the command
+exits with a nonzero exit code after reporting all errors.
+
 Arguments:
   <buildfile>       The file with build targets to process, or '-' for stdin.
                     Defaults to 'build.rcl' when no file is specified.
+  <indir>           With --tree, the directory with .rcl files to evaluate.
+  <outdir>          With --tree, the directory to write outputs to.
 
 Options:
   --dry-run         Print what files we would write to stdout, instead of
                     writing to the file system, which would overwrite existing
                     files.
+  -f --format <format>
+                    With --tree, the output format, as for 'rcl evaluate
+                    --format'. Defaults to 'rcl'.
+  -I --include <dir>
+                    Search <dir> for imports that do not exist relative to the
+                    importing file, see 'rcl evaluate --help'.
   --sandbox <mode>  Sandboxing mode, see 'rcl evaluate --help' for an
                     explanation of the modes. Defaults to 'workdir'.
+  --strict          Reject an empty '{}' that could be a dict or a set,
+                    if-else branches with incompatible types, and duplicate
+                    set elements, see 'rcl evaluate --help'.
+  --tree            Evaluate a directory tree instead of a build file, see
+                    above.
+  -w --width <width>
+                    With --tree, the target width for formatting, as for 'rcl
+                    evaluate --width'. Defaults to 80.
 
 See also --help for global options.
 
@@ -119,25 +152,64 @@ result.
 
 Arguments:
   <file>     The input file to process, or '-' for stdin. Defaults to stdin when
-             no file is specified.
+             no file is specified. 'evaluate' accepts multiple files, and prints
+             their results in order, for formats ndjson, raw, and yaml-stream,
+             or with --concat.
   <query>    An RCL expression to evaluate. The result of evaluating the input
              file is bound to the variable 'input'.
 
 Options:
   --banner <message>       Prepend the message to the output. This can be useful
                            to add headings or comments to generated files.
+  --canonical              Output in a canonical form that does not depend on
+                           the formatting of the input, nor on --width,
+                           --no-trailing-newline, or --rcl-int-base, for
+                           reproducible output. Cannot be combined with
+                           --banner, or with --color other than none.
+  --concat                 With multiple files, require every file to evaluate
+                           to a list, and output one list with all elements.
+  --fail-fast              With multiple files, stop at the first file that
+                           fails, instead of reporting the errors of all files.
   -f --format <format>     Output format, see below for the available formats.
                            Defaults to 'rcl'.
+  -I --include <dir>       Search <dir> for imports that do not exist relative to
+                           the importing file. Can be repeated, directories are
+                           searched in order, followed by those in RCL_PATH.
   -o --output <outfile>    Write to the given file instead of stdout.
   --output-depfile <file>  Write all dependencies that were loaded during
                            evaluation to <file> in Makefile syntax. This can be
                            used by e.g. the Ninja build system.
+  --rcl-int-base <base>    Print integers in base 2, 10, or 16 in the 'rcl'
+                           output format. Defaults to 10.
   --sandbox <mode>         Sandboxing mode, see below. Defaults to 'workdir'.
+  --schema <file>          Check the result against the type in <file>, and
+                           report every value that does not fit, see below.
+  --stream                 With --format=json, write the elements of a top-level
+                           list to stdout as they are evaluated. When evaluation
+                           fails halfway, the output is incomplete.
+  --strict                 Reject an empty '{}' when it is not clear whether it
+                           should be a dict or a set. Write '{:}' for an empty
+                           dict, or add a type annotation for an empty set.
+                           Also reject if-else branches with incompatible types
+                           when no type annotation allows both, and sets that
+                           contain the same element more than once.
+  --[no-]trailing-newline  Whether to end the output in a newline. Defaults to
+                           ending in a newline.
+  --watch                  Evaluate again whenever the input file, or any file
+                           that it imports, changes.
   -w --width <width>       Target width for pretty-printing, must be an integer.
                            Defaults to 80.
 
 Output format:
+  csv           If the document is a list of dicts that all have the same keys,
+                output a CSV table with a header row and one row per dict.
+  env           If the document is a dict of strings, numbers, or booleans, output
+                every key-value pair as a KEY=value line, quoted for use in a
+                shell or dotenv file.
   json          Output pretty-printed JSON.
+  ndjson        If the document is a list, output every element as compact JSON
+                on its own line. Top-level values other than lists are not valid
+                for this format.
   raw           If the document is a string, output the string itself. If the
                 document is a list or set of strings, output each string on its
                 own line.
@@ -148,10 +220,36 @@ Output format:
                 Top-level values other than lists are not valid for this format.
 
 Sandboxing modes:
-  workdir       Only allow importing files inside the working directory and
-                subdirectories.
+  workdir       Only allow importing files inside the working directory, the
+                search paths, and their subdirectories.
   unrestricted  Grant unrestricted filesystem access, allow importing any file.
 
+Schema files:
+  A schema file contains a single type expression, for example
+  'Dict[String, List[Int]]'. Every element of a list or set, and every key and
+  value of a dict, is checked against the type, and each value that does not
+  fit is reported with its path. Dict types constrain all keys and values alike,
+  so there are no required or unexpected fields: a key that does not fit the key
+  type is reported as a mismatch, a dict without a particular key is accepted.
+
+See also --help for global options.
+"#;
+
+const USAGE_HIGHLIGHT: &str = r#"
+RCL -- A reasonable configuration language.
+
+Usage:
+  rcl [<options>] highlight [<options>] [<file>]
+
+The 'highlight' command prints the input document with syntax highlighting.
+
+Arguments:
+  <file>        The input file to highlight, or '-' for stdin. Defaults to stdin
+                when no file is specified.
+
+Options:
+  -n --line-numbers  Prefix every line with its line number.
+
 See also --help for global options.
 "#;
 
@@ -173,6 +271,8 @@ Options:
                          By default the formatted result is written to stdout.
   -o --output <outfile>  Write to the given file instead of stdout. This is
                          incompatible with --in-place.
+  --watch                Format again whenever the input file changes. Only
+                         supported when writing to stdout or --output.
   -w --width <width>     Target width in number of columns, must be an integer.
                          Defaults to 80.
 
@@ -189,14 +289,33 @@ pub struct GlobalOptions {
     /// call it.
     pub markup: Option<MarkupMode>,
 
+    /// The color theme to use for ANSI output.
+    pub theme: Theme,
+
     /// Alter the working directory for filesystem access.
     pub workdir: Option<String>,
+
+    /// Whether to run the command again when its input files change.
+    pub watch: bool,
+}
+
+impl GlobalOptions {
+    /// Return the markup mode to use for the given file descriptor.
+    ///
+    /// When `--color` was not set, this auto-detects whether to use color.
+    pub fn markup_for_fd<T: std::io::IsTerminal>(&self, fd: &T) -> MarkupMode {
+        self.markup
+            .unwrap_or_else(|| MarkupMode::default_for_fd(fd, self.theme))
+    }
 }
 
 /// The available output formats (JSON, RCL).
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum OutputFormat {
+    Csv,
+    Env,
     Json,
+    Ndjson,
     Raw,
     #[default]
     Rcl,
@@ -205,7 +324,7 @@ pub enum OutputFormat {
 }
 
 /// Options for commands that evaluate expressions.
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct EvalOptions {
     /// The format to output in.
     pub format: OutputFormat,
@@ -221,10 +340,64 @@ pub struct EvalOptions {
 
     /// A banner message to prepend to the output.
     pub banner: Option<String>,
+
+    /// Directories to search for imports, from `-I`, in order.
+    pub include: Vec<String>,
+
+    /// Whether the output ends in a newline.
+    pub trailing_newline: bool,
+
+    /// Whether to reject constructs that are accepted but often a mistake.
+    ///
+    /// These are an empty `{}` that could be a dict or a set, and if-else
+    /// branches with incompatible types.
+    pub strict: bool,
+
+    /// A file with a type expression that the result must fit.
+    pub schema: Option<String>,
+
+    /// The base to print integers in, for the `rcl` output format.
+    pub rcl_int_base: IntBase,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::default(),
+            sandbox: SandboxMode::default(),
+            output_depfile: None,
+            banner: None,
+            include: Vec::new(),
+            trailing_newline: true,
+            strict: false,
+            schema: None,
+            rcl_int_base: IntBase::Decimal,
+        }
+    }
+}
+
+impl EvalOptions {
+    /// Return the directories to search for imports.
+    ///
+    /// These are the directories passed with `-I`, followed by the entries of
+    /// the colon-separated `RCL_PATH` environment variable.
+    pub fn search_paths(&self) -> Vec<SearchPath> {
+        let mut result: Vec<SearchPath> = self
+            .include
+            .iter()
+            .cloned()
+            .map(SearchPath::Include)
+            .collect();
+        if let Ok(rcl_path) = std::env::var("RCL_PATH") {
+            let dirs = rcl_path.split(':').filter(|dir| !dir.is_empty());
+            result.extend(dirs.map(|dir| SearchPath::Env(dir.to_string())));
+        }
+        result
+    }
 }
 
 /// Options for commands that pretty-print their output.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StyleOptions {
     /// Target width (number of columns) to try to not exceed.
     pub width: u32,
@@ -237,7 +410,7 @@ impl Default for StyleOptions {
 }
 
 /// Input to act on.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Target {
     /// A file, selected explicitly.
     File(String),
@@ -255,7 +428,7 @@ pub enum Target {
 }
 
 /// For the `fmt` command, which documents to format, and in what mode.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum FormatTarget {
     Stdout { fname: Target },
     InPlace { fnames: Vec<Target> },
@@ -263,7 +436,7 @@ pub enum FormatTarget {
 }
 
 /// An output file to write results to.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum OutputTarget {
     /// Write to the given file.
     File(String),
@@ -272,18 +445,37 @@ pub enum OutputTarget {
 }
 
 /// The different subcommands supported by the main program.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Cmd {
     Build {
         eval_opts: EvalOptions,
         build_mode: BuildMode,
         fname: Target,
     },
+    BuildTree {
+        eval_opts: EvalOptions,
+        style_opts: StyleOptions,
+        build_mode: BuildMode,
+        input_dir: String,
+        output_dir: String,
+    },
     Evaluate {
         eval_opts: EvalOptions,
         style_opts: StyleOptions,
         fname: Target,
         output: OutputTarget,
+        /// Whether to write a top-level json list while evaluating it.
+        stream: bool,
+    },
+    EvaluateMany {
+        eval_opts: EvalOptions,
+        style_opts: StyleOptions,
+        fnames: Vec<Target>,
+        /// Whether to concatenate the lists that the files evaluate to.
+        concat: bool,
+        /// Whether to stop at the first file that fails.
+        fail_fast: bool,
+        output: OutputTarget,
     },
     Query {
         eval_opts: EvalOptions,
@@ -299,13 +491,35 @@ pub enum Cmd {
     },
     Highlight {
         fname: Target,
+        line_numbers: bool,
     },
+    Lsp,
     Help {
         usage: &'static str,
     },
     Version,
 }
 
+impl Cmd {
+    /// Return the inputs to watch for changes, for commands that support it.
+    ///
+    /// The files that the inputs import are not included, those are only known
+    /// after evaluation, see [`Loader::loaded_paths`](crate::loader::Loader::loaded_paths).
+    pub fn watch_targets(&self) -> Option<Vec<&Target>> {
+        let targets = match self {
+            Cmd::Evaluate { fname, .. } => vec![fname],
+            Cmd::EvaluateMany { fnames, .. } => fnames.iter().collect(),
+            Cmd::Query { fname, .. } => vec![fname],
+            Cmd::Format {
+                target: FormatTarget::Stdout { fname },
+                ..
+            } => vec![fname],
+            _ => return None,
+        };
+        Some(targets)
+    }
+}
+
 /// Parse the command line.
 pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
     let mut args = ArgIter::new(args);
@@ -319,7 +533,13 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
     let mut global_opts = GlobalOptions::default();
     let mut eval_opts = EvalOptions::default();
     let mut in_place = false;
+    let mut line_numbers = false;
+    let mut canonical = false;
     let mut check = false;
+    let mut concat = false;
+    let mut fail_fast = false;
+    let mut stream = false;
+    let mut is_tree = false;
     let mut is_version = false;
     let mut targets: Vec<Target> = Vec::new();
     let mut output = OutputTarget::Stdout;
@@ -340,9 +560,13 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 global_opts.markup = match_option! {
                     args: arg,
                     "auto" => None,
-                    "ansi" => Some(MarkupMode::Ansi),
+                    "ansi" => Some(MarkupMode::Ansi(ColorLevel::Ansi16, Theme::Dark)),
+                    "ansi256" => Some(MarkupMode::Ansi(ColorLevel::Ansi256, Theme::Dark)),
+                    "always" => Some(MarkupMode::Ansi(ColorLevel::Ansi16, Theme::Dark)),
                     "html" => Some(MarkupMode::HtmlPandoc),
+                    "never" => Some(MarkupMode::None),
                     "none" => Some(MarkupMode::None),
+                    "truecolor" => Some(MarkupMode::Ansi(ColorLevel::TrueColor, Theme::Dark)),
                 }
             }
             Arg::Long("directory") | Arg::Short("C") => {
@@ -351,19 +575,35 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
                 };
             }
+            Arg::Long("concat") => {
+                concat = true;
+            }
             Arg::Long("dry-run") => {
                 build_mode = BuildMode::DryRun;
             }
+            Arg::Long("fail-fast") => {
+                fail_fast = true;
+            }
             Arg::Long("format") | Arg::Short("f") => {
                 eval_opts.format = match_option! {
                     args: arg,
+                    "csv" => OutputFormat::Csv,
+                    "env" => OutputFormat::Env,
                     "json" => OutputFormat::Json,
+                    "ndjson" => OutputFormat::Ndjson,
                     "raw" => OutputFormat::Raw,
                     "rcl" => OutputFormat::Rcl,
                     "toml" => OutputFormat::Toml,
                     "yaml-stream" => OutputFormat::YamlStream,
                 }
             }
+            Arg::Long("include") | Arg::Short("I") => {
+                let dir = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(x.to_string())
+                };
+                eval_opts.include.push(dir);
+            }
             Arg::Long("output") | Arg::Short("o") => {
                 output = parse_option! {
                     args: arg,
@@ -376,6 +616,14 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
                 };
             }
+            Arg::Long("rcl-int-base") => {
+                eval_opts.rcl_int_base = match_option! {
+                    args: arg,
+                    "2" => IntBase::Binary,
+                    "10" => IntBase::Decimal,
+                    "16" => IntBase::Hexadecimal,
+                }
+            }
             Arg::Long("sandbox") => {
                 eval_opts.sandbox = match_option! {
                     args: arg,
@@ -383,12 +631,49 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                     "unrestricted" => SandboxMode::Unrestricted,
                 }
             }
+            Arg::Long("schema") => {
+                eval_opts.schema = parse_option! {
+                    args: arg,
+                    |x: &str| Ok::<_, std::convert::Infallible>(Some(x.to_string()))
+                };
+            }
+            Arg::Long("stream") => {
+                stream = true;
+            }
+            Arg::Long("strict") => {
+                eval_opts.strict = true;
+            }
+            Arg::Long("trailing-newline") => {
+                eval_opts.trailing_newline = true;
+            }
+            Arg::Long("no-trailing-newline") => {
+                eval_opts.trailing_newline = false;
+            }
+            Arg::Long("tree") => {
+                is_tree = true;
+            }
+            Arg::Long("theme") => {
+                global_opts.theme = match_option! {
+                    args: arg,
+                    "dark" => Theme::Dark,
+                    "light" => Theme::Light,
+                }
+            }
+            Arg::Long("watch") => {
+                global_opts.watch = true;
+            }
             Arg::Long("width") | Arg::Short("w") => {
                 style_opts.width = parse_option! { args: arg, u32::from_str };
             }
+            Arg::Long("canonical") => {
+                canonical = true;
+            }
             Arg::Long("in-place") | Arg::Short("i") => {
                 in_place = true;
             }
+            Arg::Long("line-numbers") | Arg::Short("n") => {
+                line_numbers = true;
+            }
             Arg::Long("help") | Arg::Short("h") => {
                 is_version = false;
                 cmd_help = match cmd {
@@ -431,6 +716,9 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
             Arg::Plain("highlight") | Arg::Plain("h") if cmd.is_none() => {
                 cmd = Some("highlight");
             }
+            Arg::Plain("lsp") if cmd.is_none() => {
+                cmd = Some("lsp");
+            }
             Arg::Plain(fname) if cmd.is_some() => {
                 targets.push(Target::File(fname.to_string()));
             }
@@ -456,6 +744,38 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
         }
     }
 
+    // The theme can be given before or after --color, so only now that we
+    // have seen all options, we can apply it.
+    global_opts.markup = global_opts.markup.map(|m| m.with_theme(global_opts.theme));
+
+    // Canonical output should not depend on options that only affect the
+    // presentation, so --canonical overrides those, regardless of the order.
+    // A banner and color are part of the output, so we reject those instead
+    // of silently dropping them.
+    if canonical {
+        let has_color = matches!(
+            global_opts.markup,
+            Some(MarkupMode::Ansi(..) | MarkupMode::HtmlPandoc)
+        );
+        let conflict = if eval_opts.banner.is_some() {
+            Some("--banner")
+        } else if has_color {
+            Some("--color")
+        } else {
+            None
+        };
+        if let Some(option) = conflict {
+            let err = concat! {
+                "Option '" Doc::highlight(option) "' cannot be combined with '"
+                Doc::highlight("--canonical") "'. See --help for usage."
+            };
+            return Error::new(err).err();
+        }
+        style_opts = StyleOptions::default();
+        eval_opts.trailing_newline = true;
+        eval_opts.rcl_int_base = IntBase::Decimal;
+    }
+
     if is_version {
         return Ok((global_opts, Cmd::Version));
     }
@@ -468,8 +788,10 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
         Some("format") => Some(Cmd::Help {
             usage: USAGE_FORMAT,
         }),
-        // TODO: Add usage for highlight.
-        Some("highlight") => Some(Cmd::Help { usage: USAGE_MAIN }),
+        Some("highlight") => Some(Cmd::Help {
+            usage: USAGE_HIGHLIGHT,
+        }),
+        Some("lsp") => Some(Cmd::Help { usage: USAGE_MAIN }),
         Some("main") => Some(Cmd::Help { usage: USAGE_MAIN }),
         Some("query") => Some(Cmd::Help {
             usage: USAGE_EVAL_QUERY,
@@ -480,7 +802,39 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
         return Ok((global_opts, help));
     }
 
+    if cmd.is_some() && cmd != Some("evaluate") && (concat || fail_fast || stream) {
+        let flag = if concat {
+            "--concat"
+        } else if fail_fast {
+            "--fail-fast"
+        } else {
+            "--stream"
+        };
+        let err = concat! {
+            "Option '"
+            Doc::highlight(flag)
+            "' is only supported for 'evaluate'. See --help for usage."
+        };
+        return Error::new(err).err();
+    }
+
     let result = match cmd {
+        Some("build") if is_tree => match targets.as_slice() {
+            [Target::File(input_dir), Target::File(output_dir)] => Cmd::BuildTree {
+                eval_opts,
+                style_opts,
+                build_mode,
+                input_dir: input_dir.clone(),
+                output_dir: output_dir.clone(),
+            },
+            _ => {
+                return Error::new(
+                    "Expected an input directory and an output directory. \
+                    See --help for usage.",
+                )
+                .err()
+            }
+        },
         Some("build") => {
             // Unlike other commands, for `rcl build` the input file defaults to
             // build.rcl instead of stdin.
@@ -493,12 +847,75 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
                 fname: get_unique_target(targets)?,
             }
         }
-        Some("evaluate") => Cmd::Evaluate {
-            eval_opts,
-            style_opts,
-            fname: get_unique_target(targets)?,
-            output,
-        },
+        Some("evaluate") if targets.len() > 1 || concat => {
+            if targets.iter().filter(|t| **t == Target::Stdin).count() > 1 {
+                return Error::new("Stdin can only be used as input once.").err();
+            }
+            if stream {
+                return Error::new(concat! {
+                    "Option '" Doc::highlight("--stream")
+                    "' supports only a single input file. See --help for usage."
+                })
+                .err();
+            }
+            // Formats other than these hold a single document, so the results
+            // of multiple files would not be valid output when we join them.
+            let is_stream = matches!(
+                eval_opts.format,
+                OutputFormat::Ndjson | OutputFormat::Raw | OutputFormat::YamlStream
+            );
+            if targets.len() > 1 && !concat && !is_stream {
+                return Error::new(
+                    "Multiple input files require --concat, or a format that can hold \
+                    multiple documents: ndjson, raw, or yaml-stream. See --help for usage.",
+                )
+                .err();
+            }
+            if targets.is_empty() {
+                targets.push(Target::StdinDefault);
+            }
+            Cmd::EvaluateMany {
+                eval_opts,
+                style_opts,
+                fnames: targets,
+                concat,
+                fail_fast,
+                output,
+            }
+        }
+        Some("evaluate") => {
+            // Streaming writes the output before we know whether evaluation
+            // succeeds, which we can only do for json to stdout, and only
+            // when there is no schema that needs the full value first.
+            if stream && eval_opts.format != OutputFormat::Json {
+                let err = concat! {
+                    "Option '" Doc::highlight("--stream") "' requires '"
+                    Doc::highlight("--format=json") "'. See --help for usage."
+                };
+                return Error::new(err).err();
+            }
+            let conflict = if output != OutputTarget::Stdout {
+                Some("--output")
+            } else if eval_opts.schema.is_some() {
+                Some("--schema")
+            } else {
+                None
+            };
+            if let (true, Some(option)) = (stream, conflict) {
+                let err = concat! {
+                    "Option '" Doc::highlight(option) "' cannot be combined with '"
+                    Doc::highlight("--stream") "'. See --help for usage."
+                };
+                return Error::new(err).err();
+            }
+            Cmd::Evaluate {
+                eval_opts,
+                style_opts,
+                fname: get_unique_target(targets)?,
+                output,
+                stream,
+            }
+        }
         Some("query") => {
             let (fname, query) = match targets.len() {
                 2 => (
@@ -554,10 +971,38 @@ pub fn parse(args: Vec<String>) -> Result<(GlobalOptions, Cmd)> {
         },
         Some("highlight") => Cmd::Highlight {
             fname: get_unique_target(targets)?,
+            line_numbers,
+        },
+        Some("lsp") => match targets.is_empty() {
+            true => Cmd::Lsp,
+            false => {
+                return Error::new(
+                    "The 'lsp' command does not take arguments. See --help for usage.",
+                )
+                .err()
+            }
         },
         None => Cmd::Help { usage: USAGE_MAIN },
         _ => panic!("Should have returned an error before getting here."),
     };
+
+    if global_opts.watch {
+        match result.watch_targets() {
+            None if matches!(result, Cmd::Help { .. }) => {}
+            None => {
+                return Error::new(
+                    "Watching is only supported for 'evaluate', 'query', and 'format' \
+                    without --in-place or --check. See --help for usage.",
+                )
+                .err()
+            }
+            Some(targets) if targets.iter().any(|t| !matches!(t, Target::File(..))) => {
+                return Error::new("Watching requires input files, stdin cannot be watched.").err()
+            }
+            Some(..) => {}
+        }
+    }
+
     Ok((global_opts, result))
 }
 
@@ -578,7 +1023,8 @@ mod test {
         StyleOptions, Target,
     };
     use crate::cmd_build::BuildMode;
-    use crate::markup::MarkupMode;
+    use crate::fmt_rcl::IntBase;
+    use crate::markup::{ColorLevel, MarkupMode, Theme};
     use crate::pprint::Config;
 
     fn fail_parse(args: &[&'static str]) -> String {
@@ -597,13 +1043,16 @@ mod test {
     fn parse_cmd_eval() {
         let expected_opt = GlobalOptions {
             markup: None,
+            theme: Theme::Dark,
             workdir: None,
+            watch: false,
         };
         let expected_cmd = Cmd::Evaluate {
             eval_opts: EvalOptions::default(),
             style_opts: StyleOptions::default(),
             fname: Target::File("infile".into()),
             output: OutputTarget::Stdout,
+            stream: false,
         };
         let mut expected = (expected_opt, expected_cmd);
 
@@ -616,10 +1065,52 @@ mod test {
         assert_eq!(parse(&["rcl", "--color=auto", "e", "infile"]), expected);
         expected.0.markup = Some(MarkupMode::None);
         assert_eq!(parse(&["rcl", "--color=none", "e", "infile"]), expected);
+        assert_eq!(parse(&["rcl", "--color=never", "e", "infile"]), expected);
         expected.0.markup = Some(MarkupMode::HtmlPandoc);
         assert_eq!(parse(&["rcl", "--color=html", "e", "infile"]), expected);
-        expected.0.markup = Some(MarkupMode::Ansi);
+        expected.0.markup = Some(MarkupMode::Ansi(ColorLevel::Ansi256, Theme::Dark));
+        assert_eq!(parse(&["rcl", "--color=ansi256", "e", "infile"]), expected);
+        expected.0.markup = Some(MarkupMode::Ansi(ColorLevel::TrueColor, Theme::Dark));
+        assert_eq!(
+            parse(&["rcl", "--color=truecolor", "e", "infile"]),
+            expected
+        );
+
+        // Test that --theme applies to ANSI modes, regardless of its position.
+        expected.0.theme = Theme::Light;
+        expected.0.markup = Some(MarkupMode::Ansi(ColorLevel::TrueColor, Theme::Light));
+        assert_eq!(
+            parse(&["rcl", "--theme=light", "--color=truecolor", "e", "infile"]),
+            expected
+        );
+        assert_eq!(
+            parse(&[
+                "rcl",
+                "--color=truecolor",
+                "e",
+                "infile",
+                "--theme",
+                "light"
+            ]),
+            expected
+        );
+        // The theme does not affect non-ANSI modes, but we do record it.
+        expected.0.markup = Some(MarkupMode::HtmlPandoc);
+        assert_eq!(
+            parse(&["rcl", "--color=html", "--theme=light", "e", "infile"]),
+            expected
+        );
+        expected.0.markup = None;
+        assert_eq!(parse(&["rcl", "--theme=light", "e", "infile"]), expected);
+        expected.0.theme = Theme::Dark;
+
+        expected.0.markup = Some(MarkupMode::Ansi(ColorLevel::Ansi16, Theme::Dark));
         assert_eq!(parse(&["rcl", "--color=ansi", "e", "infile"]), expected);
+        assert_eq!(parse(&["rcl", "--color=always", "e", "infile"]), expected);
+        assert_eq!(
+            parse(&["rcl", "--theme=dark", "--color=ansi", "e", "infile"]),
+            expected
+        );
 
         // We should be able to pass --color in any place.
         assert_eq!(parse(&["rcl", "--color=ansi", "e", "infile"]), expected);
@@ -703,13 +1194,98 @@ mod test {
         }
         assert_eq!(parse(&["rcl", "e", "infile", "--banner=prefix"]), expected);
 
+        // Test that -I can be repeated and preserves the order.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.banner = None;
+            eval_opts.include = vec!["lib".to_string(), "../vendor".to_string()];
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "-I", "lib", "infile", "--include=../vendor"]),
+            expected
+        );
+
+        // Test --no-trailing-newline, last option wins.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.include = Vec::new();
+            eval_opts.trailing_newline = false;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "infile", "--no-trailing-newline"]),
+            expected
+        );
+        assert_eq!(
+            parse(&[
+                "rcl",
+                "--trailing-newline",
+                "e",
+                "--no-trailing-newline",
+                "infile"
+            ]),
+            expected
+        );
+
+        // Test --strict.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.trailing_newline = true;
+            eval_opts.strict = true;
+        }
+        assert_eq!(parse(&["rcl", "e", "--strict", "infile"]), expected);
+
+        // Test --schema.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.strict = false;
+            eval_opts.schema = Some("schema.rcl".into());
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "--schema", "schema.rcl", "infile"]),
+            expected
+        );
+
+        // Test --rcl-int-base.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.schema = None;
+            eval_opts.rcl_int_base = IntBase::Hexadecimal;
+        }
+        assert_eq!(
+            parse(&["rcl", "e", "--rcl-int-base=16", "infile"]),
+            expected
+        );
+
+        // Test that --canonical overrides presentation options in any order.
+        if let Cmd::Evaluate { eval_opts, .. } = &mut expected.1 {
+            eval_opts.rcl_int_base = IntBase::Decimal;
+        }
+        assert_eq!(
+            parse(&[
+                "rcl",
+                "e",
+                "--canonical",
+                "-w20",
+                "--no-trailing-newline",
+                "--rcl-int-base=2",
+                "infile"
+            ]),
+            expected
+        );
+
+        // A banner and color would be part of the output, --canonical rejects
+        // them rather than dropping them.
+        assert_eq!(
+            fail_parse(&["rcl", "e", "--canonical", "--banner=x", "infile"]),
+            "Error: Option '--banner' cannot be combined with '--canonical'. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "--color=ansi", "e", "infile", "--canonical"]),
+            "Error: Option '--color' cannot be combined with '--canonical'. See --help for usage.\n"
+        );
+        assert_eq!(
+            parse(&["rcl", "--color=none", "e", "--canonical", "infile"]).1,
+            expected.1
+        );
+
         // Test that defaulting to stdin works. If '-' is there we get it
         // explicitly, if it's not, we get it implicitly.
-        if let Cmd::Evaluate {
-            fname, eval_opts, ..
-        } = &mut expected.1
-        {
-            eval_opts.banner = None;
+        if let Cmd::Evaluate { fname, .. } = &mut expected.1 {
             *fname = Target::Stdin;
         }
         assert_eq!(parse(&["rcl", "e", "-"]), expected);
@@ -736,9 +1312,17 @@ mod test {
             fail_parse(&["rcl", "eval", "infile", "-wbobcat"]),
             "Error: 'bobcat' is not valid for -w. See --help for usage.\n"
         );
+        assert_eq!(
+            fail_parse(&["rcl", "eval", "infile", "--theme=solarized"]),
+            "Error: Expected --theme to be followed by one of dark, light. See --help for usage.\n"
+        );
         assert_eq!(
             fail_parse(&["rcl", "eval", "infile", "--format=yamr"]),
-            "Error: Expected --format to be followed by one of json, raw, rcl, toml, yaml-stream. See --help for usage.\n"
+            "Error: Expected --format to be followed by one of csv, env, json, ndjson, raw, rcl, toml, yaml-stream. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "eval", "infile", "--rcl-int-base=8"]),
+            "Error: Expected --rcl-int-base to be followed by one of 2, 10, 16. See --help for usage.\n"
         );
         assert_eq!(
             fail_parse(&["rcl", "frobnicate", "infile"]),
@@ -750,11 +1334,145 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_cmd_eval_many() {
+        let (_, cmd) = parse(&["rcl", "e", "-fndjson", "a.rcl", "-", "--fail-fast", "b.rcl"]);
+        let eval_opts = EvalOptions {
+            format: OutputFormat::Ndjson,
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            cmd,
+            Cmd::EvaluateMany {
+                eval_opts,
+                style_opts: StyleOptions::default(),
+                fnames: vec![
+                    Target::File("a.rcl".to_string()),
+                    Target::Stdin,
+                    Target::File("b.rcl".to_string()),
+                ],
+                concat: false,
+                fail_fast: true,
+                output: OutputTarget::Stdout,
+            }
+        );
+
+        // With --concat, even a single file needs to be a list.
+        let (_, cmd) = parse(&["rcl", "je", "--concat", "a.rcl"]);
+        let eval_opts = EvalOptions {
+            format: OutputFormat::Json,
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            cmd,
+            Cmd::EvaluateMany {
+                eval_opts,
+                style_opts: StyleOptions::default(),
+                fnames: vec![Target::File("a.rcl".to_string())],
+                concat: true,
+                fail_fast: false,
+                output: OutputTarget::Stdout,
+            }
+        );
+
+        assert_eq!(
+            fail_parse(&["rcl", "e", "-fraw", "-", "a.rcl", "-"]),
+            "Error: Stdin can only be used as input once.\n"
+        );
+
+        // Single-document formats cannot hold the results of multiple files.
+        for format_arg in [
+            "--format=csv",
+            "--format=env",
+            "--format=json",
+            "--format=rcl",
+            "--format=toml",
+        ] {
+            assert_eq!(
+                fail_parse(&["rcl", "e", format_arg, "a.rcl", "b.rcl"]),
+                "Error: Multiple input files require --concat, or a format that can hold \
+                multiple documents: ndjson, raw, or yaml-stream. See --help for usage.\n"
+            );
+            let (_, cmd) = parse(&["rcl", "e", format_arg, "--concat", "a.rcl", "b.rcl"]);
+            assert!(matches!(cmd, Cmd::EvaluateMany { concat: true, .. }));
+        }
+        for format_arg in ["--format=ndjson", "--format=raw", "--format=yaml-stream"] {
+            let (_, cmd) = parse(&["rcl", "e", format_arg, "a.rcl", "b.rcl"]);
+            assert!(matches!(cmd, Cmd::EvaluateMany { concat: false, .. }));
+        }
+
+        assert_eq!(
+            fail_parse(&["rcl", "q", "--concat", "a.rcl", "input"]),
+            "Error: Option '--concat' is only supported for 'evaluate'. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "f", "--fail-fast", "a.rcl"]),
+            "Error: Option '--fail-fast' is only supported for 'evaluate'. See --help for usage.\n"
+        );
+    }
+
+    #[test]
+    fn parse_cmd_eval_stream() {
+        // Streaming is opt-in, also for json to stdout.
+        let (_, cmd) = parse(&["rcl", "je", "a.rcl"]);
+        assert!(matches!(cmd, Cmd::Evaluate { stream: false, .. }));
+        let (_, cmd) = parse(&["rcl", "je", "--stream", "a.rcl"]);
+        assert!(matches!(cmd, Cmd::Evaluate { stream: true, .. }));
+
+        assert_eq!(
+            fail_parse(&["rcl", "e", "--stream", "a.rcl"]),
+            "Error: Option '--stream' requires '--format=json'. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "je", "--stream", "a.rcl", "-o", "out.json"]),
+            "Error: Option '--output' cannot be combined with '--stream'. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "je", "--schema=s.rcl", "--stream", "a.rcl"]),
+            "Error: Option '--schema' cannot be combined with '--stream'. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "je", "--stream", "a.rcl", "b.rcl"]),
+            "Error: Option '--stream' supports only a single input file. See --help for usage.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "jq", "--stream", "a.rcl", "input"]),
+            "Error: Option '--stream' is only supported for 'evaluate'. See --help for usage.\n"
+        );
+    }
+
+    #[test]
+    fn parse_cmd_watch() {
+        let (opts, cmd) = parse(&["rcl", "e", "--watch", "infile"]);
+        assert!(opts.watch);
+        assert_eq!(
+            cmd.watch_targets(),
+            Some(vec![&Target::File("infile".to_string())])
+        );
+        let (opts, _) = parse(&["rcl", "--watch", "f", "infile"]);
+        assert!(opts.watch);
+
+        assert_eq!(
+            fail_parse(&["rcl", "e", "--watch"]),
+            "Error: Watching requires input files, stdin cannot be watched.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "e", "-fndjson", "--watch", "a.rcl", "-"]),
+            "Error: Watching requires input files, stdin cannot be watched.\n"
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "f", "--watch", "--in-place", "infile"]),
+            "Error: Watching is only supported for 'evaluate', 'query', and 'format' without --in-place or --check. See --help for usage.\n"
+        );
+    }
+
     #[test]
     fn parse_cmd_fmt() {
         let expected_opt = GlobalOptions {
             markup: None,
+            theme: Theme::Dark,
             workdir: None,
+            watch: false,
         };
         let expected_cmd = Cmd::Format {
             style_opts: StyleOptions::default(),
@@ -814,20 +1532,44 @@ mod test {
     fn parse_cmd_highlight() {
         let expected_opt = GlobalOptions {
             markup: None,
+            theme: Theme::Dark,
             workdir: None,
+            watch: false,
         };
         let expected_cmd = Cmd::Highlight {
             fname: Target::File("infile".into()),
+            line_numbers: false,
         };
         let expected = (expected_opt, expected_cmd);
         assert_eq!(parse(&["rcl", "highlight", "infile"]), expected);
+
+        let expected_cmd = Cmd::Highlight {
+            fname: Target::File("infile".into()),
+            line_numbers: true,
+        };
+        assert_eq!(
+            parse(&["rcl", "highlight", "--line-numbers", "infile"]).1,
+            expected_cmd
+        );
+        assert_eq!(parse(&["rcl", "highlight", "-n", "infile"]).1, expected_cmd);
+    }
+
+    #[test]
+    fn parse_cmd_lsp() {
+        assert_eq!(parse(&["rcl", "lsp"]).1, Cmd::Lsp);
+        assert_eq!(
+            fail_parse(&["rcl", "lsp", "infile"]),
+            "Error: The 'lsp' command does not take arguments. See --help for usage.\n",
+        );
     }
 
     #[test]
     fn parse_cmd_query() {
         let expected_opt = GlobalOptions {
             markup: None,
+            theme: Theme::Dark,
             workdir: None,
+            watch: false,
         };
         let expected_cmd = Cmd::Query {
             eval_opts: EvalOptions::default(),
@@ -868,7 +1610,9 @@ mod test {
     fn parse_cmd_build() {
         let expected_opt = GlobalOptions {
             markup: None,
+            theme: Theme::Dark,
             workdir: None,
+            watch: false,
         };
         let expected_cmd = Cmd::Build {
             eval_opts: EvalOptions::default(),
@@ -890,30 +1634,57 @@ mod test {
         assert_eq!(parse(&["rcl", "build", "--dry-run", "other.rcl"]), expected);
     }
 
+    #[test]
+    fn parse_cmd_build_tree() {
+        let (_, cmd) = parse(&["rcl", "build", "--tree", "-fjson", "src", "out"]);
+        let eval_opts = EvalOptions {
+            format: OutputFormat::Json,
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            cmd,
+            Cmd::BuildTree {
+                eval_opts,
+                style_opts: StyleOptions::default(),
+                build_mode: BuildMode::WriteFilesystem,
+                input_dir: "src".to_string(),
+                output_dir: "out".to_string(),
+            }
+        );
+        assert_eq!(
+            fail_parse(&["rcl", "build", "--tree", "src"]),
+            "Error: Expected an input directory and an output directory. See --help for usage.\n"
+        );
+    }
+
     #[test]
     fn parse_cmd_handles_stdin_and_double_dash() {
         assert_eq!(
             parse(&["rcl", "highlight", "infile"]).1,
             Cmd::Highlight {
                 fname: Target::File("infile".into()),
+                line_numbers: false,
             }
         );
         assert_eq!(
             parse(&["rcl", "highlight", "--", "infile"]).1,
             Cmd::Highlight {
                 fname: Target::File("infile".into()),
+                line_numbers: false,
             }
         );
         assert_eq!(
             parse(&["rcl", "highlight", "-"]).1,
             Cmd::Highlight {
                 fname: Target::Stdin,
+                line_numbers: false,
             }
         );
         assert_eq!(
             parse(&["rcl", "highlight", "--", "-"]).1,
             Cmd::Highlight {
                 fname: Target::File("-".into()),
+                line_numbers: false,
             }
         );
     }
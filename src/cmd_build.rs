@@ -7,11 +7,12 @@
 
 //! Implementation of the `rcl build` subcommand.
 
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use crate::cli::OutputFormat;
 use crate::error::{Error, PathElement, Result};
-use crate::fmt_rcl::format_rcl;
+use crate::fmt_rcl::{format_rcl, IntBase};
 use crate::loader::Loader;
 use crate::pprint::{concat, Config, Doc};
 use crate::runtime::Value;
@@ -68,7 +69,10 @@ struct Target {
 fn parse_format(format: &str) -> Option<OutputFormat> {
     // Note, this is duplicated between the CLI parser.
     let f = match format {
+        "csv" => OutputFormat::Csv,
+        "env" => OutputFormat::Env,
         "json" => OutputFormat::Json,
+        "ndjson" => OutputFormat::Ndjson,
         "raw" => OutputFormat::Raw,
         "rcl" => OutputFormat::Rcl,
         "toml" => OutputFormat::Toml,
@@ -187,7 +191,12 @@ pub fn execute_build(
     for (i, target) in targets.iter().enumerate() {
         println!("[{}/{}] {}", i + 1, targets.len(), target.out_path);
 
-        let mut doc = crate::cmd_eval::format_value(target.format, doc_span, &target.contents)?;
+        let mut doc = crate::cmd_eval::format_value(
+            target.format,
+            IntBase::Decimal,
+            doc_span,
+            &target.contents,
+        )?;
 
         if let Some(banner) = target.banner.as_ref() {
             doc = concat! {
@@ -229,3 +238,109 @@ pub fn execute_build(
 
     Ok(())
 }
+
+/// A file to evaluate in a tree build, see [`list_tree_targets`].
+#[derive(Debug, Eq, PartialEq)]
+pub struct TreeTarget {
+    /// The file to evaluate, relative to the input directory.
+    pub input: PathBuf,
+    /// The file to write the output to, relative to the output directory.
+    pub output: PathBuf,
+}
+
+/// Return the file extension for outputs in the given format.
+pub fn output_extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Csv => "csv",
+        OutputFormat::Env => "env",
+        OutputFormat::Json => "json",
+        OutputFormat::Ndjson => "ndjson",
+        OutputFormat::Raw => "txt",
+        OutputFormat::Rcl => "rcl",
+        OutputFormat::Toml => "toml",
+        OutputFormat::YamlStream => "yaml",
+    }
+}
+
+/// List the `.rcl` files in a directory tree to evaluate with `rcl build --tree`.
+///
+/// Files whose name starts with an underscore are libraries that other files
+/// import, so they are skipped. The targets are sorted by input path, so the
+/// build order does not depend on the order of the directory listing.
+pub fn list_tree_targets(input_dir: &Path, format: OutputFormat) -> Result<Vec<TreeTarget>> {
+    let mut result = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(rel_dir) = pending.pop() {
+        let dir = input_dir.join(&rel_dir);
+        let read_error = |err: std::io::Error| {
+            Error::new(concat! {
+                "Failed to read directory '" Doc::path(&dir) "': " err.to_string()
+            })
+        };
+        let entries = std::fs::read_dir(&dir).map_err(read_error)?;
+        for entry in entries {
+            let entry = entry.map_err(read_error)?;
+            let rel_path = rel_dir.join(entry.file_name());
+            let file_type = entry.file_type().map_err(read_error)?;
+            if file_type.is_dir() {
+                pending.push(rel_path);
+                continue;
+            }
+            let is_library = entry.file_name().to_string_lossy().starts_with('_');
+            let is_rcl = rel_path.extension().map_or(false, |ext| ext == "rcl");
+            if is_rcl && !is_library {
+                result.push(TreeTarget {
+                    output: rel_path.with_extension(output_extension(format)),
+                    input: rel_path,
+                });
+            }
+        }
+    }
+
+    result.sort_by(|a, b| a.input.cmp(&b.input));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{list_tree_targets, TreeTarget};
+    use crate::cli::OutputFormat;
+
+    #[test]
+    fn list_tree_targets_skips_libraries() {
+        let dir = std::env::temp_dir().join(format!("rcl-test-tree-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub/_private")).unwrap();
+        for name in [
+            "b.rcl",
+            "a.rcl",
+            "_lib.rcl",
+            "notes.txt",
+            "sub/c.rcl",
+            "sub/_helper.rcl",
+            "sub/_private/d.rcl",
+        ] {
+            std::fs::write(dir.join(name), "null").unwrap();
+        }
+
+        let targets = list_tree_targets(&dir, OutputFormat::Json).unwrap();
+        let target = |input: &str, output: &str| TreeTarget {
+            input: PathBuf::from(input),
+            output: PathBuf::from(output),
+        };
+        assert_eq!(
+            targets,
+            [
+                target("a.rcl", "a.json"),
+                target("b.rcl", "b.json"),
+                target("sub/_private/d.rcl", "sub/_private/d.json"),
+                target("sub/c.rcl", "sub/c.json"),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(list_tree_targets(Path::new(&dir), OutputFormat::Json).is_err());
+    }
+}
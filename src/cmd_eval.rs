@@ -9,17 +9,142 @@
 
 use crate::cli::OutputFormat;
 use crate::error::Result;
+use crate::fmt_rcl::IntBase;
 use crate::pprint::Doc;
 use crate::runtime::Value;
 use crate::source::Span;
 
-pub fn format_value(format: OutputFormat, value_span: Span, value: &Value) -> Result<Doc> {
+/// Format a value in the given output format.
+///
+/// The `int_base` only affects the `rcl` format, other formats print integers
+/// in decimal.
+pub fn format_value(
+    format: OutputFormat,
+    int_base: IntBase,
+    value_span: Span,
+    value: &Value,
+) -> Result<Doc> {
     let result = match format {
+        OutputFormat::Csv => crate::fmt_csv::format_csv(value_span, value)?,
+        OutputFormat::Env => crate::fmt_env::format_env(value_span, value)?,
         OutputFormat::Json => crate::fmt_json::format_json(value_span, value)?,
+        OutputFormat::Ndjson => crate::fmt_ndjson::format_ndjson(value_span, value)?,
         OutputFormat::Raw => crate::fmt_raw::format_raw(value_span, value)?,
-        OutputFormat::Rcl => crate::fmt_rcl::format_rcl(value),
+        OutputFormat::Rcl => crate::fmt_rcl::format_rcl_with_int_base(value, int_base),
         OutputFormat::Toml => crate::fmt_toml::format_toml(value_span, value)?,
         OutputFormat::YamlStream => crate::fmt_yaml_stream::format_yaml_stream(value_span, value)?,
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::format_value;
+    use crate::cli::{Cmd, OutputFormat, StyleOptions};
+    use crate::fmt_rcl::IntBase;
+    use crate::loader::{Loader, VoidFilesystem};
+    use crate::pprint::Config;
+    use crate::source::DocId;
+    use crate::tracer::VoidTracer;
+
+    /// Evaluate the document and format it with the default width.
+    fn format_with(format: OutputFormat, int_base: IntBase, source: &str) -> String {
+        let mut loader = Loader::new();
+        loader.set_filesystem(Box::new(VoidFilesystem));
+        let id = loader.load_string(source.to_string());
+        let mut type_env = crate::typecheck::prelude();
+        let mut value_env = crate::runtime::prelude();
+        let value = loader
+            .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+            .unwrap();
+        let cfg = Config {
+            width: StyleOptions::default().width,
+        };
+        let doc = format_value(format, int_base, loader.get_span(id), &value).unwrap();
+        doc.println(&cfg).to_string_no_markup()
+    }
+
+    /// Evaluate the document with the options that the command line sets.
+    fn eval_with_args(args: &[&str], source: &str) -> String {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (eval_opts, style_opts) = match crate::cli::parse(args).unwrap().1 {
+            Cmd::Evaluate {
+                eval_opts,
+                style_opts,
+                ..
+            } => (eval_opts, style_opts),
+            _ => panic!("Expected an evaluate command."),
+        };
+        let mut loader = Loader::new();
+        let value = loader.evaluate_str("input", source).unwrap();
+        let span = loader.get_span(DocId(0));
+        let doc = format_value(eval_opts.format, eval_opts.rcl_int_base, span, &value).unwrap();
+        let cfg = Config {
+            width: style_opts.width,
+        };
+        let mut result = doc.println(&cfg);
+        if !eval_opts.trailing_newline {
+            result.trim_newline_end();
+        }
+        result.to_string_no_markup()
+    }
+
+    #[test]
+    fn canonical_output_does_not_depend_on_input_or_presentation() {
+        let a = r#"[{ name = "web", ports = {443, 80}, env = { b = "2", a = "1" } }]"#;
+        let b = r#"
+        // The same value, with the keys and set elements in a different order.
+        [
+          {
+            env = {
+              a = "1",
+              b = "2",
+            },
+            name = "web",
+            ports = {80, 443},
+          },
+        ]
+        "#;
+        for format in ["-fjson", "-fndjson", "-frcl", "-fyaml-stream"] {
+            let canonical = eval_with_args(&["rcl", "e", format, "--canonical"], a);
+            // Presentation options do not affect canonical output, regardless
+            // of whether they come before or after --canonical.
+            let presented = eval_with_args(
+                &[
+                    "rcl",
+                    "e",
+                    "-w10",
+                    format,
+                    "--canonical",
+                    "--no-trailing-newline",
+                    "--rcl-int-base=16",
+                ],
+                b,
+            );
+            assert_eq!(canonical, presented, "Output differs for {format}.");
+        }
+    }
+
+    #[test]
+    fn rcl_int_base_renders_the_same_value() {
+        let source =
+            "{ mode = 0b1_1010_0100, mask = 0xff, neg = -42, min = (-9223372036854775807) - 1 }";
+        assert_eq!(
+            format_with(OutputFormat::Rcl, IntBase::Decimal, source),
+            "{ mask = 255, min = -9223372036854775808, mode = 420, neg = -42 }\n",
+        );
+        assert_eq!(
+            format_with(OutputFormat::Rcl, IntBase::Hexadecimal, source),
+            "{ mask = 0xff, min = -0x8000000000000000, mode = 0x1a4, neg = -0x2a }\n",
+        );
+        assert_eq!(
+            format_with(OutputFormat::Rcl, IntBase::Binary, "[5, -2, 0]"),
+            "[0b101, -0b10, 0b0]\n",
+        );
+        // The base only applies to RCL output, json is always decimal.
+        assert_eq!(
+            format_with(OutputFormat::Json, IntBase::Hexadecimal, source),
+            "{\"mask\": 255, \"min\": -9223372036854775808, \"mode\": 420, \"neg\": -42}\n",
+        );
+    }
+}
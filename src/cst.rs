@@ -31,6 +31,16 @@ pub enum UnOp {
     Neg,
 }
 
+impl UnOp {
+    /// Return the operator as it is written in source code.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnOp::Not => "not",
+            UnOp::Neg => "-",
+        }
+    }
+}
+
 /// A binary operator.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum BinOp {
@@ -46,15 +56,33 @@ pub enum BinOp {
     /// `-` Subtract two numbers.
     Sub,
 
+    /// `**` Raise a number to a power.
+    Pow,
+
     /// `and`: Boolean AND.
     And,
 
     /// `or`: Boolean OR.
     Or,
 
-    /// `|`: Union two collections
+    /// `xor`: Boolean exclusive OR.
+    Xor,
+
+    /// `|`: Union two collections, or bitwise OR of two integers.
     Union,
 
+    /// `&`: Bitwise AND of two integers.
+    BitAnd,
+
+    /// `^`: Bitwise exclusive OR of two integers.
+    BitXor,
+
+    /// `<<`: Shift an integer left.
+    Shl,
+
+    /// `>>`: Shift an integer right, preserving the sign.
+    Shr,
+
     /// `<`: Less than.
     Lt,
 
@@ -72,6 +100,46 @@ pub enum BinOp {
 
     /// `!=`: Does not equal.
     Neq,
+
+    /// `..`: Range of integers, excluding the upper bound.
+    Range,
+
+    /// `..=`: Range of integers, including the upper bound.
+    RangeInclusive,
+}
+
+impl BinOp {
+    /// Return the operator as it is written in source code.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Sub => "-",
+            BinOp::Pow => "**",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Xor => "xor",
+            BinOp::Union => "|",
+            BinOp::BitAnd => "&",
+            BinOp::BitXor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::LtEq => "<=",
+            BinOp::GtEq => ">=",
+            BinOp::Eq => "==",
+            BinOp::Neq => "!=",
+            BinOp::Range => "..",
+            BinOp::RangeInclusive => "..=",
+        }
+    }
+
+    /// Whether the operator is one of the ordering comparisons `<`, `>`, `<=`, `>=`.
+    pub fn is_ordering(self) -> bool {
+        matches!(self, BinOp::Lt | BinOp::Gt | BinOp::LtEq | BinOp::GtEq)
+    }
 }
 
 /// Not code, but a piece of the document relevant to preserve for formatting.
@@ -83,6 +151,9 @@ pub enum NonCode {
     /// A comment that runs until the end of the line (excluding the newline).
     LineComment(Span),
 
+    /// A `/* */` comment, which may span multiple lines.
+    BlockComment(Span),
+
     /// A line that starts with `#!`, excluding the newline itself.
     Shebang(Span),
 }
@@ -136,9 +207,9 @@ pub enum StringPart {
 /// name, we call those _statements_.
 #[derive(Debug)]
 pub enum Stmt {
-    /// A let-binding that binds `value` to the name `ident` in `body`.
+    /// A let-binding that binds `value` to the names in `binding` in `body`.
     Let {
-        ident: Span,
+        binding: Binding,
         type_: Option<Box<Type>>,
         value_span: Span,
         value: Box<Expr>,
@@ -184,6 +255,12 @@ pub enum Expr {
         elements: List<Prefixed<Seq>>,
     },
 
+    /// The empty dict literal `{:}`.
+    ///
+    /// Unlike `{}`, which can be an empty dict or an empty set depending on
+    /// the expected type, this is always a dict.
+    EmptyDictLit { open: Span, close: Span },
+
     /// A `[]`-enclosed collection literal.
     BracketLit {
         open: Span,
@@ -241,6 +318,14 @@ pub enum Expr {
         else_body: Box<Expr>,
     },
 
+    /// A match expression, `match scrutinee { pattern => body, ... }`.
+    Match {
+        match_span: Span,
+        scrutinee_span: Span,
+        scrutinee: Box<Expr>,
+        arms: List<Prefixed<MatchArm>>,
+    },
+
     /// Define a lambda function.
     Function {
         args: List<Prefixed<Span>>,
@@ -302,6 +387,55 @@ pub enum Expr {
     },
 }
 
+/// A pattern in an arm of a match expression.
+#[derive(Debug)]
+pub enum Pattern {
+    /// The wildcard `_`, which matches any value.
+    Wildcard(Span),
+
+    /// A literal, which matches values equal to it.
+    Literal { span: Span, value: Box<Expr> },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span) => *span,
+            Pattern::Literal { span, .. } => *span,
+        }
+    }
+}
+
+/// The left-hand side of a let-binding, or a loop variable.
+#[derive(Debug)]
+pub enum Binding {
+    /// Bind the value to a single name.
+    Ident(Span),
+
+    /// Unpack a list of exactly this many elements, as in `let [a, b] = xs;`.
+    List {
+        span: Span,
+        elements: Box<[Binding]>,
+    },
+}
+
+impl Binding {
+    pub fn span(&self) -> Span {
+        match self {
+            Binding::Ident(span) => *span,
+            Binding::List { span, .. } => *span,
+        }
+    }
+}
+
+/// An arm `pattern => body` of a match expression.
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body_span: Span,
+    pub body: Expr,
+}
+
 /// An inner element of a collection literal.
 #[derive(Debug)]
 pub enum Seq {
@@ -341,9 +475,9 @@ pub enum Seq {
         body: Box<Prefixed<Seq>>,
     },
 
-    /// Loop over the collection, binding the values to `idents`.
+    /// Loop over the collection, binding the values to `bindings`.
     For {
-        idents: Box<[Span]>,
+        bindings: Box<[Binding]>,
         collection_span: Span,
         collection: Box<Expr>,
         body: Box<Prefixed<Seq>>,
@@ -412,6 +546,18 @@ pub enum Chain {
         /// The index expression.
         index: Box<Expr>,
     },
+
+    /// Take a slice of a collection with `[start:end]`.
+    Slice {
+        /// The opening bracket.
+        open: Span,
+        /// The closing bracket.
+        close: Span,
+        /// The lower bound, if there is one.
+        start: Option<(Span, Box<Expr>)>,
+        /// The upper bound, if there is one.
+        end: Option<(Span, Box<Expr>)>,
+    },
 }
 
 #[derive(Debug)]
@@ -436,4 +582,9 @@ pub enum Type {
         args: List<Prefixed<Type>>,
         result: Box<Type>,
     },
+
+    /// A union of two or more types separated by `|`.
+    ///
+    /// For example, `Int | String`. This is equivalent to `Union[Int, String]`.
+    Union { span: Span, members: Box<[Type]> },
 }
@@ -42,6 +42,14 @@ impl<T> Env<T> {
             .map(|(_k, v)| v)
     }
 
+    /// Iterate over all bindings, from the most recent to the oldest.
+    ///
+    /// Shadowed names occur more than once; the first occurrence is the one
+    /// that is in scope.
+    pub fn iter(&self) -> impl Iterator<Item = (&Ident, &T)> {
+        self.bindings.iter().rev().map(|(k, v)| (k, v))
+    }
+
     /// Return a checkpoint of the environment to later [`Env::pop`] to.
     ///
     /// Note, the environment is a stack and the pushes and pops have to be
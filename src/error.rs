@@ -7,11 +7,13 @@
 
 //! Types and functions for error reporting.
 
-use crate::fmt_rcl::format_rcl;
+use crate::cst::{BinOp, UnOp};
+use crate::fmt_rcl::{format_rcl, format_rcl_short};
 use crate::markup::Markup;
-use crate::pprint::{concat, Doc};
+use crate::pprint::{concat, indent, Doc};
 use crate::runtime::Value;
 use crate::source::{Inputs, Span};
+use crate::types::AsTypeName;
 
 pub type Result<T> = std::result::Result<T, Box<Error>>;
 
@@ -305,10 +307,212 @@ impl IntoError for Span {
     }
 }
 
+/// Construct the error for an import that would close a cycle.
+///
+/// The `chain` holds the names of the documents in the cycle in import order,
+/// starting and ending with the document that is imported again, and `at` is
+/// the import that closes the cycle.
+pub fn import_cycle_error(at: Span, chain: &[&str]) -> Error {
+    let mut cycle = Vec::with_capacity(chain.len() * 2);
+    for (i, name) in chain.iter().enumerate() {
+        if i > 0 {
+            cycle.push(Doc::from(" → "));
+        }
+        cycle.push(Doc::string(name.to_string()).with_markup(Markup::Highlight));
+    }
+    at.error("This import creates a cycle.")
+        .with_body(concat! { "The cycle is " Doc::Concat(cycle) "." })
+}
+
+/// Construct the error for a loop with the wrong number of variables.
+///
+/// The `collection_type` is the short name of the type that is being iterated,
+/// one of `List`, `Set`, `Dict`, or `String`. This error is shared between the
+/// typechecker and the evaluator, which catches the cases where the type of the
+/// collection is not known statically.
+pub fn iteration_arity_error(
+    idents_span: Span,
+    num_idents: usize,
+    collection_span: Span,
+    collection_type: &'static str,
+) -> Error {
+    let (expected, note) = match collection_type {
+        "Dict" => (
+            "Expected two variables in dict iteration",
+            "This is a dict, it yields a key and value per iteration.",
+        ),
+        "Set" => (
+            "Expected a single variable",
+            "This is a set, it yields one element per iteration.",
+        ),
+        "String" => (
+            "Expected a single variable",
+            "This is a string, it yields one character per iteration.",
+        ),
+        _ => (
+            "Expected a single variable",
+            "This is a list, it yields one element per iteration.",
+        ),
+    };
+    idents_span
+        .error(concat! { expected ", but got " num_idents.to_string() "." })
+        .with_note(collection_span, note)
+}
+
+/// Construct the error for iterating a value that is not a collection.
+pub fn not_iterable_error(collection_span: Span, value: &Value) -> Error {
+    collection_span
+        .error(concat! {
+            "Cannot iterate a value of type "
+            value.type_name().format_type()
+            "."
+        })
+        .with_body(concat! {
+            "Expected a list, set, dict, or string, but got:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+}
+
+/// Construct the error for an input to `rcl evaluate --concat` that is not a list.
+pub fn concat_not_list_error(doc_span: Span, value: &Value) -> Error {
+    doc_span
+        .error(concat! {
+            "Cannot concatenate a value of type "
+            value.type_name().format_type()
+            "."
+        })
+        .with_body(concat! {
+            "With '" Doc::highlight("--concat") "', every input must evaluate to a list, but got:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+}
+
+/// Construct the error for a list binding that does not match the value.
+///
+/// The span should be the span of the list binding, e.g. `[a, b]`.
+pub fn unpack_error(binding_span: Span, num_elements: usize, value: &Value) -> Error {
+    let elements = |n: usize| if n == 1 { " element" } else { " elements" };
+    let found: Doc = match value {
+        Value::List(xs) => concat! { "a list of " xs.len().to_string() elements(xs.len()) },
+        _ => concat! { "a value of type " value.type_name().format_type() },
+    };
+    binding_span
+        .error(concat! {
+            "Expected a list of " num_elements.to_string() elements(num_elements)
+            " to unpack, but got " found "."
+        })
+        .with_body(concat! {
+            "The value is:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+}
+
+/// Construct the error for a condition that is not a boolean.
+///
+/// The span should be the span of the condition expression.
+pub fn condition_type_error(condition_span: Span, value: &Value) -> Error {
+    condition_span
+        .error(concat! {
+            "Condition should be "
+            "Bool".format_type()
+            ", but found a value of type "
+            value.type_name().format_type()
+            "."
+        })
+        .with_body(concat! {
+            "Condition:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+        .with_help("There is no implicit conversion, conditions must be boolean.")
+}
+
+/// Construct the error for a set that contains the same element twice.
+///
+/// This is only an error in strict mode, otherwise duplicates are merged. When
+/// a comprehension produces the same value twice from the same expression, the
+/// two spans are equal, and we only point at it once.
+pub fn duplicate_set_element_error(first_span: Span, span: Span, value: &Value) -> Error {
+    let err = span
+        .error("Duplicate set element.")
+        .with_body(concat! {
+            "The element is:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+        .with_help(concat! {
+            "Outside of " Doc::highlight("--strict") " mode, duplicate set elements are merged. "
+            "To keep duplicates, use a list instead."
+        });
+    if first_span == span {
+        err
+    } else {
+        err.with_note(first_span, "The element first occurs here.")
+    }
+}
+
+/// Construct the error for a unary operator that does not support its operand.
+pub fn unop_type_error(op_span: Span, op: UnOp, value: &Value) -> Error {
+    op_span
+        .error(concat! {
+            "Cannot apply "
+            Doc::highlight(op.symbol())
+            " to a value of type "
+            value.type_name().format_type()
+            "."
+        })
+        .with_body(concat! {
+            "Operand:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(value) }
+        })
+}
+
+/// Construct the help for an ordering comparison between Bools.
+///
+/// Bools have no order, so the comparison was most likely meant to test for
+/// equality.
+pub fn bool_ordering_help(op: BinOp) -> Doc<'static> {
+    concat! {
+        "Bools cannot be compared with " Doc::highlight(op.symbol()) ". "
+        "To test whether they are equal, use " Doc::highlight("==")
+        " or " Doc::highlight("!=") " instead."
+    }
+}
+
+/// Construct the error for a binary operator that does not support its operands.
+///
+/// The error names the operator and the types of both sides, and the body
+/// shows the values themselves.
+pub fn binop_type_error(op_span: Span, op: BinOp, lhs: &Value, rhs: &Value) -> Error {
+    op_span
+        .error(concat! {
+            "Cannot apply "
+            Doc::highlight(op.symbol())
+            " to values of type "
+            lhs.type_name().format_type()
+            " and "
+            rhs.type_name().format_type()
+            "."
+        })
+        .with_body(concat! {
+            "Left-hand side:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(lhs) }
+            Doc::HardBreak Doc::HardBreak
+            "Right-hand side:"
+            Doc::HardBreak Doc::HardBreak
+            indent! { format_rcl_short(rhs) }
+        })
+}
+
 /// Highlight a span in a line.
 pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc<'a> {
     use std::cmp;
-    use unicode_width::UnicodeWidthStr;
+    use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
     let doc = &inputs[span.doc().0 as usize];
     let input = doc.data;
@@ -337,9 +541,8 @@ pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc
     }
 
     // Save this for reporting the error location, in case we adjust the line
-    // start below. Add 1 because the first column is column 1, not 0.
-    // TODO: This should measure width, not count bytes.
-    let column = 1 + span.start() - line_start;
+    // start below.
+    let (start, _end) = span.line_col(input);
 
     // If there is a really long line (for example, because you are evaluating
     // a multi-megabyte json document that is formatted without whitespace, on
@@ -379,17 +582,26 @@ pub fn highlight_span<'a>(inputs: &'a Inputs, span: Span, markup: Markup) -> Doc
 
     // The width of the error is not necessarily the number of bytes,
     // measure the Unicode width of the span to underline.
-    let indent_width = indent_content.width() + trunc_prefix.width();
     let mark_width = cmp::max(1, error_content.width());
 
+    // A tab does not have a fixed width, it depends on the column. The source
+    // line and the mark line have equally wide prefixes, so if we copy the
+    // tabs into the indent, the terminal expands them to the same width.
+    let mut mark_indent = " ".repeat(trunc_prefix.width());
+    for ch in indent_content.chars() {
+        match ch {
+            '\t' => mark_indent.push('\t'),
+            _ => mark_indent.extend(std::iter::repeat(' ').take(ch.width().unwrap_or(0))),
+        }
+    }
+
     let line_num_str = line.to_string();
     let line_num_pad: String = line_num_str.chars().map(|_| ' ').collect();
-    let mark_indent: String = " ".repeat(indent_width);
     let mark_under: String = "~".repeat(mark_width - 1);
     let doc_under = concat! { "^" mark_under };
 
     concat! {
-        doc.name format!(":{line}:{column}")
+        doc.name format!(":{start}")
         Doc::HardBreak
         line_num_pad.clone() " " Doc::from("╷").with_markup(markup)
         Doc::HardBreak
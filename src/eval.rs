@@ -11,9 +11,12 @@ use std::collections::HashMap;
 use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 
-use crate::ast::{BinOp, CallArg, Expr, FormatFragment, Seq, Stmt, UnOp, Yield};
-use crate::error::{Error, IntoError, Result};
-use crate::fmt_rcl::{self, format_rcl};
+use crate::ast::{BinOp, Binding, CallArg, Expr, FormatFragment, Pattern, Seq, Stmt, UnOp, Yield};
+use crate::error::{
+    binop_type_error, bool_ordering_help, condition_type_error, duplicate_set_element_error,
+    iteration_arity_error, not_iterable_error, unop_type_error, unpack_error, IntoError, Result,
+};
+use crate::fmt_rcl::{format_rcl, format_rcl_short};
 use crate::loader::Loader;
 use crate::pprint::{concat, indent, Doc};
 use crate::runtime::{
@@ -26,14 +29,6 @@ use crate::tracer::Tracer;
 use crate::typecheck;
 use crate::types;
 
-/// An entry on the evaluation stack.
-pub struct EvalContext {
-    /// The document that we are evaluating.
-    pub doc: DocId,
-    /// The source that caused evaluation of this document.
-    pub imported_from: Option<Span>,
-}
-
 /// A limiter to catch infinite loops.
 ///
 /// Some programs diverge, and instead of hanging, we want to report an error.
@@ -112,7 +107,6 @@ impl EvalCount {
 pub struct Evaluator<'a> {
     pub loader: &'a mut Loader,
     pub tracer: &'a mut dyn Tracer,
-    pub import_stack: Vec<EvalContext>,
 
     /// The single instance of the standard library.
     ///
@@ -142,7 +136,6 @@ impl<'a> Evaluator<'a> {
         Evaluator {
             loader,
             tracer,
-            import_stack: Vec::new(),
             stdlib: stdlib::initialize(),
             function_type_cache: HashMap::new(),
             eval_depth: 0,
@@ -199,62 +192,83 @@ impl<'a> Evaluator<'a> {
         value_env: &mut Env,
         doc: DocId,
     ) -> Result<Value> {
-        debug_assert!(self.import_stack.is_empty());
+        debug_assert!(self.loader.current_doc().is_none());
         let expr = self.loader.get_typechecked_ast(type_env, doc)?;
-        let ctx = EvalContext {
-            doc,
-            imported_from: None,
-        };
-        self.import_stack.push(ctx);
-        let result = self.eval_expr(value_env, &expr)?;
-        self.import_stack.pop().expect("Push/pop are balanced.");
-        Ok(result)
+        self.loader.push_import(doc, None)?;
+        let result = self.eval_expr(value_env, &expr);
+        self.loader.pop_import();
+        result
     }
 
-    /// Evaluate a document for an import.
-    fn eval_import(&mut self, doc: DocId, imported_from: Span) -> Result<Value> {
-        // Before we allow the import, check that this would not create a cycle.
-        let mut error: Option<Error> = None;
-        for ctx in &self.import_stack {
-            if ctx.doc == doc {
-                debug_assert!(
-                    error.is_none(),
-                    // coverage:off -- Error message not hit when there is no error.
-                    "There should not be a cycle in the stack already.",
-                    // coverage:on
-                );
-                let err = imported_from.error("This import creates a cycle.");
-                error = Some(err);
+    /// Evaluate a document as the entry point, streaming the elements of a list.
+    ///
+    /// When the body of the document, after any statements, is a list literal,
+    /// then instead of building the list, we call `on_elem` for every element
+    /// as soon as it is evaluated, and return `None`. This bounds the memory
+    /// needed for large outputs. `on_elem` receives the span of the document
+    /// body, to blame errors on. For other documents, this returns the value,
+    /// like [`Evaluator::eval_doc`].
+    pub fn eval_doc_streaming(
+        &mut self,
+        type_env: &mut typecheck::Env,
+        value_env: &mut Env,
+        doc: DocId,
+        on_elem: &mut dyn FnMut(Span, Value) -> Result<()>,
+    ) -> Result<Option<Value>> {
+        debug_assert!(self.loader.current_doc().is_none());
+        let expr = self.loader.get_typechecked_ast(type_env, doc)?;
+        self.loader.push_import(doc, None)?;
+        let body_span = self.loader.get_span(doc);
+        let result = self.eval_expr_streaming(value_env, &expr, &mut |v| on_elem(body_span, v));
+        self.loader.pop_import();
+        result
+    }
+
+    fn eval_expr_streaming(
+        &mut self,
+        env: &mut Env,
+        expr: &Expr,
+        on_elem: &mut dyn FnMut(Value) -> Result<()>,
+    ) -> Result<Option<Value>> {
+        match expr {
+            Expr::Stmt { stmt, body, .. } => {
+                let ck = env.checkpoint();
+                self.eval_stmt(env, stmt)?;
+                let result = self.eval_expr_streaming(env, body, on_elem)?;
+                env.pop(ck);
+                Ok(result)
             }
-            if let Some(error) = error.as_mut() {
-                if let Some(src) = ctx.imported_from {
-                    error.add_note(src, "Imported here.");
+            Expr::BracketLit { open, elements } => {
+                self.inc_eval_depth(*open)?;
+                for seq in elements {
+                    self.eval_seq(env, seq, &mut |_, v| on_elem(v), &mut |_, _| {
+                        unreachable!("Typechecker ensures scalar elements.")
+                    })?;
                 }
+                self.dec_eval_depth();
+                Ok(None)
             }
+            _ => self.eval_expr(env, expr).map(Some),
         }
-        if let Some(mut err) = error {
-            // Reverse the notes: we print the error itself first, and then we
-            // want to print what the offending file was imported from, etc.
-            err.notes.reverse();
-            return Err(err.into());
-        }
+    }
+
+    /// Evaluate a document for an import.
+    fn eval_import(&mut self, doc: DocId, imported_from: Span) -> Result<Value> {
+        // Before we allow the import, check that this would not create a cycle.
+        self.loader.push_import(doc, Some(imported_from))?;
 
         // Evaluate the import in its own clean environment, it should not be
         // affected by the surrounding environment of the import statement.
         let mut type_env = typecheck::prelude();
         let mut value_env = runtime::prelude();
 
-        let expr = self.loader.get_typechecked_ast(&mut type_env, doc)?;
-        let ctx = EvalContext {
-            doc,
-            imported_from: Some(imported_from),
-        };
-
-        self.import_stack.push(ctx);
-        let result = self.eval_expr(&mut value_env, &expr)?;
-        self.import_stack.pop().expect("Push/pop are balanced.");
+        let result = self
+            .loader
+            .get_typechecked_ast(&mut type_env, doc)
+            .and_then(|expr| self.eval_expr(&mut value_env, &expr));
+        self.loader.pop_import();
 
-        Ok(result)
+        result
     }
 
     fn eval_expr(&mut self, env: &mut Env, expr: &Expr) -> Result<Value> {
@@ -291,6 +305,7 @@ impl<'a> Evaluator<'a> {
                         if err.origin.is_none() {
                             err.origin = Some(*path_span);
                         }
+                        self.loader.add_import_notes(&mut err);
                         err
                     })?;
                 self.eval_import(doc, *path_span)
@@ -307,7 +322,11 @@ impl<'a> Evaluator<'a> {
                 let mut out = Vec::with_capacity(elements.len());
                 self.inc_eval_depth(*open)?;
                 for seq in elements {
-                    self.eval_seq(env, seq, &mut |v| out.push(v), &mut |_, _| {
+                    let mut on_scalar = |_, v| {
+                        out.push(v);
+                        Ok(())
+                    };
+                    self.eval_seq(env, seq, &mut on_scalar, &mut |_, _| {
                         unreachable!("Typechecker ensures scalar elements.")
                     })?;
                 }
@@ -317,9 +336,23 @@ impl<'a> Evaluator<'a> {
 
             Expr::SetLit { open, elements } => {
                 let mut out = BTreeSet::new();
+                // In strict mode we remember where every element came from,
+                // so we can point at both occurrences of a duplicate.
+                let mut spans = BTreeMap::new();
+                let strict = self.loader.is_strict();
                 self.inc_eval_depth(*open)?;
                 for seq in elements {
-                    self.eval_seq(env, seq, &mut |v| _ = out.insert(v), &mut |_, _| {
+                    let mut on_scalar = |span: Span, v: Value| {
+                        if strict {
+                            if let Some(first_span) = spans.get(&v) {
+                                return duplicate_set_element_error(*first_span, span, &v).err();
+                            }
+                            spans.insert(v.clone(), span);
+                        }
+                        out.insert(v);
+                        Ok(())
+                    };
+                    self.eval_seq(env, seq, &mut on_scalar, &mut |_, _| {
                         unreachable!("Typechecker ensures scalar elements.")
                     })?;
                 }
@@ -334,7 +367,7 @@ impl<'a> Evaluator<'a> {
                     self.eval_seq(
                         env,
                         seq,
-                        &mut |_| unreachable!("Typechecker ensures assoc elements."),
+                        &mut |_, _| unreachable!("Typechecker ensures assoc elements."),
                         &mut |k, v| _ = out.insert(k, v),
                     )?;
                 }
@@ -364,12 +397,46 @@ impl<'a> Evaluator<'a> {
                 let result = match cond {
                     Value::Bool(true) => self.eval_expr(env, body_then),
                     Value::Bool(false) => self.eval_expr(env, body_else),
-                    _ => unreachable!("The typechecker ensures the condition is a Bool."),
+                    other => condition_type_error(*condition_span, &other).err(),
                 };
                 self.dec_eval_depth();
                 result
             }
 
+            Expr::Match {
+                scrutinee_span,
+                scrutinee,
+                arms,
+                ..
+            } => {
+                self.inc_eval_depth(*scrutinee_span)?;
+                let value = self.eval_expr(env, scrutinee)?;
+                let mut result = None;
+                for arm in arms {
+                    let is_match = match &arm.pattern {
+                        Pattern::Wildcard => true,
+                        Pattern::Literal(pattern) => self.eval_expr(env, pattern)? == value,
+                    };
+                    if is_match {
+                        result = Some(self.eval_expr(env, &arm.body)?);
+                        break;
+                    }
+                }
+                self.dec_eval_depth();
+                match result {
+                    Some(v) => Ok(v),
+                    None => scrutinee_span
+                        .error("No arm matches this value.")
+                        .with_body(concat! {
+                            "The value is:"
+                            Doc::HardBreak Doc::HardBreak
+                            indent! { format_rcl_short(&value) }
+                        })
+                        .with_help("Add an arm with the wildcard pattern '_' to match any value.")
+                        .err(),
+                }
+            }
+
             Expr::Var { ident, .. } => match env.lookup(ident) {
                 Some(value) => Ok(value.clone()),
                 // TODO: The typechecker could replace all variable lookups with
@@ -394,10 +461,12 @@ impl<'a> Evaluator<'a> {
                     (Value::String(_), "chars") => Some(&stdlib::STRING_CHARS),
                     (Value::String(_), "contains") => Some(&stdlib::STRING_CONTAINS),
                     (Value::String(_), "ends_with") => Some(&stdlib::STRING_ENDS_WITH),
+                    (Value::String(_), "index_of") => Some(&stdlib::STRING_INDEX_OF),
                     (Value::String(_), "len") => Some(&stdlib::STRING_LEN),
                     (Value::String(_), "parse_int") => Some(&stdlib::STRING_PARSE_INT),
                     (Value::String(_), "remove_prefix") => Some(&stdlib::STRING_REMOVE_PREFIX),
                     (Value::String(_), "remove_suffix") => Some(&stdlib::STRING_REMOVE_SUFFIX),
+                    (Value::String(_), "repeat") => Some(&stdlib::STRING_REPEAT),
                     (Value::String(_), "replace") => Some(&stdlib::STRING_REPLACE),
                     (Value::String(_), "split") => Some(&stdlib::STRING_SPLIT),
                     (Value::String(_), "split_lines") => Some(&stdlib::STRING_SPLIT_LINES),
@@ -407,9 +476,13 @@ impl<'a> Evaluator<'a> {
 
                     (Value::Dict(_), "contains") => Some(&stdlib::DICT_CONTAINS),
                     (Value::Dict(_), "except") => Some(&stdlib::DICT_EXCEPT),
+                    (Value::Dict(_), "fetch") => Some(&stdlib::DICT_FETCH),
                     (Value::Dict(_), "get") => Some(&stdlib::DICT_GET),
+                    (Value::Dict(_), "get_or_else") => Some(&stdlib::DICT_GET_OR_ELSE),
+                    (Value::Dict(_), "has") => Some(&stdlib::DICT_HAS),
                     (Value::Dict(_), "keys") => Some(&stdlib::DICT_KEYS),
                     (Value::Dict(_), "len") => Some(&stdlib::DICT_LEN),
+                    (Value::Dict(_), "map_values") => Some(&stdlib::DICT_MAP_VALUES),
                     (Value::Dict(_), "values") => Some(&stdlib::DICT_VALUES),
                     (Value::Dict(fields), _field_name) => {
                         // If it wasn't a builtin, look for a key in the dict.
@@ -420,12 +493,7 @@ impl<'a> Evaluator<'a> {
                                     .error("Unknown field.")
                                     .with_note(
                                         *inner_span,
-                                        concat! {
-                                            // TODO: Printing the full value may be overkill,
-                                            // the full value could be very large. We
-                                            // could print the dict keys here.
-                                            "On value: " format_rcl(&inner).into_owned()
-                                        },
+                                        concat! { "On value: " format_rcl_short(&inner) },
                                     )
                                     .err();
                             }
@@ -444,6 +512,8 @@ impl<'a> Evaluator<'a> {
                     (Value::List(_), "map") => Some(&stdlib::LIST_MAP),
                     (Value::List(_), "reverse") => Some(&stdlib::LIST_REVERSE),
                     (Value::List(_), "sum") => Some(&stdlib::LIST_SUM),
+                    (Value::List(_), "unique") => Some(&stdlib::LIST_UNIQUE),
+                    (Value::List(_), "zip") => Some(&stdlib::LIST_ZIP),
 
                     (Value::Set(_), "contains") => Some(&stdlib::SET_CONTAINS),
                     (Value::Set(_), "except") => Some(&stdlib::SET_EXCEPT),
@@ -467,19 +537,13 @@ impl<'a> Evaluator<'a> {
                         };
                         Ok(Value::BuiltinMethod(Rc::new(instance)))
                     }
-                    None => {
-                        field_span
-                            .error("Unknown field.")
-                            .with_note(
-                                *inner_span,
-                                concat! {
-                                    // TODO: Printing the full value may be overkill,
-                                    // the full value could be very large.
-                                    "On value: " format_rcl(&inner).into_owned()
-                                },
-                            )
-                            .err()
-                    }
+                    None => field_span
+                        .error("Unknown field.")
+                        .with_note(
+                            *inner_span,
+                            concat! { "On value: " format_rcl_short(&inner) },
+                        )
+                        .err(),
                 }
             }
 
@@ -535,6 +599,27 @@ impl<'a> Evaluator<'a> {
                 self.eval_index(*open, collection, *collection_span, index, *index_span)
             }
 
+            Expr::Slice {
+                open,
+                collection_span,
+                collection: collection_expr,
+                start: start_expr,
+                end: end_expr,
+                ..
+            } => {
+                let collection = self.eval_expr(env, collection_expr)?;
+                let mut bounds = [None, None];
+                for (bound, bound_expr) in bounds.iter_mut().zip([start_expr, end_expr]) {
+                    if let Some((_, bound_expr)) = bound_expr {
+                        match self.eval_expr(env, bound_expr)? {
+                            Value::Int(i) => *bound = Some(i),
+                            _ => unreachable!("The typechecker ensures slice bounds are Int."),
+                        }
+                    }
+                }
+                self.eval_slice(*open, collection, *collection_span, bounds[0], bounds[1])
+            }
+
             // coverage:off -- Not covered if it's really unreachable.
             Expr::Function { .. } => unreachable!(
                 "The typechecker replaces all Expr::Function with Expr::TypedFunction.",
@@ -663,19 +748,102 @@ impl<'a> Evaluator<'a> {
     ///
     /// This does not perform all required checks; use [`eval_call`] to evaluate
     /// a general call to any callable value.
+    ///
+    /// When the body ends in a call to the function itself, we evaluate that
+    /// call in a loop, rather than through native recursion, so tail-recursive
+    /// functions are not limited by the evaluation depth.
     fn eval_function_call(&mut self, fun: &Function, call: FunctionCall) -> Result<Value> {
-        // TODO: If we could stack multiple layers of envs, then we would not
-        // have to clone the full thing.
-        let mut env = fun.env.clone();
-        for (arg, CallArg { value, .. }) in fun.type_.args.iter().zip(call.args) {
-            let arg_name = arg
-                .name
-                .as_ref()
-                .expect("Types attached to functions have arg names.");
-            env.push(arg_name.clone(), value.clone());
+        let mut args = call.args.iter().map(|arg| arg.value.clone()).collect();
+        loop {
+            // TODO: If we could stack multiple layers of envs, then we would not
+            // have to clone the full thing.
+            let mut env = fun.env.clone();
+            for (arg, value) in fun.type_.args.iter().zip(args) {
+                let arg_name = arg
+                    .name
+                    .as_ref()
+                    .expect("Types attached to functions have arg names.");
+                env.push(arg_name.clone(), value);
+            }
+
+            match self.eval_tail(&mut env, fun, fun.body.as_ref())? {
+                Tail::Value(result) => return Ok(result),
+                Tail::SelfCall(next_args) => args = next_args,
+            }
         }
+    }
+
+    /// Evaluate the body of `fun`, but return a call to `fun` in tail position unevaluated.
+    fn eval_tail(&mut self, env: &mut Env, fun: &Function, expr: &Expr) -> Result<Tail> {
+        match expr {
+            Expr::Stmt { stmt, body, .. } => {
+                let ck = env.checkpoint();
+                self.eval_stmt(env, stmt)?;
+                let result = self.eval_tail(env, fun, body)?;
+                env.pop(ck);
+                Ok(result)
+            }
+
+            Expr::IfThenElse {
+                condition_span,
+                condition,
+                body_then,
+                body_else,
+                ..
+            } => {
+                self.inc_eval_depth(*condition_span)?;
+                let cond = self.eval_expr(env, condition)?;
+                self.dec_eval_depth();
+                match cond {
+                    Value::Bool(true) => self.eval_tail(env, fun, body_then),
+                    Value::Bool(false) => self.eval_tail(env, fun, body_else),
+                    other => condition_type_error(*condition_span, &other).err(),
+                }
+            }
+
+            Expr::Call {
+                open,
+                close,
+                function_span,
+                function: fun_expr,
+                args: args_exprs,
+            } => {
+                self.inc_eval_depth(*function_span)?;
+                let callee = self.eval_expr(env, fun_expr)?;
+                let args = args_exprs
+                    .iter()
+                    .map(|call_arg| {
+                        Ok(CallArg {
+                            span: call_arg.span,
+                            value: self.eval_expr(env, &call_arg.value)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                self.dec_eval_depth();
 
-        self.eval_expr(&mut env, fun.body.as_ref())
+                match &callee {
+                    Value::Function(callee_fun) if std::ptr::eq(callee_fun.as_ref(), fun) => {
+                        // The self-call still counts against the evaluation
+                        // budget, it just does not use up the depth.
+                        self.eval_count.inc(*open)?;
+                        fun.type_.check_arity(None, &args, *close)?;
+                        let args = args.into_iter().map(|arg| arg.value).collect();
+                        Ok(Tail::SelfCall(args))
+                    }
+                    _ => {
+                        let call = FunctionCall {
+                            call_open: *open,
+                            call_close: *close,
+                            args: &args[..],
+                        };
+                        self.eval_call(*function_span, &callee, call)
+                            .map(Tail::Value)
+                    }
+                }
+            }
+
+            _ => self.eval_expr(env, expr).map(Tail::Value),
+        }
     }
 
     /// While joining values for string formatting, push one fragment.
@@ -693,7 +861,7 @@ impl<'a> Evaluator<'a> {
                         "This value cannot be interpolated into a string:"
                         Doc::HardBreak
                         Doc::HardBreak
-                        indent! { format_rcl(not_formattable).into_owned() }
+                        indent! { format_rcl_short(not_formattable) }
                     })
                     .err();
             }
@@ -702,7 +870,12 @@ impl<'a> Evaluator<'a> {
     }
 
     /// Join fragments pushed by [`push_format_fragment`] into one string.
-    pub fn join_format_fragments(fragments: Vec<Rc<str>>) -> Value {
+    pub fn join_format_fragments(mut fragments: Vec<Rc<str>>) -> Value {
+        // A single fragment, such as in `f"{name}"`, we can share rather than copy.
+        if fragments.len() == 1 {
+            return Value::String(fragments.pop().expect("Has one element."));
+        }
+
         let mut result = String::with_capacity(fragments.iter().map(|s| s.len()).sum());
 
         for s in fragments {
@@ -743,7 +916,7 @@ impl<'a> Evaluator<'a> {
             not_indexable => {
                 let note = concat! {
                     "Expected a dict or list, but found: "
-                    format_rcl(&not_indexable).into_owned()
+                    format_rcl_short(&not_indexable)
                     "."
                 };
                 open_span
@@ -754,6 +927,45 @@ impl<'a> Evaluator<'a> {
         }
     }
 
+    fn eval_slice(
+        &mut self,
+        open_span: Span,
+        collection: Value,
+        collection_span: Span,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Result<Value> {
+        match collection {
+            Value::List(xs) => {
+                let (start, end) = slice_bounds(xs.len(), start, end);
+                Ok(Value::List(Rc::new(xs[start..end].to_vec())))
+            }
+            Value::String(s) => {
+                // Bounds are in Unicode scalar values, so we have to map them
+                // back to byte offsets, which are always on a char boundary.
+                let (start, end) = slice_bounds(s.chars().count(), start, end);
+                let mut offsets = s.char_indices().map(|(i, _)| i).chain([s.len()]);
+                let start_byte = offsets.nth(start).expect("Start is clamped to the length.");
+                let end_byte = match end - start {
+                    0 => start_byte,
+                    n => offsets.nth(n - 1).expect("End is clamped to the length."),
+                };
+                Ok(Value::String(s[start_byte..end_byte].into()))
+            }
+            not_sliceable => {
+                let note = concat! {
+                    "Expected a list or string, but found: "
+                    format_rcl_short(&not_sliceable)
+                    "."
+                };
+                open_span
+                    .error("Slicing is not supported here.")
+                    .with_note(collection_span, note)
+                    .err()
+            }
+        }
+    }
+
     fn eval_index_list(&mut self, list: &[Value], index: Value, index_span: Span) -> Result<Value> {
         let i_signed = match index {
             Value::Int(i) => i,
@@ -782,7 +994,7 @@ impl<'a> Evaluator<'a> {
 
     fn eval_index_dict(
         &mut self,
-        dict: &BTreeMap<Value, Value>,
+        dict: &Rc<BTreeMap<Value, Value>>,
         dict_span: Span,
         index: Value,
         index_span: Span,
@@ -791,14 +1003,14 @@ impl<'a> Evaluator<'a> {
             None => index_span
                 .error(concat! {
                     "Dict does not have a key "
-                    format_rcl(&index).into_owned()
+                    format_rcl_short(&index)
                     "."
                 })
                 .with_note(
                     dict_span,
                     concat! {
                         "On value: "
-                        fmt_rcl::dict(dict.iter()).into_owned()
+                        format_rcl_short(&Value::Dict(dict.clone()))
                     },
                 )
                 .err(),
@@ -813,12 +1025,14 @@ impl<'a> Evaluator<'a> {
                 Some(nx) => Ok(Value::Int(nx)),
                 None => {
                     let err = concat! {
-                        "Negation of " x.to_string() " would overflow."
+                        "Negation of " format_rcl_short(&Value::Int(x)) " would overflow."
                     };
                     op_span.error(err).err()
                 }
             },
-            _ => unreachable!("Invalid cases are prevented by the typechecker."),
+            // The typechecker rules out invalid operands where it can do so
+            // statically, but it leaves the check of other operands to us.
+            (op, v) => unop_type_error(op_span, op, &v).err(),
         }
     }
 
@@ -840,27 +1054,47 @@ impl<'a> Evaluator<'a> {
                 result.extend(ys.iter().cloned());
                 Ok(Value::Set(Rc::new(result)))
             }
-            (BinOp::Union, _, _) => {
-                // We could make a nicer error and include the values, but I plan
-                // to remove | in favor of unpack, so I'm not going to bother.
-                op_span
-                    .error(concat! {
-                        "Union operator " Doc::highlight("|")
-                        " is not supported between these values. "
-                    })
-                    .with_help("The left-hand side must be a dict or set.")
-                    .err()
-            }
+            (BinOp::Union, Value::Int(x), Value::Int(y)) => Ok(Value::Int(x | y)),
+            (BinOp::Union, lhs, rhs) => binop_type_error(op_span, op, &lhs, &rhs)
+                .with_help(
+                    "The left-hand side must be a dict or set, or both sides must be integers.",
+                )
+                .err(),
             // TODO: Could evaluate these boolean expressions lazily, if the
             // language is really pure. But if I enable external side effects like
             // running a program to read its input, that would be questionable to do.
             (BinOp::And, Value::Bool(x), Value::Bool(y)) => Ok(Value::Bool(x && y)),
             (BinOp::Or, Value::Bool(x), Value::Bool(y)) => Ok(Value::Bool(x || y)),
+            (BinOp::Xor, Value::Bool(x), Value::Bool(y)) => Ok(Value::Bool(x != y)),
+            (BinOp::BitAnd, Value::Int(x), Value::Int(y)) => Ok(Value::Int(x & y)),
+            (BinOp::BitXor, Value::Int(x), Value::Int(y)) => Ok(Value::Int(x ^ y)),
+            (BinOp::Shl | BinOp::Shr, Value::Int(x), Value::Int(y)) => {
+                let n = match u32::try_from(y) {
+                    Ok(n) if n < 64 => n,
+                    _ => {
+                        let err = concat! { "Shift amount " y.to_string() " is out of range." };
+                        return op_span
+                            .error(err)
+                            .with_help("The shift amount must be between 0 and 63.")
+                            .err();
+                    }
+                };
+                // Left shifts discard the bits shifted out, right shifts are
+                // arithmetic: they preserve the sign.
+                match op {
+                    BinOp::Shl => Ok(Value::Int(x << n)),
+                    _ => Ok(Value::Int(x >> n)),
+                }
+            }
             (BinOp::Add, Value::Int(x), Value::Int(y)) => match x.checked_add(y) {
                 Some(z) => Ok(Value::Int(z)),
                 None => {
                     let err = concat! {
-                        "Addition " x.to_string() " + " y.to_string() " would overflow."
+                        "Addition "
+                        format_rcl_short(&Value::Int(x))
+                        " + "
+                        format_rcl_short(&Value::Int(y))
+                        " would overflow."
                     };
                     op_span.error(err).err()
                 }
@@ -869,7 +1103,11 @@ impl<'a> Evaluator<'a> {
                 Some(z) => Ok(Value::Int(z)),
                 None => {
                     let err = concat! {
-                        "Subtraction " x.to_string() " - " y.to_string() " would overflow."
+                        "Subtraction "
+                        format_rcl_short(&Value::Int(x))
+                        " - "
+                        format_rcl_short(&Value::Int(y))
+                        " would overflow."
                     };
                     op_span.error(err).err()
                 }
@@ -878,11 +1116,46 @@ impl<'a> Evaluator<'a> {
                 Some(z) => Ok(Value::Int(z)),
                 None => {
                     let err = concat! {
-                        "Multiplication " x.to_string() " * " y.to_string() " would overflow."
+                        "Multiplication "
+                        format_rcl_short(&Value::Int(x))
+                        " * "
+                        format_rcl_short(&Value::Int(y))
+                        " would overflow."
                     };
                     op_span.error(err).err()
                 }
             },
+            (BinOp::Pow, Value::Int(x), Value::Int(y)) => {
+                let z = match u32::try_from(y) {
+                    Ok(n) => x.checked_pow(n),
+                    Err(..) if y < 0 => {
+                        let err = concat! {
+                            "Exponent " y.to_string() " is negative, but the result must be an integer."
+                        };
+                        return op_span.error(err).err();
+                    }
+                    // The exponent is too large for `checked_pow`, which can
+                    // only be fine when the base is 0, 1, or -1.
+                    Err(..) => match x {
+                        0 | 1 => Some(x),
+                        -1 => Some(if y % 2 == 0 { 1 } else { -1 }),
+                        _ => None,
+                    },
+                };
+                match z {
+                    Some(z) => Ok(Value::Int(z)),
+                    None => {
+                        let err = concat! {
+                            "Exponentiation "
+                            format_rcl_short(&Value::Int(x))
+                            " ** "
+                            format_rcl_short(&Value::Int(y))
+                            " would overflow."
+                        };
+                        op_span.error(err).err()
+                    }
+                }
+            }
             (BinOp::Div, Value::Int(x), Value::Int(y)) => {
                 if y == 0 {
                     op_span.error("Division by zero.").err()
@@ -911,6 +1184,13 @@ impl<'a> Evaluator<'a> {
             (BinOp::Gt, Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x > y)),
             (BinOp::LtEq, Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x <= y)),
             (BinOp::GtEq, Value::Int(x), Value::Int(y)) => Ok(Value::Bool(x >= y)),
+            (op, lhs @ Value::Bool(..), rhs) | (op, lhs, rhs @ Value::Bool(..))
+                if op.is_ordering() =>
+            {
+                binop_type_error(op_span, op, &lhs, &rhs)
+                    .with_help(bool_ordering_help(op))
+                    .err()
+            }
             // TODO: Throw a type error when the types are not the same, instead of
             // enabling comparing values of different types. Or do we want to allow
             // comparing arbitrary values after all? Hmm ... So far I haven't felt
@@ -918,19 +1198,28 @@ impl<'a> Evaluator<'a> {
             // maybe it should be a type error?
             (BinOp::Eq, x, y) => Ok(Value::Bool(x == y)),
             (BinOp::Neq, x, y) => Ok(Value::Bool(x != y)),
-            _ => unreachable!("Invalid cases are prevented by the typechecker."),
+            (BinOp::Range, Value::Int(x), Value::Int(y)) => {
+                stdlib::range_impl(op_span, x, y, false)
+            }
+            (BinOp::RangeInclusive, Value::Int(x), Value::Int(y)) => {
+                stdlib::range_impl(op_span, x, y, true)
+            }
+            // Other invalid cases are prevented by the typechecker, which
+            // inserts runtime type checks where it cannot rule them out,
+            // except for ordering operators, which we check here.
+            (op, lhs, rhs) => binop_type_error(op_span, op, &lhs, &rhs).err(),
         }
     }
 
     fn eval_stmt(&mut self, env: &mut Env, stmt: &Stmt) -> Result<()> {
         match stmt {
-            Stmt::Let { ident, value, .. } => {
+            Stmt::Let { binding, value, .. } => {
                 // Note, this is not a recursive let, the variable is not bound
                 // when we evaluate the expression. Even if the let binding has
                 // a type annotation, we don't check it here; the typechecker
                 // inserts a dedicated `CheckType` node when needed.
                 let v = self.eval_expr(env, value)?;
-                env.push(ident.clone(), v);
+                bind(env, binding, v)?;
             }
             Stmt::Assert {
                 condition_span,
@@ -979,16 +1268,16 @@ impl<'a> Evaluator<'a> {
         on_assoc: &mut OnAssoc,
     ) -> Result<()>
     where
-        OnScalar: FnMut(Value),
+        OnScalar: FnMut(Span, Value) -> Result<()> + ?Sized,
         OnAssoc: FnMut(Value, Value),
     {
         match seq {
             Seq::Yield(Yield::Elem {
-                value: value_expr, ..
+                span,
+                value: value_expr,
             }) => {
                 let value = self.eval_expr(env, value_expr)?;
-                on_scalar(value);
-                Ok(())
+                on_scalar(*span, value)
             }
             Seq::Yield(Yield::Assoc {
                 key: key_expr,
@@ -1001,74 +1290,87 @@ impl<'a> Evaluator<'a> {
                 Ok(())
             }
             Seq::For {
-                idents_span,
-                idents,
+                bindings_span,
+                bindings,
                 collection_span,
                 collection,
                 body,
             } => {
                 let collection_value = self.eval_expr(env, collection)?;
-                match (&idents[..], collection_value) {
-                    ([name], Value::List(xs)) => {
+                match (&bindings[..], collection_value) {
+                    ([binding], Value::List(xs)) => {
                         for x in xs.iter() {
-                            let ck = env.push(name.clone(), x.clone());
+                            let ck = env.checkpoint();
+                            bind(env, binding, x.clone())?;
                             self.eval_seq(env, body, on_scalar, on_assoc)?;
                             env.pop(ck);
                         }
                         Ok(())
                     }
-                    (_names, Value::List(..)) => {
-                        let err = idents_span.error("Expected a single variable.").with_note(
-                            *collection_span,
-                            "This is a list, it yields one element per iteration.",
-                        );
-                        Err(err.into())
+                    (names, Value::List(..)) => {
+                        iteration_arity_error(*bindings_span, names.len(), *collection_span, "List")
+                            .err()
                     }
-                    ([name], Value::Set(xs)) => {
+                    ([binding], Value::Set(xs)) => {
                         for x in xs.iter() {
-                            let ck = env.push(name.clone(), x.clone());
+                            let ck = env.checkpoint();
+                            bind(env, binding, x.clone())?;
                             self.eval_seq(env, body, on_scalar, on_assoc)?;
                             env.pop(ck);
                         }
                         Ok(())
                     }
-                    (_names, Value::Set(..)) => {
-                        let err = idents_span.error("Expected a single variable.").with_note(
-                            *collection_span,
-                            "This is a set, it yields one element per iteration.",
-                        );
-                        Err(err.into())
+                    (names, Value::Set(..)) => {
+                        iteration_arity_error(*bindings_span, names.len(), *collection_span, "Set")
+                            .err()
                     }
-                    ([k_name, v_name], Value::Dict(xs)) => {
+                    ([k_binding, v_binding], Value::Dict(xs)) => {
                         for (k, v) in xs.iter() {
                             let ck = env.checkpoint();
-                            env.push(k_name.clone(), k.clone());
-                            env.push(v_name.clone(), v.clone());
+                            bind(env, k_binding, k.clone())?;
+                            bind(env, v_binding, v.clone())?;
+                            self.eval_seq(env, body, on_scalar, on_assoc)?;
+                            env.pop(ck);
+                        }
+                        Ok(())
+                    }
+                    (names, Value::Dict(..)) => {
+                        iteration_arity_error(*bindings_span, names.len(), *collection_span, "Dict")
+                            .err()
+                    }
+                    ([binding], Value::String(s)) => {
+                        // Like `String.chars`, every iteration binds a string
+                        // that holds a single code point.
+                        for (i, ch) in s.char_indices() {
+                            let ck = env.checkpoint();
+                            bind(env, binding, Value::from(&s[i..i + ch.len_utf8()]))?;
                             self.eval_seq(env, body, on_scalar, on_assoc)?;
                             env.pop(ck);
                         }
                         Ok(())
                     }
-                    (_names, Value::Dict(..)) => {
-                        let err = idents_span
-                            .error("Expected two variables in dict iteration.")
-                            .with_note(
-                                *collection_span,
-                                "This is a dict, it yields a key and value per iteration.",
-                            );
-                        Err(err.into())
+                    (names, Value::String(..)) => iteration_arity_error(
+                        *bindings_span,
+                        names.len(),
+                        *collection_span,
+                        "String",
+                    )
+                    .err(),
+                    (_names, not_collection) => {
+                        not_iterable_error(*collection_span, &not_collection).err()
                     }
-                    _ => Err(collection_span.error("This is not iterable.").into()),
                 }
             }
             Seq::If {
-                condition, body, ..
+                condition_span,
+                condition,
+                body,
             } => {
                 let cond = self.eval_expr(env, condition)?;
                 match cond {
                     Value::Bool(true) => self.eval_seq(env, body, on_scalar, on_assoc),
                     Value::Bool(false) => Ok(()),
-                    _ => unreachable!("The typechecker ensures the condition is a Bool."),
+                    other => condition_type_error(*condition_span, &other).err(),
                 }
             }
             Seq::Stmt { stmt, body } => {
@@ -1081,3 +1383,49 @@ impl<'a> Evaluator<'a> {
         }
     }
 }
+
+/// The result of evaluating a function body with [`Evaluator::eval_tail`].
+enum Tail {
+    /// The body evaluated to this value.
+    Value(Value),
+
+    /// The body ends in a call to the function itself, with these arguments.
+    SelfCall(Vec<Value>),
+}
+
+/// Bind the value to the names in the binding, unpacking lists as needed.
+fn bind(env: &mut Env, binding: &Binding, value: Value) -> Result<()> {
+    match binding {
+        Binding::Ident { ident, .. } => {
+            env.push(ident.clone(), value);
+            Ok(())
+        }
+        Binding::List { span, elements } => match &value {
+            Value::List(xs) if xs.len() == elements.len() => {
+                for (element, x) in elements.iter().zip(xs.iter()) {
+                    bind(env, element, x.clone())?;
+                }
+                Ok(())
+            }
+            _ => unpack_error(*span, elements.len(), &value).err(),
+        },
+    }
+}
+
+/// Resolve optional slice bounds against a sequence of length `len`.
+///
+/// Negative bounds count from the end, and bounds that fall outside the
+/// sequence are clamped to it. When the lower bound exceeds the upper bound,
+/// the result is an empty range.
+fn slice_bounds(len: usize, start: Option<i64>, end: Option<i64>) -> (usize, usize) {
+    let resolve = |bound: i64| -> usize {
+        if bound < 0 {
+            (len as i64).saturating_add(bound).max(0) as usize
+        } else {
+            bound.min(len as i64) as usize
+        }
+    };
+    let end = end.map(resolve).unwrap_or(len);
+    let start = start.map(resolve).unwrap_or(0).min(end);
+    (start, end)
+}
@@ -11,7 +11,10 @@
 //! pretty-printed for formatting.
 
 use crate::ast::UnOp;
-use crate::cst::{Chain, Expr, List, NonCode, Prefixed, Seq, Stmt, StringPart, Type};
+use crate::cst::{
+    BinOp, Binding, Chain, Expr, List, MatchArm, NonCode, Pattern, Prefixed, Seq, Stmt, StringPart,
+    Type,
+};
 use crate::lexer::{QuoteStyle, StringPrefix};
 use crate::markup::Markup;
 use crate::pprint::{concat, flush_indent, group, indent, Doc};
@@ -102,6 +105,10 @@ impl<'a> Formatter<'a> {
                     result.push(self.span(*span).with_markup(Markup::Comment));
                     result.push(Doc::HardBreak);
                 }
+                NonCode::BlockComment(span) => {
+                    result.push(self.block_comment(*span).with_markup(Markup::Comment));
+                    result.push(Doc::HardBreak);
+                }
                 NonCode::Shebang(span) => {
                     result.push(self.span(*span));
                     result.push(Doc::HardBreak);
@@ -112,20 +119,67 @@ impl<'a> Formatter<'a> {
         Doc::Concat(result)
     }
 
+    /// Format a block comment, re-indenting its continuation lines.
+    ///
+    /// Continuation lines keep their indentation relative to the opening `/*`,
+    /// so that they stay aligned when the comment moves to a different column.
+    pub fn block_comment(&self, span: Span) -> Doc<'a> {
+        let line_start = self.input[..span.start()]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let column = span.start() - line_start;
+
+        let mut result = Vec::new();
+        for (i, line) in span.resolve(self.input).split('\n').enumerate() {
+            let line = match i {
+                0 => line,
+                _ => {
+                    result.push(Doc::HardBreak);
+                    let n_strip = line
+                        .bytes()
+                        .take(column)
+                        .take_while(|ch| ch.is_ascii_whitespace())
+                        .count();
+                    line[n_strip..].trim_end()
+                }
+            };
+            if !line.is_empty() {
+                result.push(Doc::str(line));
+            }
+        }
+
+        Doc::Concat(result)
+    }
+
     /// The final trailing comma in a collection (also call args, type args, etc.).
     ///
-    /// Also includes the soft break after the comma.
+    /// Also includes the soft break after the comma. The policy is to have a
+    /// trailing comma if and only if the collection is formatted tall. Because
+    /// a trailing comma in the input forces the collection to be tall, see
+    /// [`Formatter::collection_opening_sep`], the output of a second pass is
+    /// the same as the first. The value printer in `fmt_rcl` follows the same
+    /// policy, so its output is formatted already.
+    ///
+    /// The policy is deliberately not configurable. The formatter has no style
+    /// options besides the width, so that all RCL code is formatted the same,
+    /// and an option to omit the comma would defeat the magic trailing comma.
     pub fn trailing_comma<T>(&self, list: &List<T>) -> Doc<'a> {
-        if !list.suffix.is_empty() {
+        if list.elements.is_empty() {
+            // Without elements there is nothing to put a comma after, but we
+            // may still need to preserve a suffix, like a comment.
             return concat! {
-                ","
                 Doc::SoftBreak
                 self.non_code(&list.suffix)
             };
         }
 
-        if list.elements.is_empty() {
-            return Doc::SoftBreak;
+        if !list.suffix.is_empty() {
+            return concat! {
+                ","
+                Doc::SoftBreak
+                self.non_code(&list.suffix)
+            };
         }
 
         concat! {
@@ -250,10 +304,25 @@ impl<'a> Formatter<'a> {
         }
     }
 
+    /// Format the left-hand side of a let-binding or loop variable.
+    ///
+    /// Bindings are never broken over multiple lines, and the parser does not
+    /// allow comments inside them.
+    pub fn binding(&self, binding: &Binding) -> Doc<'a> {
+        match binding {
+            Binding::Ident(span) => self.span(*span),
+            Binding::List { elements, .. } => concat! {
+                "["
+                Doc::join(elements.iter().map(|b| self.binding(b)), ", ".into())
+                "]"
+            },
+        }
+    }
+
     pub fn stmt(&self, stmt: &Stmt) -> Doc<'a> {
         match stmt {
             Stmt::Let {
-                ident,
+                binding,
                 value,
                 type_,
                 ..
@@ -261,7 +330,7 @@ impl<'a> Formatter<'a> {
                 let mut result: Vec<Doc<'a>> = Vec::new();
                 result.push(Doc::str("let").with_markup(Markup::Keyword));
                 result.push(" ".into());
-                result.push(self.span(*ident));
+                result.push(self.binding(binding));
                 if let Some(t) = type_ {
                     result.push(": ".into());
                     result.push(self.type_(t));
@@ -350,6 +419,8 @@ impl<'a> Formatter<'a> {
                 }
             }
 
+            Expr::EmptyDictLit { .. } => Doc::str("{:}"),
+
             Expr::BracketLit { elements, .. } => {
                 if elements.elements.is_empty() && elements.suffix.is_empty() {
                     Doc::str("[]")
@@ -433,9 +504,33 @@ impl<'a> Formatter<'a> {
                 }
             }
 
+            // Match expressions are always tall, with one arm per line.
+            Expr::Match {
+                scrutinee, arms, ..
+            } => {
+                let mut result = vec![
+                    Doc::str("match").with_markup(Markup::Keyword),
+                    " ".into(),
+                    self.expr(scrutinee),
+                    " {".into(),
+                    Doc::HardBreak,
+                ];
+                let mut arms_doc = Vec::new();
+                for arm in arms.elements.iter() {
+                    arms_doc.push(self.non_code(&arm.prefix));
+                    arms_doc.push(self.match_arm(&arm.inner));
+                    arms_doc.push(",".into());
+                    arms_doc.push(Doc::HardBreak);
+                }
+                arms_doc.push(self.non_code(&arms.suffix));
+                result.push(indent! { Doc::Concat(arms_doc) });
+                result.push("}".into());
+                Doc::Concat(result)
+            }
+
             Expr::Function { args, body, .. } => {
                 let args_doc: Doc = match args.elements.len() {
-                    0 => Doc::str("()"),
+                    0 if args.suffix.is_empty() => Doc::str("()"),
                     // Don't put parens around the argument if there is a single
                     // argument that has no comments on it. If it has comments,
                     // then we need the parens, because otherwise we might
@@ -475,6 +570,19 @@ impl<'a> Formatter<'a> {
                 },
             },
 
+            // Ranges are formatted without spaces around the operator, as `0..n`.
+            Expr::BinOp {
+                op: BinOp::Range | BinOp::RangeInclusive,
+                op_span,
+                lhs,
+                rhs,
+                ..
+            } => concat! {
+                self.expr(lhs)
+                self.span(*op_span)
+                self.expr(rhs)
+            },
+
             // TODO: Make this a collection in the parser, so we can toggle
             // operator chains into all-wide or all-tall but not mixed.
             Expr::BinOp {
@@ -497,6 +605,17 @@ impl<'a> Formatter<'a> {
         }
     }
 
+    /// Format an arm of a match expression.
+    pub fn match_arm(&self, arm: &MatchArm) -> Doc<'a> {
+        let pattern = match &arm.pattern {
+            Pattern::Wildcard(span) => self.span(*span),
+            Pattern::Literal { value, .. } => self.expr(value),
+        };
+        concat! {
+            pattern " => " self.expr(&arm.body)
+        }
+    }
+
     /// Format a chained expression.
     pub fn chain(&self, base: &Expr, chain: &[(Span, Chain)]) -> Doc<'a> {
         // Every field should start a new line. When we have a call or index,
@@ -543,6 +662,19 @@ impl<'a> Formatter<'a> {
                     };
                     group.push(index_doc);
                 }
+
+                Chain::Slice { start, end, .. } => {
+                    let mut slice_doc = vec!["[".into()];
+                    if let Some((_, start)) = start {
+                        slice_doc.push(self.expr(start));
+                    }
+                    slice_doc.push(":".into());
+                    if let Some((_, end)) = end {
+                        slice_doc.push(self.expr(end));
+                    }
+                    slice_doc.push("]".into());
+                    group.push(Doc::Concat(slice_doc));
+                }
             }
         }
 
@@ -643,7 +775,7 @@ impl<'a> Formatter<'a> {
             }
 
             Seq::For {
-                idents,
+                bindings,
                 collection,
                 body,
                 ..
@@ -656,7 +788,7 @@ impl<'a> Formatter<'a> {
                     // cannot break this over multiple lines. But maybe that's
                     // okay.
                     Doc::join(
-                        idents.iter().map(|ident| self.span(*ident)),
+                        bindings.iter().map(|binding| self.binding(binding)),
                         ", ".into(),
                     )
                     " "
@@ -699,6 +831,9 @@ impl<'a> Formatter<'a> {
                 " -> "
                 self.type_(result)
             },
+            Type::Union { members, .. } => {
+                Doc::join(members.iter().map(|m| self.type_(m)), " | ".into())
+            }
         }
     }
 
@@ -723,3 +858,216 @@ impl<'a> Formatter<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::format_expr;
+    use crate::cst::Expr;
+    use crate::lexer::lex;
+    use crate::parser::parse;
+    use crate::pprint::Config;
+    use crate::source::DocId;
+
+    fn parse_str(input: &str) -> Option<Expr> {
+        let tokens = lex(DocId(0), input).ok()?;
+        let (_span, expr) = parse(DocId(0), input, &tokens).ok()?;
+        Some(expr)
+    }
+
+    /// Render the structure of the CST, with leaf spans replaced by their content.
+    ///
+    /// Spans that cover more than a single token, such as the span of a
+    /// function body, are expected to change when formatting, and their
+    /// structure is already captured by the nested nodes, so we elide them.
+    /// The formatter is allowed to add or remove trailing commas, and to
+    /// change the whitespace inside multi-line strings, so we ignore those.
+    /// It also normalizes number literals to lowercase.
+    fn shape(input: &str, expr: &Expr) -> String {
+        let debug = format!("{expr:?}")
+            .replace("trailing_comma: true", "")
+            .replace("trailing_comma: false", "");
+        let mut result = String::with_capacity(debug.len());
+        let mut remainder = &debug[..];
+        while let Some(i) = remainder.find("d0[") {
+            result.push_str(&remainder[..i]);
+            let span = &remainder[i + 3..];
+            let close = span.find(']').expect("Span debug format ends in ']'.");
+            let (start, end) = span[..close]
+                .split_once("..")
+                .expect("Span debug format contains '..'.");
+            let start: usize = start.parse().expect("Span start is a number.");
+            let end: usize = end.parse().expect("Span end is a number.");
+            let content = &input[start..end];
+            match lex(DocId(0), content) {
+                Ok(tokens) if tokens.len() > 1 => result.push('…'),
+                // A single token, or a fragment of a string literal that does
+                // not lex on its own.
+                _ => {
+                    // The formatter normalizes number literals to lowercase.
+                    let is_num = content.starts_with(|ch: char| ch.is_ascii_digit());
+                    result.push('«');
+                    for ch in content.chars().filter(|ch| !ch.is_whitespace()) {
+                        result.push(if is_num { ch.to_ascii_lowercase() } else { ch });
+                    }
+                    result.push('»');
+                }
+            }
+            remainder = &span[close + 1..];
+        }
+        result.push_str(remainder);
+        // The formatter escapes trailing whitespace in multi-line strings to
+        // make it visible. We ignore whitespace inside strings anyway.
+        result.replace("Escape(«\\u0020», Unicode4), ", "")
+    }
+
+    fn format_str(input: &str, width: u32) -> String {
+        let expr = parse_str(input).expect("Input should parse.");
+        let cfg = Config { width };
+        format_expr(input, &expr)
+            .println(&cfg)
+            .to_string_no_markup()
+    }
+
+    /// Assert that formatting the input does not change its meaning.
+    ///
+    /// Inputs that do not parse are skipped. Returns whether the input was checked.
+    fn check_round_trip(name: &str, input: &str) -> bool {
+        let expr = match parse_str(input) {
+            Some(expr) => expr,
+            None => return false,
+        };
+        // At a small width we force most collections to be tall, at a large
+        // width most of them can be wide, so we exercise both layouts.
+        for width in [1, 80, 500] {
+            let formatted = format_str(input, width);
+            let reparsed = parse_str(&formatted).unwrap_or_else(|| {
+                panic!("Formatted output of {name} at width {width} does not parse:\n{formatted}")
+            });
+            let before = shape(input, &expr);
+            let after = shape(&formatted, &reparsed);
+            // The shapes are long, so only report where they diverge.
+            let at = before
+                .chars()
+                .zip(after.chars())
+                .take_while(|(x, y)| x == y)
+                .count();
+            let context =
+                |s: &str| -> String { s.chars().skip(at.saturating_sub(60)).take(200).collect() };
+            assert!(
+                before == after,
+                "Formatting {name} at width {width} changed its structure.\n\
+                Before: ...{}\nAfter: ...{}\nFormatted:\n{formatted}",
+                context(&before),
+                context(&after),
+            );
+        }
+        true
+    }
+
+    #[test]
+    fn format_preserves_structure_of_golden_inputs() {
+        let golden = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("golden");
+        let mut n_checked = 0;
+        for dir in std::fs::read_dir(golden).unwrap() {
+            let dir = dir.unwrap().path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(&dir).unwrap() {
+                let path = file.unwrap().path();
+                if path.extension() != Some("test".as_ref()) {
+                    continue;
+                }
+                let contents = std::fs::read_to_string(&path).unwrap();
+                let input = match contents.split_once("\n# output:\n") {
+                    Some((input, _output)) => input,
+                    None => continue,
+                };
+                if check_round_trip(&path.display().to_string(), input) {
+                    n_checked += 1;
+                }
+            }
+        }
+        // Guard against the test passing vacuously when no files are found.
+        assert!(
+            n_checked > 100,
+            "Expected more inputs, checked {n_checked}."
+        );
+    }
+
+    #[test]
+    fn format_preserves_structure_of_comments_and_escapes() {
+        let inputs = [
+            "// Leading comment.\n{\n  // Before a field.\n  a = 1, // Trailing.\n\n\n  b = [\n    // Inside.\n  ],\n}\n",
+            "[[1, [2, [3, []]]], { x = { y = { z = {} } } }, {1, {2}}]",
+            r#"["tab\tquote\"backslash\\unicode\u{1f574}", f"hole {1 + 2} in \"{"nested"}\"", "dollar $"]"#,
+            "\"\"\"\n  multi\n  line \\\"\"\" with {braces}\n  \"\"\"",
+            "f\"\"\"\n  {[for x in [1, 2]: x * 2]}\n  \"\"\"",
+            "let f = (x, y) => x + y;\nassert f(1, 2) == 3, \"math works\";\n// The body.\nf(3, 4)",
+            "{ for k, v in { a = 1 }: if v > 0: k: v, }",
+            "let f = (\n  // Only a comment.\n) => g(\n  // Only a comment.\n);\n0",
+            "let x: Union[\n  // Only a comment.\n] = null;\nx",
+        ];
+        for (i, input) in inputs.iter().enumerate() {
+            assert!(
+                check_round_trip(&format!("input {i}"), input),
+                "Input {i} should parse."
+            );
+        }
+    }
+
+    #[test]
+    fn format_trailing_comma_only_when_tall() {
+        let cases = [
+            // Single-line collections have no trailing comma, also when the
+            // input is spread over multiple lines.
+            ("[1, 2]", 80, "[1, 2]\n"),
+            ("[\n  1, 2\n]", 80, "[1, 2]\n"),
+            ("{a = 1, b = 2}", 80, "{ a = 1, b = 2 }\n"),
+            ("f(1, 2)", 80, "f(1, 2)\n"),
+            // A trailing comma forces the collection to be tall.
+            ("[1, 2,]", 80, "[\n  1,\n  2,\n]\n"),
+            ("{a = 1, b = 2,}", 80, "{\n  a = 1,\n  b = 2,\n}\n"),
+            ("f(1, 2,)", 80, "f(\n  1,\n  2,\n)\n"),
+            // When a collection does not fit, it becomes tall and gains a
+            // trailing comma, then it stays tall at a larger width.
+            ("[100, 200, 300]", 10, "[\n  100,\n  200,\n  300,\n]\n"),
+            (
+                "[\n  100,\n  200,\n  300,\n]",
+                80,
+                "[\n  100,\n  200,\n  300,\n]\n",
+            ),
+        ];
+        for (input, width, expected) in cases {
+            let once = format_str(input, width);
+            assert_eq!(once, expected, "Unexpected output for {input:?}.");
+            let twice = format_str(&once, width);
+            assert_eq!(twice, once, "Second pass changed the output of {input:?}.");
+        }
+    }
+
+    #[test]
+    fn format_value_output_is_formatted_already() {
+        use crate::loader::Loader;
+        let inputs = [
+            "[1, 2]",
+            "{ a = [1, 2], b = { c = {1} } }",
+            "[for i in std.range(0, 30): { id = i, tags = {\"x\"} }]",
+        ];
+        for input in inputs {
+            for width in [20, 80] {
+                let mut loader = Loader::new();
+                let value = loader.evaluate_str("input", input).unwrap();
+                let cfg = Config { width };
+                let printed = crate::fmt_rcl::format_rcl(&value)
+                    .println(&cfg)
+                    .to_string_no_markup();
+                let formatted = format_str(&printed, width);
+                assert_eq!(
+                    formatted, printed,
+                    "Formatting changed the value {input:?}."
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints a list of records as CSV.
+
+use std::collections::BTreeMap;
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::fmt_rcl::format_rcl_short;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value as CSV.
+///
+/// The top-level value must be a list of dicts that all have the same keys.
+/// The first line is a header with the keys, followed by one line per record.
+/// The columns are ordered by key. Values must be strings, numbers, booleans,
+/// or null, which is output as an empty field. Fields are quoted as specified
+/// in [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180), but lines end in
+/// `\n` rather than `\r\n`. An empty list produces no output at all.
+pub fn format_csv(caller: Span, v: &Value) -> Result<Doc> {
+    let records = match v {
+        Value::List(xs) => xs,
+        _ => {
+            return caller
+                .error("To format as CSV, the top-level value must be a list.")
+                .err()
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut header: Option<&BTreeMap<Value, Value>> = None;
+
+    for (i, record) in records.iter().enumerate() {
+        let fields = match record {
+            Value::Dict(kv) => kv.as_ref(),
+            _ => {
+                return caller
+                    .error(concat! {
+                        "To format as CSV, every element must be a dict, but got: "
+                        format_rcl_short(record)
+                    })
+                    .with_path(vec![PathElement::Index(i)])
+                    .err()
+            }
+        };
+
+        match header {
+            None => {
+                lines.push(header_line(caller, i, fields)?);
+                header = Some(fields);
+            }
+            Some(first) => check_same_keys(caller, i, first, fields)?,
+        }
+
+        let mut line = Vec::with_capacity(fields.len() * 2);
+        for (j, (k, v)) in fields.iter().enumerate() {
+            if j > 0 {
+                line.push(",".into());
+            }
+            let field = match v {
+                Value::Null => continue,
+                Value::Bool(true) => "true".to_string(),
+                Value::Bool(false) => "false".to_string(),
+                Value::Int(i) => i.to_string(),
+                Value::String(s) => quote(s),
+                _not_scalar => {
+                    return caller
+                        .error(concat! {
+                            "To format as CSV, values must be strings, numbers, booleans, or null, but got: "
+                            format_rcl_short(v)
+                        })
+                        .with_path(vec![PathElement::Index(i), PathElement::Key(k.clone())])
+                        .err()
+                }
+            };
+            line.push(Doc::string_lines(field));
+        }
+        lines.push(Doc::Concat(line));
+        lines.push(Doc::HardBreak);
+    }
+
+    Ok(Doc::Concat(lines))
+}
+
+/// Format the header line from the keys of the first record.
+fn header_line<'a>(caller: Span, i: usize, fields: &BTreeMap<Value, Value>) -> Result<Doc<'a>> {
+    let mut line = Vec::with_capacity(fields.len() * 2 + 1);
+    for (j, k) in fields.keys().enumerate() {
+        if j > 0 {
+            line.push(",".into());
+        }
+        match k {
+            Value::String(k) => {
+                line.push(Doc::string_lines(quote(k)).with_markup(Markup::Field));
+            }
+            _ => {
+                return caller
+                    .error(concat! {
+                        "To format as CSV, keys must be strings, but got: "
+                        format_rcl_short(k)
+                    })
+                    .with_path(vec![PathElement::Index(i)])
+                    .err()
+            }
+        }
+    }
+    line.push(Doc::HardBreak);
+    Ok(Doc::Concat(line))
+}
+
+/// Report an error if a record does not have the same keys as the first one.
+fn check_same_keys(
+    caller: Span,
+    i: usize,
+    first: &BTreeMap<Value, Value>,
+    fields: &BTreeMap<Value, Value>,
+) -> Result<()> {
+    if let Some(k) = first.keys().find(|k| !fields.contains_key(k)) {
+        return caller
+            .error(concat! {
+                "To format as CSV, every record must have the same keys, "
+                "but this record is missing key " format_rcl_short(k) "."
+            })
+            .with_path(vec![PathElement::Index(i)])
+            .err();
+    }
+    if let Some(k) = fields.keys().find(|k| !first.contains_key(k)) {
+        return caller
+            .error(concat! {
+                "To format as CSV, every record must have the same keys, "
+                "but this record has key " format_rcl_short(k) ", "
+                "which the first record does not have."
+            })
+            .with_path(vec![PathElement::Index(i)])
+            .err();
+    }
+    Ok(())
+}
+
+/// Quote a field if it contains a separator, quote, or line break.
+fn quote(s: &str) -> String {
+    if !s.contains([',', '"', '\n', '\r']) {
+        return s.to_string();
+    }
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => result.push_str("\"\""),
+            _ => result.push(ch),
+        }
+    }
+    result.push('"');
+    result
+}
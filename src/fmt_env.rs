@@ -0,0 +1,102 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints a dict as `KEY=value` lines for shells and dotenv files.
+
+use crate::error::{Error, IntoError, PathElement, Result};
+use crate::fmt_rcl::format_rcl_short;
+use crate::markup::Markup;
+use crate::pprint::{concat, Doc};
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value in env format.
+///
+/// The top-level value must be a dict with string keys that are valid shell
+/// variable names. Values must be strings, numbers, or booleans. Every
+/// key-value pair is output on its own line, with the value quoted if that is
+/// needed for a POSIX shell to read it back verbatim.
+pub fn format_env(caller: Span, v: &Value) -> Result<Doc> {
+    let kv = match v {
+        Value::Dict(kv) => kv,
+        _ => {
+            return caller
+                .error("To format as env, the top-level value must be a dict.")
+                .err()
+        }
+    };
+
+    let mut lines = Vec::with_capacity(kv.len() * 4);
+    for (k, v) in kv.iter() {
+        let key = match k {
+            Value::String(k) if is_variable_name(k) => k,
+            _ => {
+                return caller
+                    .error(concat! {
+                        "To format as env, keys must be valid variable names, but got: "
+                        format_rcl_short(k)
+                    })
+                    .with_path(vec![PathElement::Key(k.clone())])
+                    .err();
+            }
+        };
+        let value: Doc = match v {
+            Value::String(s) => Doc::string_lines(quote(s)),
+            Value::Int(i) => i.to_string().into(),
+            Value::Bool(true) => "true".into(),
+            Value::Bool(false) => "false".into(),
+            _not_scalar => return error_not_scalar(caller, k, v).err(),
+        };
+        lines.push(Doc::from(key.as_ref()).with_markup(Markup::Field));
+        lines.push("=".into());
+        lines.push(value);
+        lines.push(Doc::HardBreak);
+    }
+
+    Ok(Doc::Concat(lines))
+}
+
+fn error_not_scalar(caller: Span, k: &Value, v: &Value) -> Error {
+    caller
+        .error(concat! {
+            "To format as env, values must be strings, numbers, or booleans, but got: "
+            format_rcl_short(v)
+        })
+        .with_path(vec![PathElement::Key(k.clone())])
+}
+
+/// Return whether the string can be used as a shell variable name.
+fn is_variable_name(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    match bytes.next() {
+        Some(b) if b.is_ascii_alphabetic() || b == b'_' => {}
+        _ => return false,
+    }
+    bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Quote a string so a POSIX shell reads it back verbatim.
+///
+/// Strings that consist only of characters without special meaning are output
+/// as-is. Other strings are wrapped in single quotes, inside of which nothing is
+/// special except the single quote itself, which we write as `'\''`.
+fn quote(s: &str) -> String {
+    let is_safe = |b: u8| b.is_ascii_alphanumeric() || b"_-.,:/@%+=".contains(&b);
+    if !s.is_empty() && s.bytes().all(is_safe) {
+        return s.to_string();
+    }
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('\'');
+    for ch in s.chars() {
+        match ch {
+            '\'' => result.push_str("'\\''"),
+            _ => result.push(ch),
+        }
+    }
+    result.push('\'');
+    result
+}
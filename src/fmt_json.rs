@@ -7,9 +7,11 @@
 
 //! Formatter that prints values as json.
 
+use std::rc::Rc;
+
 use crate::error::{IntoError, PathElement, Result};
-use crate::markup::Markup;
-use crate::pprint::{concat, group, indent, Doc};
+use crate::markup::{Markup, MarkupString};
+use crate::pprint::{concat, group, indent, Config, Doc};
 use crate::runtime::Value;
 use crate::source::Span;
 use crate::string::escape_json;
@@ -30,6 +32,9 @@ pub struct Formatter {
 
     /// Where we currently are in the value to be formatted.
     pub path: Vec<PathElement>,
+
+    /// Whether to format everything on a single line without whitespace.
+    pub compact: bool,
 }
 
 impl Formatter {
@@ -37,6 +42,16 @@ impl Formatter {
         Formatter {
             caller,
             path: Vec::new(),
+            compact: false,
+        }
+    }
+
+    /// Create a formatter that formats values on a single line.
+    pub fn new_compact(caller: Span) -> Formatter {
+        Formatter {
+            caller,
+            path: Vec::new(),
+            compact: true,
         }
     }
 
@@ -62,20 +77,15 @@ impl Formatter {
         for (i, v) in vs.enumerate() {
             if !elements.is_empty() {
                 elements.push(",".into());
-                elements.push(Doc::Sep);
+                if !self.compact {
+                    elements.push(Doc::Sep);
+                }
             }
             self.path.push(PathElement::Index(i));
             elements.push(self.value(v)?);
             self.path.pop().expect("Push and pop are balanced.");
         }
-        let result = group! {
-            "["
-            Doc::SoftBreak
-            indent! { Doc::Concat(elements) }
-            Doc::SoftBreak
-            "]"
-        };
-        Ok(result)
+        Ok(self.collection("[", elements, "]"))
     }
 
     fn dict<'a>(&mut self, vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Result<Doc<'a>> {
@@ -83,7 +93,9 @@ impl Formatter {
         for (k, v) in vs {
             if !elements.is_empty() {
                 elements.push(",".into());
-                elements.push(Doc::Sep);
+                if !self.compact {
+                    elements.push(Doc::Sep);
+                }
             }
             self.path.push(PathElement::Key(k.clone()));
             match k {
@@ -92,18 +104,25 @@ impl Formatter {
                 }
                 _ => return self.error("To export as json, keys must be strings."),
             };
-            elements.push(": ".into());
+            elements.push(if self.compact { ":" } else { ": " }.into());
             elements.push(self.value(v)?);
             self.path.pop().expect("Push and pop are balanced.");
         }
-        let result = group! {
-            "{"
+        Ok(self.collection("{", elements, "}"))
+    }
+
+    /// Wrap the elements of a list or dict in delimiters.
+    fn collection<'a>(&self, open: &'a str, elements: Vec<Doc<'a>>, close: &'a str) -> Doc<'a> {
+        if self.compact {
+            return concat! { open Doc::Concat(elements) close };
+        }
+        group! {
+            open
             Doc::SoftBreak
             indent! { Doc::Concat(elements) }
             Doc::SoftBreak
-            "}"
-        };
-        Ok(result)
+            close
+        }
     }
 
     pub fn value<'a>(&mut self, v: &'a Value) -> Result<Doc<'a>> {
@@ -123,3 +142,210 @@ impl Formatter {
         Ok(result)
     }
 }
+
+/// Formats a list as json one element at a time, to stream it to the output.
+///
+/// The output is the same as formatting the full list with [`format_json`].
+/// While the list still fits on a single line, we hold on to the elements,
+/// because we don't yet know whether to print the list wide or tall. Once it no
+/// longer fits, we print every element as soon as it arrives.
+pub struct ListWriter<'c> {
+    config: &'c Config,
+
+    /// The elements that we hold on to while the list still fits on a line.
+    buffer: Vec<Value>,
+
+    /// Once the list no longer fits on a line, the number of elements printed.
+    n_printed: Option<usize>,
+}
+
+impl<'c> ListWriter<'c> {
+    pub fn new(config: &'c Config) -> ListWriter<'c> {
+        ListWriter {
+            config,
+            buffer: Vec::new(),
+            n_printed: None,
+        }
+    }
+
+    /// Add the next element, and pass any output that is ready to `out`.
+    pub fn push(
+        &mut self,
+        caller: Span,
+        v: Value,
+        out: &mut dyn FnMut(MarkupString),
+    ) -> Result<()> {
+        if let Some(i) = self.n_printed {
+            self.print_element(caller, i, &v, out)?;
+            self.n_printed = Some(i + 1);
+            return Ok(());
+        }
+
+        self.buffer.push(v);
+        let list = Value::List(Rc::new(self.buffer.clone()));
+        let doc = format_json(caller, &list)?;
+        let fits = !doc.print(self.config).to_string_no_markup().contains('\n');
+        if !fits {
+            let buffer = std::mem::take(&mut self.buffer);
+            for (i, elem) in buffer.iter().enumerate() {
+                self.print_element(caller, i, elem, out)?;
+            }
+            self.n_printed = Some(buffer.len());
+        }
+        Ok(())
+    }
+
+    /// Whether part of the list has been printed, with the last line unfinished.
+    pub fn is_printing(&self) -> bool {
+        self.n_printed.is_some()
+    }
+
+    /// Pass the remaining output, that closes the list, to `out`.
+    ///
+    /// The output ends in a newline if `trailing_newline` is true.
+    pub fn finish(
+        self,
+        caller: Span,
+        trailing_newline: bool,
+        out: &mut dyn FnMut(MarkupString),
+    ) -> Result<()> {
+        match self.n_printed {
+            None => {
+                let list = Value::List(Rc::new(self.buffer));
+                let doc = format_json(caller, &list)?;
+                let mut result = doc.println(self.config);
+                if !trailing_newline {
+                    result.trim_newline_end();
+                }
+                out(result);
+            }
+            Some(_) => {
+                let mut tail = MarkupString::new();
+                tail.push("\n", Markup::None);
+                tail.push("]", Markup::None);
+                if trailing_newline {
+                    tail.push("\n", Markup::None);
+                }
+                out(tail);
+            }
+        }
+        Ok(())
+    }
+
+    /// Print one element of a list that is formatted tall.
+    fn print_element(
+        &self,
+        caller: Span,
+        i: usize,
+        v: &Value,
+        out: &mut dyn FnMut(MarkupString),
+    ) -> Result<()> {
+        let mut formatter = Formatter::new(caller);
+        formatter.path.push(PathElement::Index(i));
+        let elem = formatter.value(v)?;
+        let doc = concat! {
+            if i == 0 { "[" } else { "," }
+            indent! { Doc::HardBreak elem }
+        };
+        out(doc.print(self.config));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{format_json, ListWriter};
+    use crate::pprint::Config;
+    use crate::runtime::Value;
+    use crate::source::{DocId, Span};
+
+    fn element(i: usize) -> Value {
+        match i % 3 {
+            0 => Value::Int(i as i64),
+            1 => Value::String(format!("element {i}").into()),
+            _ => {
+                let mut fields = std::collections::BTreeMap::new();
+                fields.insert(Value::String("id".into()), Value::Int(i as i64));
+                fields.insert(Value::String("odd".into()), Value::Bool(i % 2 == 1));
+                Value::Dict(Rc::new(fields))
+            }
+        }
+    }
+
+    #[test]
+    fn list_writer_matches_format_json() {
+        let caller = Span::new(DocId(0), 0, 0);
+        for n in [0, 1, 2, 3, 7, 100, 5_000] {
+            for width in [1, 20, 80] {
+                let config = Config { width };
+                let elements: Vec<Value> = (0..n).map(element).collect();
+
+                let mut streamed = String::new();
+                let mut writer = ListWriter::new(&config);
+                for v in elements.iter() {
+                    writer
+                        .push(caller, v.clone(), &mut |s| {
+                            s.write_string_no_markup(&mut streamed)
+                        })
+                        .unwrap();
+                }
+                writer
+                    .finish(caller, true, &mut |s| {
+                        s.write_string_no_markup(&mut streamed)
+                    })
+                    .unwrap();
+
+                let list = Value::List(Rc::new(elements));
+                let materialized = format_json(caller, &list)
+                    .unwrap()
+                    .println(&config)
+                    .to_string_no_markup();
+
+                assert_eq!(streamed, materialized, "Mismatch for n={n} width={width}.");
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_newline_controls_final_byte() {
+        let caller = Span::new(DocId(0), 0, 0);
+        let config = Config { width: 20 };
+        for n in [2, 20] {
+            let elements: Vec<Value> = (0..n).map(element).collect();
+            for trailing_newline in [true, false] {
+                let mut streamed = String::new();
+                let mut writer = ListWriter::new(&config);
+                for v in elements.iter() {
+                    writer
+                        .push(caller, v.clone(), &mut |s| {
+                            s.write_string_no_markup(&mut streamed)
+                        })
+                        .unwrap();
+                }
+                writer
+                    .finish(caller, trailing_newline, &mut |s| {
+                        s.write_string_no_markup(&mut streamed)
+                    })
+                    .unwrap();
+
+                let list = Value::List(Rc::new(elements.clone()));
+                let doc = format_json(caller, &list).unwrap();
+                let mut printed = doc.println(&config);
+                if !trailing_newline {
+                    printed.trim_newline_end();
+                }
+                let printed = printed.to_string_no_markup();
+
+                for out in [&streamed, &printed] {
+                    assert_eq!(
+                        out.as_bytes().last(),
+                        Some(&if trailing_newline { b'\n' } else { b']' })
+                    );
+                }
+                assert_eq!(streamed, printed, "Mismatch for n={n}.");
+            }
+        }
+    }
+}
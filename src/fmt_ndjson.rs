@@ -0,0 +1,41 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Formatter that prints list elements as compact json, one per line.
+
+use crate::error::{IntoError, PathElement, Result};
+use crate::fmt_json::Formatter;
+use crate::pprint::Doc;
+use crate::runtime::Value;
+use crate::source::Span;
+
+/// Render a value in newline-delimited json format.
+///
+/// Every element of the top-level list is output as json on a single line.
+/// An empty list produces no output at all.
+pub fn format_ndjson(caller: Span, v: &Value) -> Result<Doc> {
+    let elements = match v {
+        Value::List(xs) => xs,
+        _ => {
+            return caller
+                .error("To format as ndjson, the top-level value must be a list.")
+                .err()
+        }
+    };
+
+    let mut formatter = Formatter::new_compact(caller);
+    let mut parts = Vec::with_capacity(elements.len() * 2);
+
+    for (i, element) in elements.iter().enumerate() {
+        formatter.path.push(PathElement::Index(i));
+        parts.push(formatter.value(element)?);
+        formatter.path.pop();
+        parts.push(Doc::HardBreak);
+    }
+
+    Ok(Doc::Concat(parts))
+}
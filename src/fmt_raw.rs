@@ -15,6 +15,11 @@ use crate::source::Span;
 
 /// Render a value in raw format.
 ///
+/// A string is output verbatim, without quotes or escapes. A list or set of
+/// strings is output with every string on its own line. Other values are an
+/// error. The output always ends in a newline, but when a string already ends
+/// in a newline, we do not add a second one.
+///
 /// It is a bit wasteful to go the `Doc` route, we could print directly to
 /// stdout, but taking the same approach as the other formatters makes error
 /// reporting easier, and output handling more uniform.
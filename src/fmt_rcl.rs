@@ -14,27 +14,112 @@ use crate::pprint::{concat, group, indent, Doc};
 use crate::runtime::Value;
 use crate::string::{escape_json, is_identifier};
 
+/// The base to print integers in.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum IntBase {
+    /// Print integers with a `0b` prefix.
+    Binary,
+    #[default]
+    Decimal,
+    /// Print integers with a `0x` prefix.
+    Hexadecimal,
+}
+
+/// Limits on how much of a value to print, and how to print integers.
+#[derive(Copy, Clone)]
+struct Limit {
+    /// The maximum number of elements to print per collection.
+    elements: usize,
+    /// The maximum number of characters to print per string.
+    chars: usize,
+    /// The base to print integers in.
+    int_base: IntBase,
+}
+
+impl Limit {
+    const NONE: Limit = Limit {
+        elements: usize::MAX,
+        chars: usize::MAX,
+        int_base: IntBase::Decimal,
+    };
+
+    const SHORT: Limit = Limit {
+        elements: 5,
+        chars: 40,
+        int_base: IntBase::Decimal,
+    };
+}
+
 /// Render a value as RCL.
 pub fn format_rcl(v: &Value) -> Doc {
-    value(v)
+    value(v, Limit::NONE)
+}
+
+/// Render a value as RCL, with integers in the given base.
+///
+/// The output is a valid RCL expression that evaluates to the same value, only
+/// the integer literals are written differently.
+pub fn format_rcl_with_int_base(v: &Value, int_base: IntBase) -> Doc {
+    let limit = Limit {
+        int_base,
+        ..Limit::NONE
+    };
+    value(v, limit)
+}
+
+/// Format an integer literal in the given base.
+fn int(i: i64, base: IntBase) -> String {
+    let sign = if i < 0 { "-" } else { "" };
+    // Take the absolute value as unsigned, so this works for `i64::MIN` too.
+    let n = i.unsigned_abs();
+    match base {
+        IntBase::Binary => format!("{sign}0b{n:b}"),
+        IntBase::Decimal => i.to_string(),
+        IntBase::Hexadecimal => format!("{sign}0x{n:x}"),
+    }
+}
+
+/// Render a value as RCL, but abbreviate long collections and strings.
+///
+/// This is intended for showing values in error messages. The output is not
+/// necessarily a valid RCL expression: elided parts are replaced with `…`.
+pub fn format_rcl_short(v: &Value) -> Doc<'static> {
+    value(v, Limit::SHORT).into_owned()
 }
 
 /// Format a string.
-fn string<'a>(s: &str) -> Doc<'a> {
+fn string<'a>(s: &str, limit: Limit) -> Doc<'a> {
     // TODO: Check if the string is multiline, and possibly format using a """-string.
     let mut into = String::with_capacity(s.len());
-    escape_json(s, &mut into);
+    match s.char_indices().nth(limit.chars) {
+        Some((i, _)) => {
+            escape_json(&s[..i], &mut into);
+            into.push('…');
+        }
+        None => escape_json(s, &mut into),
+    }
     concat! { "\"" into "\"" }
 }
 
-fn list<'a>(open: &'a str, close: &'a str, vs: impl Iterator<Item = &'a Value>) -> Doc<'a> {
+fn list<'a>(
+    open: &'a str,
+    close: &'a str,
+    vs: impl ExactSizeIterator<Item = &'a Value>,
+    limit: Limit,
+) -> Doc<'a> {
     let mut elements = Vec::new();
-    for v in vs {
+    let n = vs.len();
+    for v in vs.take(limit.elements) {
         if !elements.is_empty() {
             elements.push(",".into());
             elements.push(Doc::Sep);
         }
-        elements.push(value(v));
+        elements.push(value(v, limit));
+    }
+    if n > limit.elements {
+        elements.push(",".into());
+        elements.push(Doc::Sep);
+        elements.push("…".into());
     }
 
     if elements.is_empty() {
@@ -54,10 +139,11 @@ fn list<'a>(open: &'a str, close: &'a str, vs: impl Iterator<Item = &'a Value>)
     }
 }
 
-pub fn dict<'a>(vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Doc<'a> {
+fn dict<'a>(vs: impl ExactSizeIterator<Item = (&'a Value, &'a Value)>, limit: Limit) -> Doc<'a> {
     let mut elements = Vec::new();
+    let n = vs.len();
 
-    for (k, v) in vs {
+    for (k, v) in vs.take(limit.elements) {
         if !elements.is_empty() {
             elements.push(",".into());
         }
@@ -69,15 +155,20 @@ pub fn dict<'a>(vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Doc<'a> {
                 elements.push(" = ".into());
             }
             Value::String(k_str) => {
-                elements.push(string(k_str).with_markup(Markup::Field));
+                elements.push(string(k_str, limit).with_markup(Markup::Field));
                 elements.push(": ".into());
             }
             _not_string => {
-                elements.push(value(k));
+                elements.push(value(k, limit));
                 elements.push(": ".into());
             }
         };
-        elements.push(value(v));
+        elements.push(value(v, limit));
+    }
+    if n > limit.elements {
+        elements.push(",".into());
+        elements.push(Doc::Sep);
+        elements.push("…".into());
     }
 
     if elements.is_empty() {
@@ -98,19 +189,23 @@ pub fn dict<'a>(vs: impl Iterator<Item = (&'a Value, &'a Value)>) -> Doc<'a> {
     }
 }
 
-fn value(v: &Value) -> Doc {
+fn value(v: &Value, limit: Limit) -> Doc {
     match v {
         Value::Null => Doc::from("null").with_markup(Markup::Keyword),
         Value::Bool(true) => Doc::from("true").with_markup(Markup::Keyword),
         Value::Bool(false) => Doc::from("false").with_markup(Markup::Keyword),
-        Value::Int(i) => Doc::from(i.to_string()).with_markup(Markup::Number),
-        Value::String(s) => string(s).with_markup(Markup::String),
-        Value::List(vs) => list("[", "]", vs.iter()),
+        Value::Int(i) => Doc::from(int(*i, limit.int_base)).with_markup(Markup::Number),
+        Value::String(s) => string(s, limit).with_markup(Markup::String),
+        Value::List(vs) => list("[", "]", vs.iter(), limit),
         // TODO: An empty set should print as {}, that would be a non-idempotency,
         // because {} is the empty dict. We could add a function `std.empty_set`,
         // and format it as that?
-        Value::Set(vs) => list("{", "}", vs.iter()),
-        Value::Dict(vs) => dict(vs.iter()),
+        // Sets print their elements in sorted order, which is the iteration
+        // order of the `BTreeSet`, not the order of the source, so equal sets
+        // always print the same. Elements of different types are ordered by
+        // type first, see the `Ord` instance of `Value`.
+        Value::Set(vs) => list("{", "}", vs.iter(), limit),
+        Value::Dict(vs) => dict(vs.iter(), limit),
 
         Value::BuiltinFunction(b) => {
             let name = b
@@ -0,0 +1,224 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Matching file paths against glob patterns, for `std.glob`.
+//!
+//! Patterns consist of `/`-separated components. In a component, `*` matches
+//! any sequence of characters, and a component `**` matches zero or more
+//! directories. Wildcards do not match names that start with a dot, unless the
+//! pattern component starts with a dot as well. A `**` does not descend into
+//! symlinks to directories, because a link to a parent directory would make
+//! the walk infinite.
+
+use std::io;
+use std::path::Path;
+
+/// Return whether a single path component matches a pattern component.
+pub fn matches_component(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let mut parts = pattern.split('*');
+    // There is always at least one part, possibly empty.
+    let first = parts.next().unwrap_or("");
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+
+    // Without a `*`, the name must match the pattern exactly.
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        None => return rest.is_empty(),
+    };
+
+    // Match the middle parts greedily from the left, the last part must be a
+    // suffix of what remains.
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Return the files under `base` that match the pattern.
+///
+/// The results are paths relative to `base`, with `/` as separator, sorted
+/// and without duplicates. Directories that do not exist have no matches.
+pub fn find_matches(base: &Path, pattern: &str) -> io::Result<Vec<String>> {
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let mut result = Vec::new();
+    walk(base, "", &components, &mut result)?;
+    result.sort();
+    result.dedup();
+    Ok(result)
+}
+
+/// An entry in a directory listing.
+#[derive(Eq, Ord, PartialEq, PartialOrd)]
+struct Entry {
+    name: String,
+    /// Whether the entry is a directory, or a symlink to one.
+    is_dir: bool,
+    is_symlink: bool,
+}
+
+/// List a directory, sorted by name, or nothing if it does not exist.
+fn read_dir_sorted(dir: &Path) -> io::Result<Vec<Entry>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut result = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        // Follow symlinks, so a link to a directory is walked as a directory.
+        // The caller is responsible for enforcing the sandbox policy.
+        result.push(Entry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            is_dir: entry.path().is_dir(),
+            is_symlink: entry.file_type()?.is_symlink(),
+        });
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn walk(dir: &Path, prefix: &str, components: &[&str], out: &mut Vec<String>) -> io::Result<()> {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    let join = |name: &str| format!("{prefix}{name}");
+
+    match *component {
+        "." => walk(dir, prefix, rest, out),
+        ".." => walk(&dir.join(".."), &join("../"), rest, out),
+        "**" => {
+            // Zero directories, then one or more directories.
+            walk(dir, prefix, rest, out)?;
+            for Entry {
+                name,
+                is_dir,
+                is_symlink,
+            } in read_dir_sorted(dir)?
+            {
+                if name.starts_with('.') {
+                    continue;
+                }
+                if is_dir && is_symlink {
+                    // Without following the link, a link to a directory is
+                    // neither a file to match nor a directory to walk.
+                    continue;
+                }
+                if is_dir {
+                    walk(
+                        &dir.join(&name),
+                        &join(&format!("{name}/")),
+                        components,
+                        out,
+                    )?;
+                } else if rest.is_empty() {
+                    out.push(join(&name));
+                }
+            }
+            Ok(())
+        }
+        pattern => {
+            for Entry { name, is_dir, .. } in read_dir_sorted(dir)? {
+                if !matches_component(pattern, &name) {
+                    continue;
+                }
+                match (rest.is_empty(), is_dir) {
+                    (true, false) => out.push(join(&name)),
+                    (true, true) => continue,
+                    (false, true) => {
+                        walk(&dir.join(&name), &join(&format!("{name}/")), rest, out)?;
+                    }
+                    (false, false) => continue,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_matches, matches_component};
+
+    #[test]
+    fn matches_component_handles_wildcards() {
+        assert!(matches_component("a.rcl", "a.rcl"));
+        assert!(!matches_component("a.rcl", "b.rcl"));
+        assert!(matches_component("*.rcl", "a.rcl"));
+        assert!(!matches_component("*.rcl", ".rcl"));
+        assert!(matches_component("*", "anything"));
+        assert!(matches_component("a*b*c", "abc"));
+        assert!(matches_component("a*b*c", "a-b-b-c"));
+        assert!(!matches_component("a*b*c", "a-c"));
+        assert!(!matches_component("*.json", "a.json.bak"));
+        assert!(!matches_component("*", ".hidden"));
+        assert!(matches_component(".*", ".hidden"));
+    }
+
+    #[test]
+    fn find_matches_is_sorted_and_recursive() {
+        let dir = std::env::temp_dir().join(format!("rcl-test-glob-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("b/c")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        for name in [
+            "z.json",
+            "a.json",
+            "a.rcl",
+            "b/x.json",
+            "b/c/y.json",
+            ".git/config.json",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(find_matches(&dir, "*.json").unwrap(), ["a.json", "z.json"]);
+        assert_eq!(
+            find_matches(&dir, "**/*.json").unwrap(),
+            ["a.json", "b/c/y.json", "b/x.json", "z.json"]
+        );
+        assert_eq!(
+            find_matches(&dir, "b/**").unwrap(),
+            ["b/c/y.json", "b/x.json"]
+        );
+        assert_eq!(find_matches(&dir, "*/x.json").unwrap(), ["b/x.json"]);
+        assert!(find_matches(&dir, "missing/*").unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_matches_does_not_follow_symlinked_directories_under_globstar() {
+        let dir = std::env::temp_dir().join(format!("rcl-test-glob-link-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::write(dir.join("a/x.rcl"), "").unwrap();
+        // A link back to the parent would make the walk infinite.
+        std::os::unix::fs::symlink("..", dir.join("a/up")).unwrap();
+
+        assert_eq!(find_matches(&dir, "**/*.rcl").unwrap(), ["a/x.rcl"]);
+        // An explicit component still follows the link.
+        assert_eq!(
+            find_matches(&dir, "a/up/a/*.rcl").unwrap(),
+            ["a/up/a/x.rcl"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
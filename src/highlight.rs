@@ -18,19 +18,25 @@ const BUILTINS: &[&str] = &[
     "ends_with",
     "enumerate",
     "except",
+    "fetch",
     "filter",
     "flat_map",
     "fold",
     "get",
+    "get_or_else",
     "group_by",
+    "has",
+    "index_of",
     "join",
     "key_by",
     "keys",
     "len",
     "map",
+    "map_values",
     "parse_int",
     "remove_prefix",
     "remove_suffix",
+    "repeat",
     "replace",
     "reverse",
     "split",
@@ -40,12 +46,15 @@ const BUILTINS: &[&str] = &[
     "sum",
     "to_lowercase",
     "to_uppercase",
+    "unique",
     "values",
+    "zip",
 ];
 
 fn get_markup(token: &Token) -> Markup {
     match token {
         Token::LineComment => Markup::Comment,
+        Token::BlockComment => Markup::Comment,
         Token::NumBinary | Token::NumHexadecimal | Token::NumDecimal => Markup::Number,
         Token::QuoteOpen(..) | Token::QuoteClose | Token::StringInner => Markup::String,
         Token::HoleOpen | Token::HoleClose | Token::Escape(..) => Markup::Escape,
@@ -60,11 +69,13 @@ fn get_markup(token: &Token) -> Markup {
         | Token::KwImport
         | Token::KwIn
         | Token::KwLet
+        | Token::KwMatch
         | Token::KwNot
         | Token::KwNull
         | Token::KwOr
         | Token::KwTrace
-        | Token::KwTrue => Markup::Keyword,
+        | Token::KwTrue
+        | Token::KwXor => Markup::Keyword,
 
         _ => Markup::None,
     }
@@ -88,5 +99,126 @@ pub fn highlight<'a>(tokens: &[Lexeme], input: &'a str) -> MarkupString<'a> {
         end = span.end();
     }
 
+    // Also include the whitespace after the last token, such as the final newline.
+    if end < input.len() {
+        out.push(&input[end..], Markup::None);
+    }
+
     out
 }
+
+/// Line numbers to put in the gutter of highlighted output.
+///
+/// The numbers are right-aligned, and the gutter is as wide as the largest
+/// line number. We render all of them into a single string up front, so the
+/// numbered output can borrow its fragments from it.
+pub struct Gutter {
+    text: String,
+    entry_len: usize,
+}
+
+impl Gutter {
+    /// Build the gutter for a document with the given number of lines.
+    pub fn new(n_lines: usize) -> Gutter {
+        let width = n_lines.max(1).to_string().len();
+        let mut text = String::new();
+        for i in 1..=n_lines {
+            text.push_str(&format!("{i:>width$} │ "));
+        }
+        Gutter {
+            text,
+            // The separator is two spaces and a three-byte box drawing character.
+            entry_len: width + 5,
+        }
+    }
+
+    /// Build the gutter for the given input document.
+    pub fn for_input(input: &str) -> Gutter {
+        Gutter::new(input.lines().count())
+    }
+
+    fn entry(&self, line: usize) -> &str {
+        &self.text[line * self.entry_len..(line + 1) * self.entry_len]
+    }
+}
+
+/// Prefix every line of highlighted output with its line number.
+///
+/// Tokens that span multiple lines, such as multi-line strings, are split at
+/// the line breaks, so every physical line gets its own number.
+pub fn number_lines<'a>(highlighted: MarkupString<'a>, gutter: &'a Gutter) -> MarkupString<'a> {
+    let mut out = MarkupString::new();
+    let mut line = 0;
+    let mut at_line_start = true;
+
+    for (fragment, markup) in highlighted.fragments {
+        for piece in fragment.split_inclusive('\n') {
+            if at_line_start {
+                out.push(gutter.entry(line), Markup::LineNumber);
+                line += 1;
+            }
+            out.push(piece, markup);
+            at_line_start = piece.ends_with('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{highlight, number_lines, Gutter};
+    use crate::lexer::lex;
+    use crate::source::DocId;
+
+    #[test]
+    fn number_lines_aligns_gutter() {
+        let input = concat!(
+            "// A snippet with more than nine lines.\n",
+            "let xs = [\n",
+            "  1,\n",
+            "  2,\n",
+            "];\n",
+            "let s = \"\"\"\n",
+            "  A string that\n",
+            "  spans lines.\n",
+            "  \"\"\";\n",
+            "{\n",
+            "  xs = xs,\n",
+            "}\n",
+        );
+        let tokens = lex(DocId(0), input).unwrap();
+        let gutter = Gutter::for_input(input);
+        let result = number_lines(highlight(&tokens, input), &gutter);
+        assert_eq!(
+            result.to_string_no_markup(),
+            concat!(
+                " 1 │ // A snippet with more than nine lines.\n",
+                " 2 │ let xs = [\n",
+                " 3 │   1,\n",
+                " 4 │   2,\n",
+                " 5 │ ];\n",
+                " 6 │ let s = \"\"\"\n",
+                " 7 │   A string that\n",
+                " 8 │   spans lines.\n",
+                " 9 │   \"\"\";\n",
+                "10 │ {\n",
+                "11 │   xs = xs,\n",
+                "12 │ }\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn number_lines_handles_short_inputs() {
+        let number = |input: &str| {
+            let tokens = lex(DocId(0), input).unwrap();
+            let gutter = Gutter::for_input(input);
+            number_lines(highlight(&tokens, input), &gutter).to_string_no_markup()
+        };
+        assert_eq!(number(""), "");
+        assert_eq!(number("1"), "1 │ 1");
+        assert_eq!(number("1\n"), "1 │ 1\n");
+        assert_eq!(number("\n\n1\n"), "1 │ \n2 │ \n3 │ 1\n");
+    }
+}
@@ -60,11 +60,16 @@ pub enum Token {
     /// Excludes the newline itself.
     Shebang,
 
-    /// A comment that starts with `//` and runs until the end of the line.
+    /// A comment that starts with `//` or `#` and runs until the end of the line.
     ///
     /// Excludes the newline itself.
     LineComment,
 
+    /// A comment that starts with `/*` and runs until the matching `*/`.
+    ///
+    /// Block comments nest, and they may span multiple lines.
+    BlockComment,
+
     /// A sequence of ascii alphanumeric or _, not starting with a digit.
     Ident,
 
@@ -122,6 +127,9 @@ pub enum Token {
     /// `let`
     KwLet,
 
+    /// `match`
+    KwMatch,
+
     /// `not`
     KwNot,
 
@@ -137,6 +145,9 @@ pub enum Token {
     /// `true`
     KwTrue,
 
+    /// `xor`
+    KwXor,
+
     /// `(`
     LParen,
 
@@ -176,6 +187,21 @@ pub enum Token {
     /// `->`
     ThinArrow,
 
+    /// `..`
+    DotDot,
+
+    /// `..=`
+    DotDotEq,
+
+    /// `**`
+    Star2,
+
+    /// `<<`
+    Lt2,
+
+    /// `>>`
+    Gt2,
+
     /// `=>`
     FatArrow,
 
@@ -212,6 +238,12 @@ pub enum Token {
     /// `|`
     Pipe,
 
+    /// `&`
+    Amp,
+
+    /// `^`
+    Caret,
+
     /// End of file.
     ///
     /// This token is not returned by the lexer, but it's used internally in the
@@ -242,6 +274,21 @@ pub type Lexeme = (Token, Span);
 /// Lex an input document into tokens.
 pub fn lex(doc: DocId, input: &str) -> Result<Vec<Lexeme>> {
     let mut tokens = Vec::new();
+    lex_into(doc, input, &mut tokens)?;
+    Ok(tokens)
+}
+
+/// Lex an input document, and also return the tokens before an error, if any.
+///
+/// This is useful for syntax highlighting, where we want to highlight as much
+/// of a document as we can, even if it contains an error further down.
+pub fn lex_partial(doc: DocId, input: &str) -> (Vec<Lexeme>, Result<()>) {
+    let mut tokens = Vec::new();
+    let result = lex_into(doc, input, &mut tokens);
+    (tokens, result)
+}
+
+fn lex_into(doc: DocId, input: &str, tokens: &mut Vec<Lexeme>) -> Result<()> {
     let mut lexer = Lexer::new(doc, input);
     while lexer.start < lexer.input.len() {
         match lexer.next()? {
@@ -266,8 +313,7 @@ pub fn lex(doc: DocId, input: &str) -> Result<Vec<Lexeme>> {
             }
         };
     }
-    lexer.report_unclosed_delimiters()?;
-    Ok(tokens)
+    lexer.report_unclosed_delimiters()
 }
 
 /// Return whether a given string is a keyword.
@@ -398,10 +444,17 @@ impl<'a> Lexer<'a> {
             return Ok(self.lex_in_shebang());
         }
 
-        if input.starts_with(b"//") {
+        // We accept `#` as well as `//` for line comments, because `#` is so
+        // common in other configuration formats, and RCL does not use `#` for
+        // anything else. Note that `#!` is a shebang, which we lex above.
+        if input.starts_with(b"//") || input[0] == b'#' {
             return Ok(self.lex_in_line_comment());
         }
 
+        if input.starts_with(b"/*") {
+            return self.lex_in_block_comment();
+        }
+
         if input.starts_with(b"f\"\"\"") {
             let style = QuoteStyle::Triple;
             let span = self.span(4);
@@ -511,11 +564,13 @@ impl<'a> Lexer<'a> {
             "import" => Token::KwImport,
             "in" => Token::KwIn,
             "let" => Token::KwLet,
+            "match" => Token::KwMatch,
             "not" => Token::KwNot,
             "null" => Token::KwNull,
             "or" => Token::KwOr,
             "trace" => Token::KwTrace,
             "true" => Token::KwTrue,
+            "xor" => Token::KwXor,
             _ => Token::Ident,
         }
     }
@@ -536,6 +591,34 @@ impl<'a> Lexer<'a> {
         (Token::LineComment, self.take_while(|ch| ch != b'\n'))
     }
 
+    fn lex_in_block_comment(&mut self) -> Result<Lexeme> {
+        let input = &self.input.as_bytes()[self.start..];
+        let mut depth = 0_usize;
+        let mut n = 0;
+
+        while n < input.len() {
+            match &input[n..] {
+                [b'/', b'*', ..] => {
+                    depth += 1;
+                    n += 2;
+                }
+                [b'*', b'/', ..] => {
+                    depth -= 1;
+                    n += 2;
+                    if depth == 0 {
+                        return Ok((Token::BlockComment, self.span(n)));
+                    }
+                }
+                _ => n += 1,
+            }
+        }
+
+        Span::new(self.doc, self.start, self.start + 2)
+            .error("Unexpected end of input, block comment is not closed.")
+            .with_help("Block comments can be nested, every '/*' needs a matching '*/'.")
+            .err()
+    }
+
     fn lex_in_number(&mut self) -> Result<Lexeme> {
         let mut input = &self.input.as_bytes()[self.start..];
         let mut n = 0;
@@ -578,8 +661,9 @@ impl<'a> Lexer<'a> {
         }
 
         // Then optionally a dot followed by one or more digits ("fraction" in
-        // the json spec). We additionally allow for numeric underscores.
-        if let Some(b'.') = input.first() {
+        // the json spec). We additionally allow for numeric underscores. Two
+        // dots are not a fraction, but a range operator following the number.
+        if input.first() == Some(&b'.') && input.get(1) != Some(&b'.') {
             if input[1..].first().map(|ch| ch.is_ascii_digit()) != Some(true) {
                 self.start += n;
                 return self
@@ -627,6 +711,10 @@ impl<'a> Lexer<'a> {
     fn lex_in_punct(&mut self) -> Result<Lexeme> {
         debug_assert!(self.start < self.input.len());
 
+        if let Some(result) = self.lex_in_punct_trigraph() {
+            return Ok(result);
+        }
+
         if let Some(result) = self.lex_in_punct_digraph() {
             return Ok(result);
         }
@@ -634,6 +722,22 @@ impl<'a> Lexer<'a> {
         self.lex_in_punct_monograph()
     }
 
+    /// Try to lex punctuation of three bytes in length.
+    fn lex_in_punct_trigraph(&mut self) -> Option<Lexeme> {
+        let input = &self.input.as_bytes()[self.start..];
+
+        if input.len() < 3 {
+            return None;
+        }
+
+        let token = match &input[..3] {
+            b"..=" => Token::DotDotEq,
+            _ => return None,
+        };
+
+        Some((token, self.span(3)))
+    }
+
     /// Try to lex punctuation of two bytes in length.
     fn lex_in_punct_digraph(&mut self) -> Option<Lexeme> {
         let input = &self.input.as_bytes()[self.start..];
@@ -649,6 +753,10 @@ impl<'a> Lexer<'a> {
             b"!=" => Token::Neq,
             b"->" => Token::ThinArrow,
             b"=>" => Token::FatArrow,
+            b".." => Token::DotDot,
+            b"**" => Token::Star2,
+            b"<<" => Token::Lt2,
+            b">>" => Token::Gt2,
             _ => return None,
         };
 
@@ -696,12 +804,8 @@ impl<'a> Lexer<'a> {
             b':' => Token::Colon,
             b';' => Token::Semicolon,
             b'|' => Token::Pipe,
-            b'#' => {
-                return span
-                    .error("Unrecognized punctuation here.")
-                    .with_help("Comments are written with '//', not with '#'.")
-                    .err();
-            }
+            b'&' => Token::Amp,
+            b'^' => Token::Caret,
             _ => return span.error("Unrecognized punctuation here.").err(),
         };
 
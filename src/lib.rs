@@ -23,15 +23,21 @@ pub mod env;
 pub mod error;
 pub mod eval;
 pub mod fmt_cst;
+pub mod fmt_csv;
+pub mod fmt_env;
 pub mod fmt_json;
+pub mod fmt_ndjson;
 pub mod fmt_raw;
 pub mod fmt_rcl;
 pub mod fmt_toml;
 pub mod fmt_type;
 pub mod fmt_yaml_stream;
+pub mod glob;
 pub mod highlight;
 pub mod lexer;
 pub mod loader;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod markup;
 pub mod parser;
 pub mod pprint;
@@ -44,3 +50,5 @@ pub mod type_diff;
 pub mod type_source;
 pub mod typecheck;
 pub mod types;
+#[cfg(feature = "watch")]
+pub mod watch;
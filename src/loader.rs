@@ -18,15 +18,16 @@ use crate::abstraction;
 use crate::ast;
 use crate::cli::Target;
 use crate::cst;
-use crate::error::{Error, Result};
+use crate::error::{self, Error, Result};
 use crate::eval::Evaluator;
 use crate::lexer;
 use crate::parser;
 use crate::pprint::{self, concat, indent};
 use crate::runtime::{Env, Value};
 use crate::source::{Doc, DocId, Span};
-use crate::tracer::Tracer;
+use crate::tracer::{Tracer, VoidTracer};
 use crate::typecheck::{self, TypeChecker};
+use crate::types::SourcedType;
 
 /// An owned document.
 ///
@@ -36,8 +37,17 @@ pub struct Document {
     name: String,
 
     /// The document contents.
+    ///
+    /// Line endings are normalized to `\n`, and a leading byte order mark is
+    /// removed, see also [`Document::original_data`].
     data: String,
 
+    /// The contents as loaded, if normalization changed them.
+    ///
+    /// Normalization is for the lexer and for spans. When a file is read as
+    /// data rather than parsed, we return it unchanged.
+    original_data: Option<String>,
+
     /// The span of the final expression, if known.
     ///
     /// If we haven't yet parsed the document, then this is the full span, but
@@ -104,6 +114,13 @@ pub trait Filesystem {
     /// working directory, just like with [`resolve`].
     fn open_build_output(&self, out_path: &str, from: &str) -> Result<fs::File>;
 
+    /// Return the files that match `pattern`, relative to the `from` file.
+    ///
+    /// The `from` path is relative to the working directory, just like with
+    /// [`resolve`]. The matches are relative to the directory of `from`, and
+    /// they are sorted.
+    fn glob(&self, pattern: &str, from: &str) -> Result<Vec<String>>;
+
     /// Return `path`, but relative to the working directory, if possible.
     ///
     /// If the path lies outside of the working directory, return the original.
@@ -137,6 +154,9 @@ impl Filesystem for PanicFilesystem {
     fn open_build_output(&self, _: &str, _: &str) -> Result<File> {
         panic!("Should have initialized the filesystem to a real one before resolving.")
     }
+    fn glob(&self, _: &str, _: &str) -> Result<Vec<String>> {
+        panic!("Should have initialized the filesystem to a real one before listing.")
+    }
     fn get_relative_path<'a>(&self, _: &'a Path) -> &'a Path {
         panic!("Should have initialized the filesystem to a real one before resolving.")
     }
@@ -165,6 +185,9 @@ impl Filesystem for VoidFilesystem {
     fn open_build_output(&self, _: &str, _: &str) -> Result<File> {
         panic!("Void filesystem does not open files.")
     }
+    fn glob(&self, _: &str, _: &str) -> Result<Vec<String>> {
+        Error::new("Void filesystem does not list files.").err()
+    }
     fn get_relative_path<'a>(&self, _: &'a Path) -> &'a Path {
         // It's okay to panic here, `get_relative_path` is only used in features
         // that are not used by the fuzzer.
@@ -181,11 +204,24 @@ pub enum SandboxMode {
     Unrestricted,
 }
 
+/// A directory to search for imports that do not exist relative to the importer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SearchPath {
+    /// A directory passed with `-I`, it is an error if it does not exist.
+    Include(String),
+    /// An entry of `RCL_PATH`, it is skipped if it does not exist.
+    Env(String),
+}
+
 /// Access the real filesystem, but in a potentially sandboxed manner.
 #[derive(Debug)]
 pub struct SandboxFilesystem {
     mode: SandboxMode,
     workdir: PathBuf,
+
+    /// Directories to search for relative imports that do not exist relative to
+    /// the importing file, in order. These are canonicalized absolute paths.
+    search_paths: Vec<PathBuf>,
 }
 
 impl SandboxFilesystem {
@@ -195,10 +231,37 @@ impl SandboxFilesystem {
             None => env::current_dir()?,
         };
         let workdir = fs::canonicalize(workdir)?;
-        let result = SandboxFilesystem { mode, workdir };
+        let result = SandboxFilesystem {
+            mode,
+            workdir,
+            search_paths: Vec::new(),
+        };
         Ok(result)
     }
 
+    /// Add a directory to search for imports, relative to the working directory.
+    ///
+    /// Under the _workdir_ sandbox policy, files in search paths can be loaded
+    /// as well as files in the working directory.
+    pub fn add_search_path(&mut self, dir: &str) -> Result<()> {
+        let path_buf = self.resolve_cli_output(dir);
+        let path_buf = fs::canonicalize(&path_buf).map_err(|err| {
+            Error::new(concat! {
+                "Failed to access search path '"
+                pprint::Doc::path(path_buf)
+                "': "
+                err.to_string()
+            })
+        })?;
+        self.search_paths.push(path_buf);
+        Ok(())
+    }
+
+    /// Whether the _workdir_ sandbox policy allows loading the canonical path.
+    fn is_in_allowed_root(&self, path: &Path) -> bool {
+        path.starts_with(&self.workdir) || self.search_paths.iter().any(|p| path.starts_with(p))
+    }
+
     /// Apply path resolution for an absolute but not yet canonicalized path.
     pub fn resolve_absolute(
         &self,
@@ -221,7 +284,11 @@ impl SandboxFilesystem {
                 // Any path is allowed, nothing to verify.
             }
             SandboxMode::Workdir => {
-                if !path_buf.starts_with(&self.workdir) {
+                if !self.is_in_allowed_root(&path_buf) {
+                    let search_paths_note = match self.search_paths.len() {
+                        0 => "",
+                        _ => " and the search paths",
+                    };
                     let mut err = Error::new(concat! {
                         "Sandbox policy '"
                         pprint::Doc::highlight("workdir")
@@ -229,7 +296,9 @@ impl SandboxFilesystem {
                         pprint::Doc::path(&path_buf)
                         "' because it lies outside of '"
                         pprint::Doc::path(&self.workdir)
-                        "'."
+                        "'"
+                        search_paths_note
+                        "."
                     });
                     let mut base_dir = self.workdir.clone();
                     while !path_buf.starts_with(&base_dir) {
@@ -290,10 +359,22 @@ impl Filesystem for SandboxFilesystem {
         } else if path.starts_with('/') {
             return Error::new("Importing absolute paths is not allowed.").err();
         } else {
-            // The path is relative to the `from` file.
+            // The path is relative to the `from` file. If it does not exist
+            // there, then we try the search paths in order. If it does not exist
+            // there either, we report the error for the relative path.
             path_buf.push(from);
             path_buf.pop();
             path_buf.push(path);
+            if !path_buf.exists() {
+                let found = self
+                    .search_paths
+                    .iter()
+                    .map(|dir| dir.join(path))
+                    .find(|candidate| candidate.exists());
+                if let Some(candidate) = found {
+                    path_buf = candidate;
+                }
+            }
         }
 
         self.resolve_absolute(path_buf, self.mode)
@@ -333,8 +414,9 @@ impl Filesystem for SandboxFilesystem {
         let doc = Document {
             name: path.name,
             data: buf,
-            // This span is a placeholder that is overwritten later when we push.
+            // These are placeholders that are overwritten later when we push.
             span: Span::new(DocId(0), 0, 0),
+            original_data: None,
         };
 
         Ok(doc)
@@ -449,6 +531,34 @@ impl Filesystem for SandboxFilesystem {
         }
     }
 
+    fn glob(&self, pattern: &str, from: &str) -> Result<Vec<String>> {
+        if pattern.starts_with('/') {
+            return Error::new("Glob patterns must be relative, absolute paths are not allowed.")
+                .err();
+        }
+
+        let mut base = self.workdir.clone();
+        base.push(from);
+        base.pop();
+
+        let matches = crate::glob::find_matches(&base, pattern).map_err(|err| {
+            Error::new(concat! {
+                "Failed to list files in '"
+                pprint::Doc::path(&base)
+                "': "
+                err.to_string()
+            })
+        })?;
+
+        // The walk follows `..` and symlinks, so every match has to pass the
+        // same sandbox check as an import would.
+        for m in matches.iter() {
+            self.resolve_absolute(base.join(m), self.mode)?;
+        }
+
+        Ok(matches)
+    }
+
     fn get_relative_path<'a>(&self, path: &'a Path) -> &'a Path {
         match path.strip_prefix(&self.workdir) {
             Ok(p) => p,
@@ -466,6 +576,22 @@ pub struct Loader {
     loaded_files: HashMap<PathBuf, DocId>,
 
     filesystem: Box<dyn Filesystem>,
+
+    /// The documents that are being evaluated, outermost first.
+    ///
+    /// This enables us to detect import cycles, and report the full chain.
+    import_stack: Vec<ImportFrame>,
+
+    /// Whether to typecheck in strict mode, see [`Loader::set_strict`].
+    strict: bool,
+}
+
+/// An entry on the import stack.
+struct ImportFrame {
+    /// The document that we are evaluating.
+    doc: DocId,
+    /// The source that caused evaluation of this document.
+    imported_from: Option<Span>,
 }
 
 impl Loader {
@@ -474,7 +600,70 @@ impl Loader {
             documents: Vec::new(),
             loaded_files: HashMap::new(),
             filesystem: Box::new(PanicFilesystem),
+            import_stack: Vec::new(),
+            strict: false,
+        }
+    }
+
+    /// Enable or disable strict mode for documents typechecked after this.
+    ///
+    /// See [`TypeChecker::set_strict`] for what strict mode rejects. The
+    /// evaluator additionally rejects duplicate set elements in strict mode.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Whether strict mode is enabled, see [`Loader::set_strict`].
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Record that we start evaluating the document.
+    ///
+    /// For imports, `imported_from` is the span of the import. If the document
+    /// is already being evaluated, this returns an error that names the cycle.
+    pub fn push_import(&mut self, doc: DocId, imported_from: Option<Span>) -> Result<()> {
+        if let Some(start) = self.import_stack.iter().position(|f| f.doc == doc) {
+            let mut chain: Vec<&str> = self.import_stack[start..]
+                .iter()
+                .map(|frame| self.get_doc(frame.doc).name)
+                .collect();
+            chain.push(self.get_doc(doc).name);
+            let at = imported_from.expect("Only imports can close a cycle.");
+            let mut err = error::import_cycle_error(at, &chain);
+            // We print the error itself first, and then we want to print what
+            // the offending file was imported from, etc.
+            for frame in self.import_stack[start..].iter().rev() {
+                if let Some(src) = frame.imported_from {
+                    err.add_note(src, "Imported here.");
+                }
+            }
+            return Err(err.into());
         }
+        self.import_stack.push(ImportFrame { doc, imported_from });
+        Ok(())
+    }
+
+    /// Add a note to `err` for every import that led to the current document.
+    ///
+    /// This is for errors in loading an import, so the chain of imports that
+    /// led to the failing one is visible, innermost first.
+    pub fn add_import_notes(&self, err: &mut Error) {
+        for frame in self.import_stack.iter().rev() {
+            if let Some(src) = frame.imported_from {
+                err.add_note(src, "Imported here.");
+            }
+        }
+    }
+
+    /// Record that we finished evaluating the innermost document.
+    pub fn pop_import(&mut self) {
+        self.import_stack.pop().expect("Push/pop are balanced.");
+    }
+
+    /// Return the document that is currently being evaluated, if any.
+    pub fn current_doc(&self) -> Option<DocId> {
+        self.import_stack.last().map(|frame| frame.doc)
     }
 
     /// Set the filesystem access handler.
@@ -482,18 +671,29 @@ impl Loader {
         self.filesystem = filesystem;
     }
 
-    /// Enable filesystem access with the given sandbox mode.
+    /// Enable filesystem access with the given sandbox mode and search paths.
     pub fn initialize_filesystem(
         &mut self,
         mode: SandboxMode,
         workdir: Option<&str>,
+        search_paths: &[SearchPath],
     ) -> Result<()> {
-        let sandbox_fs = SandboxFilesystem::new(mode, workdir).map_err(|err| {
+        let mut sandbox_fs = SandboxFilesystem::new(mode, workdir).map_err(|err| {
             Error::new(concat! {
                 "Failed to initialize filesystem access layer: "
                 err.to_string()
             })
         })?;
+        for search_path in search_paths {
+            match search_path {
+                SearchPath::Include(dir) => sandbox_fs.add_search_path(dir)?,
+                // Like for PATH, a stale entry in the environment should not
+                // break every evaluation, even of documents without imports.
+                SearchPath::Env(dir) => {
+                    let _ = sandbox_fs.add_search_path(dir);
+                }
+            }
+        }
         self.set_filesystem(Box::new(sandbox_fs));
         Ok(())
     }
@@ -509,6 +709,15 @@ impl Loader {
         self.filesystem.open_build_output(out_path, from_name)
     }
 
+    /// List the files that match a glob pattern, relative to document `from`.
+    pub fn glob(&self, pattern: &str, from: Option<DocId>) -> Result<Vec<String>> {
+        let from_path = match from {
+            Some(id) => self.get_doc(id).name,
+            None => "",
+        };
+        self.filesystem.glob(pattern, from_path)
+    }
+
     /// Borrow all documents.
     pub fn as_inputs(&self) -> Vec<Doc> {
         self.documents.iter().map(Document::as_doc).collect()
@@ -519,6 +728,40 @@ impl Loader {
         self.documents[id.0 as usize].as_doc()
     }
 
+    /// Whether we changed the document when loading it.
+    ///
+    /// This is the case when the document has `\r\n` line endings, or when it
+    /// starts with a byte order mark.
+    pub fn is_normalized(&self, id: DocId) -> bool {
+        self.documents[id.0 as usize].original_data.is_some()
+    }
+
+    /// Return the contents of a document as it was loaded, before normalization.
+    pub fn get_original_data(&self, id: DocId) -> &str {
+        let document = &self.documents[id.0 as usize];
+        document.original_data.as_deref().unwrap_or(&document.data)
+    }
+
+    /// Convert a byte offset into a document to an offset into its original data.
+    ///
+    /// Spans point into the document after normalization, which removed a
+    /// leading byte order mark, and the `\r` of every `\r\n`.
+    pub fn get_original_offset(&self, id: DocId, offset: usize) -> usize {
+        let original = self.get_original_data(id).as_bytes();
+        let mut i = match original.starts_with("\u{feff}".as_bytes()) {
+            true => "\u{feff}".len(),
+            false => 0,
+        };
+        let mut remaining = offset;
+        while remaining > 0 && i < original.len() {
+            if !original[i..].starts_with(b"\r\n") {
+                remaining -= 1;
+            }
+            i += 1;
+        }
+        i
+    }
+
     /// Return the span of the document's body expression if known.
     ///
     /// This span is known only after parsing. Before that, this returns the
@@ -534,6 +777,12 @@ impl Loader {
         Ok(tokens)
     }
 
+    /// Lex the given document, and also return the tokens before an error, if any.
+    pub fn get_tokens_partial(&self, id: DocId) -> (Vec<lexer::Lexeme>, Result<()>) {
+        let doc = self.get_doc(id);
+        lexer::lex_partial(id, doc.data)
+    }
+
     /// Parse the given document and return its Concrete Syntax Tree.
     pub fn get_cst(&mut self, id: DocId) -> Result<cst::Expr> {
         let doc = self.get_doc(id);
@@ -547,6 +796,20 @@ impl Loader {
         Ok(expr)
     }
 
+    /// Parse the given document as a type expression, and evaluate the type.
+    ///
+    /// This is used for schema files, that contain only a type.
+    pub fn get_type(&mut self, id: DocId) -> Result<SourcedType> {
+        let doc = self.get_doc(id);
+        let tokens = self.get_tokens(id)?;
+        let (doc_span, ctype) = parser::parse_type(id, doc.data, &tokens)?;
+        let atype = abstraction::abstract_type(doc.data, &ctype)?;
+
+        self.documents[id.0 as usize].span = doc_span;
+
+        typecheck::eval_type_expr(&atype)
+    }
+
     /// Parse the given document and return its Abstract Syntax Tree.
     ///
     /// This is the AST before typecheking.
@@ -571,6 +834,7 @@ impl Loader {
         let span = self.get_span(id);
         let mut ast = self.get_unchecked_ast(id)?;
         let mut checker = TypeChecker::new(env);
+        checker.set_strict(self.strict);
         checker.check_expr(typecheck::type_any(), span, &mut ast)?;
         Ok(ast)
     }
@@ -587,11 +851,52 @@ impl Loader {
         evaluator.eval_doc(type_env, value_env, id)
     }
 
+    /// Evaluate a string as a document, without touching the filesystem.
+    ///
+    /// This replaces the filesystem of the loader with [`VoidFilesystem`], so
+    /// imports fail with an error, and it discards traces. Error messages refer
+    /// to the document by `name`, render them with [`Loader::as_inputs`].
+    pub fn evaluate_str(&mut self, name: &str, source: &str) -> Result<Value> {
+        self.set_filesystem(Box::new(VoidFilesystem));
+        let id = self.load_named_string(name, source.to_string());
+        let mut type_env = typecheck::prelude();
+        let mut value_env = crate::runtime::prelude();
+        self.evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+    }
+
+    /// Evaluate the given document, streaming the elements if it is a list.
+    ///
+    /// See [`Evaluator::eval_doc_streaming`].
+    pub fn evaluate_streaming(
+        &mut self,
+        type_env: &mut typecheck::Env,
+        value_env: &mut Env,
+        id: DocId,
+        tracer: &mut dyn Tracer,
+        on_elem: &mut dyn FnMut(Span, Value) -> Result<()>,
+    ) -> Result<Option<Value>> {
+        let mut evaluator = Evaluator::new(self, tracer);
+        evaluator.eval_doc_streaming(type_env, value_env, id, on_elem)
+    }
+
     /// Push a document and set its span to the full document.
     ///
     /// We set the span here because the span contains the document id, which is
-    /// only known after we push the document.
+    /// only known after we push the document. We also normalize `\r\n` line
+    /// endings to `\n` here, so the lexer, spans in error messages, and string
+    /// literals all see the same thing regardless of the line ending convention.
+    /// For the same reason, we remove a leading byte order mark. Spans are byte
+    /// offsets into the normalized data, so they remain consistent.
     fn push(&mut self, mut document: Document) -> DocId {
+        let has_bom = document.data.starts_with('\u{feff}');
+        if has_bom || document.data.contains("\r\n") {
+            let data = document
+                .data
+                .strip_prefix('\u{feff}')
+                .unwrap_or(&document.data);
+            let normalized = data.replace("\r\n", "\n");
+            document.original_data = Some(std::mem::replace(&mut document.data, normalized));
+        }
         let n = self.documents.len();
         let id = DocId(n.try_into().expect("Cannot load that many documents!"));
         document.span = Span::new(id, 0, document.data.len());
@@ -608,8 +913,9 @@ impl Loader {
         let doc = Document {
             name: "stdin".to_string(),
             data: buf,
-            // This span is a placeholder that is overwritten by `push`.
+            // These are placeholders that are overwritten by `push`.
             span: Span::new(DocId(0), 0, 0),
+            original_data: None,
         };
         Ok(self.push(doc))
     }
@@ -642,13 +948,28 @@ impl Loader {
         Ok(id)
     }
 
+    /// Return the paths of all files that were loaded from the filesystem.
+    ///
+    /// These are the entrypoints and everything that they import, transitively.
+    pub fn loaded_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.loaded_files.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
     /// Load a string into a new document.
     pub fn load_string(&mut self, data: String) -> DocId {
+        self.load_named_string("input", data)
+    }
+
+    /// Load a string into a new document, that error messages refer to by `name`.
+    pub fn load_named_string(&mut self, name: &str, data: String) -> DocId {
         let doc = Document {
-            name: "input".to_string(),
+            name: name.to_string(),
             data,
-            // This span is a placeholder that is overwritten by `push`.
+            // These are placeholders that are overwritten by `push`.
             span: Span::new(DocId(0), 0, 0),
+            original_data: None,
         };
         self.push(doc)
     }
@@ -727,3 +1048,137 @@ impl Loader {
             .map_err(|err| Error::new(format!("Failed to write depfile: {}.", err)).into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Loader, SandboxMode, VoidFilesystem};
+    use crate::cli::Target;
+    use crate::pprint::Config;
+    use crate::runtime::Value;
+    use crate::tracer::VoidTracer;
+
+    /// Evaluate the document, return the result or error report as string.
+    fn eval(data: &str) -> String {
+        let mut loader = Loader::new();
+        loader.set_filesystem(Box::new(VoidFilesystem));
+        let id = loader.load_string(data.to_string());
+        let mut type_env = crate::typecheck::prelude();
+        let mut value_env = crate::runtime::prelude();
+        let cfg = Config { width: 80 };
+        let result = loader.evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer);
+        match result {
+            Ok(v) => crate::fmt_rcl::format_rcl(&v)
+                .println(&cfg)
+                .to_string_no_markup(),
+            Err(err) => {
+                let inputs = loader.as_inputs();
+                err.report(&inputs).println(&cfg).to_string_no_markup()
+            }
+        }
+    }
+
+    #[test]
+    fn load_crlf_normalizes_line_endings() {
+        let result = eval("// Comment.\r\n[\r\n  \"\"\"\r\n  a\r\n  b\r\n  \"\"\",\r\n]\r\n");
+        assert_eq!(result, "[\"a\\nb\\n\"]\n");
+    }
+
+    #[test]
+    fn load_crlf_reports_visual_line_numbers() {
+        let result = eval("let x = 1;\r\n\r\nlet y = x + true;\r\ny\r\n");
+        assert_eq!(
+            result.lines().take(4).collect::<Vec<_>>(),
+            [
+                "input:3:13",
+                "  ╷",
+                "3 │ let y = x + true;",
+                "  ╵             ^~~~",
+            ],
+        );
+    }
+
+    #[test]
+    fn evaluate_str_returns_value() {
+        let mut loader = Loader::new();
+        let result = loader.evaluate_str("config.rcl", "let x = 20; x + 22");
+        assert_eq!(result.unwrap(), Value::Int(42));
+    }
+
+    #[test]
+    fn evaluate_str_reports_errors_in_named_source() {
+        let mut loader = Loader::new();
+        let err = loader
+            .evaluate_str("config.rcl", "let x = 1;\nx + true\n")
+            .unwrap_err();
+
+        let span = err.origin.expect("Type errors have a source location.");
+        let source = loader.get_doc(span.doc()).data;
+        assert_eq!(&source[span.start()..span.end()], "true");
+
+        let inputs = loader.as_inputs();
+        let cfg = Config { width: 80 };
+        let report = err.report(&inputs).println(&cfg).to_string_no_markup();
+        assert_eq!(report.lines().next(), Some("config.rcl:2:5"));
+    }
+
+    #[test]
+    fn evaluate_str_does_not_touch_the_filesystem() {
+        let mut loader = Loader::new();
+        let result = loader.evaluate_str("config.rcl", "import \"Cargo.toml\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn import_cycle_reports_full_chain() {
+        let mut loader = Loader::new();
+        loader
+            .initialize_filesystem(SandboxMode::Workdir, Some("golden/error"), &[])
+            .unwrap();
+        let target = Target::File("_import_cycle_b.rcl".to_string());
+        let id = loader.load_cli_target(&target).unwrap();
+        let cfg = Config { width: 200 };
+
+        // Evaluate twice, to confirm that the error does not leave anything on
+        // the import stack that would affect the next evaluation.
+        for _ in 0..2 {
+            let mut type_env = crate::typecheck::prelude();
+            let mut value_env = crate::runtime::prelude();
+            let err = loader
+                .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+                .unwrap_err();
+            let inputs = loader.as_inputs();
+            let report = err.report(&inputs).println(&cfg).to_string_no_markup();
+            assert!(report.contains(
+                "Error: This import creates a cycle. The cycle is \
+                _import_cycle_b.rcl → _import_cycle_c.rcl → _import_cycle_a.rcl → \
+                _import_cycle_b.rcl."
+            ));
+        }
+    }
+
+    #[test]
+    fn loaded_paths_exclude_imports_that_evaluation_does_not_reach() {
+        let mut loader = Loader::new();
+        loader
+            .initialize_filesystem(SandboxMode::Workdir, Some("golden/include"), &[])
+            .unwrap();
+        let id = loader.load_string(
+            r#"
+            let unused = if false: import "lib/util.rcl" else: null;
+            [import "shadowed.rcl", unused]
+            "#
+            .to_string(),
+        );
+        let mut type_env = crate::typecheck::prelude();
+        let mut value_env = crate::runtime::prelude();
+        loader
+            .evaluate(&mut type_env, &mut value_env, id, &mut VoidTracer)
+            .unwrap();
+        let names: Vec<_> = loader
+            .loaded_paths()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["shadowed.rcl"]);
+    }
+}
@@ -0,0 +1,832 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2023 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! A minimal language server for RCL.
+//!
+//! The server speaks the Language Server Protocol: JSON-RPC messages framed by
+//! a `Content-Length` header, over stdin and stdout. It publishes diagnostics,
+//! produced by the same parser and typechecker as the CLI, and it provides
+//! completions. The server does not evaluate documents, so it does not report
+//! runtime errors.
+//!
+//! Messages come from the client, so we parse them with a strict json parser,
+//! not with the RCL evaluator. Json values map onto RCL values, except for
+//! non-integer numbers, which RCL does not have. We keep those as the string
+//! of their source text, none of the fields that the server reads are floats.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+use crate::abstraction::{completions, Completion};
+use crate::error::Error;
+use crate::fmt_json::format_json;
+use crate::loader::{Loader, VoidFilesystem};
+use crate::pprint;
+use crate::runtime::Value;
+use crate::source::{DocId, Span};
+use crate::typecheck;
+
+/// The largest message body that we accept.
+///
+/// We allocate the body before reading it, so the `Content-Length` that the
+/// client claims must be bounded.
+const MAX_MESSAGE_LEN: usize = 256 * 1024 * 1024;
+
+/// JSON-RPC error code for a message that is not valid json.
+const PARSE_ERROR: i64 = -32700;
+
+/// JSON-RPC error code for a request with a method that we do not support.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// LSP `DiagnosticSeverity` for errors.
+const SEVERITY_ERROR: i64 = 1;
+
+/// LSP `TextDocumentSyncKind` for sending the full document on every change.
+const SYNC_FULL: i64 = 1;
+
+/// LSP `CompletionItemKind`s.
+const COMPLETION_METHOD: i64 = 2;
+const COMPLETION_FIELD: i64 = 5;
+const COMPLETION_VARIABLE: i64 = 6;
+
+/// Read one message from the input, return `None` at end of input.
+fn read_message(input: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(len) = header.strip_prefix("Content-Length:") {
+            content_length = len.trim().parse::<usize>().ok();
+        }
+    }
+    let len = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header.")
+    })?;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Content-Length exceeds the maximum message size.",
+        ));
+    }
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Write one message to the output.
+fn write_message(output: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let span = Span::new(DocId(0), 0, 0);
+    let doc = format_json(span, message).expect("Messages contain only json values.");
+    // A large width puts the entire message on one line.
+    let cfg = pprint::Config { width: u32::MAX };
+    let body = doc.println(&cfg).to_string_no_markup();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// The maximum nesting depth of arrays and objects in a message.
+///
+/// Messages are untrusted input, this bounds the recursion of the parser.
+const MAX_JSON_DEPTH: u32 = 128;
+
+/// Parse a json message. Returns `None` if the message is not valid json.
+fn parse_json(body: &str) -> Option<Value> {
+    let mut parser = JsonParser {
+        input: body.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    match parser.pos == parser.input.len() {
+        true => Some(value),
+        false => None,
+    }
+}
+
+/// A recursive descent parser for json as specified in RFC 8259.
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: u32,
+}
+
+impl<'a> JsonParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `expected` if it is next, return whether it was.
+    fn eat(&mut self, expected: &[u8]) -> bool {
+        let is_match = self.input[self.pos..].starts_with(expected);
+        if is_match {
+            self.pos += expected.len();
+        }
+        is_match
+    }
+
+    fn parse_value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'{' => self.parse_object(),
+            b'[' => self.parse_array(),
+            b'"' => self.parse_string().map(|s| Value::String(s.into())),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            _ if self.eat(b"null") => Some(Value::Null),
+            _ if self.eat(b"true") => Some(Value::Bool(true)),
+            _ if self.eat(b"false") => Some(Value::Bool(false)),
+            _ => None,
+        }
+    }
+
+    /// Parse the elements of an array or object, separated by commas.
+    fn parse_seq<F>(&mut self, open: u8, close: u8, mut parse_elem: F) -> Option<()>
+    where
+        F: FnMut(&mut Self) -> Option<()>,
+    {
+        self.depth += 1;
+        if self.depth > MAX_JSON_DEPTH || !self.eat(&[open]) {
+            return None;
+        }
+        self.skip_whitespace();
+        if !self.eat(&[close]) {
+            loop {
+                parse_elem(self)?;
+                self.skip_whitespace();
+                if self.eat(&[close]) {
+                    break;
+                }
+                if !self.eat(b",") {
+                    return None;
+                }
+            }
+        }
+        self.depth -= 1;
+        Some(())
+    }
+
+    fn parse_array(&mut self) -> Option<Value> {
+        let mut elements = Vec::new();
+        self.parse_seq(b'[', b']', |p| {
+            elements.push(p.parse_value()?);
+            Some(())
+        })?;
+        Some(Value::List(Rc::new(elements)))
+    }
+
+    fn parse_object(&mut self) -> Option<Value> {
+        let mut fields = BTreeMap::new();
+        self.parse_seq(b'{', b'}', |p| {
+            p.skip_whitespace();
+            let key = p.parse_string()?;
+            p.skip_whitespace();
+            if !p.eat(b":") {
+                return None;
+            }
+            let value = p.parse_value()?;
+            fields.insert(Value::String(key.into()), value);
+            Some(())
+        })?;
+        Some(Value::Dict(Rc::new(fields)))
+    }
+
+    /// Parse four hex digits of a `\u` escape sequence.
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let digits = self.input.get(self.pos..self.pos + 4)?;
+        let digits = std::str::from_utf8(digits).ok()?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        if !self.eat(b"\"") {
+            return None;
+        }
+        let mut result = String::new();
+        loop {
+            // The input is a `&str`, and we only stop at ascii bytes, so the
+            // run of bytes before the stop is valid UTF-8.
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            result.push_str(std::str::from_utf8(&self.input[start..self.pos]).ok()?);
+
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(result);
+                }
+                b'\\' => self.pos += 1,
+                // Control characters must be escaped.
+                _ => return None,
+            }
+            let escaped = self.peek()?;
+            self.pos += 1;
+            let c = match escaped {
+                b'"' => '"',
+                b'\\' => '\\',
+                b'/' => '/',
+                b'b' => '\u{8}',
+                b'f' => '\u{c}',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'u' => {
+                    let high = self.parse_hex4()?;
+                    match high {
+                        // A high surrogate must be followed by a low surrogate.
+                        0xd800..=0xdbff => {
+                            if !self.eat(b"\\u") {
+                                return None;
+                            }
+                            let low = self.parse_hex4()?;
+                            if !(0xdc00..=0xdfff).contains(&low) {
+                                return None;
+                            }
+                            char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))?
+                        }
+                        // Lone surrogates are not valid scalar values, so
+                        // `from_u32` rejects them.
+                        _ => char::from_u32(high)?,
+                    }
+                }
+                _ => return None,
+            };
+            result.push(c);
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<Value> {
+        let start = self.pos;
+        self.eat(b"-");
+        // Leading zeros are not allowed, so the integer part is either a
+        // single zero, or digits that do not start with zero.
+        if !self.eat(b"0") {
+            self.eat_digits()?;
+        }
+        let mut is_int = true;
+        if self.eat(b".") {
+            is_int = false;
+            self.eat_digits()?;
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_int = false;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            self.eat_digits()?;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).ok()?;
+        match text.parse::<i64>() {
+            Ok(i) if is_int => Some(Value::Int(i)),
+            // RCL has no floats, and integers may exceed the range of an Int.
+            _ => Some(Value::String(text.into())),
+        }
+    }
+
+    /// Consume one or more digits.
+    fn eat_digits(&mut self) -> Option<()> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        match self.pos > start {
+            true => Some(()),
+            false => None,
+        }
+    }
+}
+
+/// Build a json object from key-value pairs.
+fn object<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    let dict: BTreeMap<Value, Value> = fields.into_iter().map(|(k, v)| (k.into(), v)).collect();
+    Value::Dict(Rc::new(dict))
+}
+
+/// Look up a field of a json object, return `Null` if it is absent.
+fn get<'a>(value: &'a Value, key: &str) -> &'a Value {
+    match value {
+        Value::Dict(dict) => dict.get(&key.into()).unwrap_or(&Value::Null),
+        _ => &Value::Null,
+    }
+}
+
+/// Look up a string field of a json object.
+fn get_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    match get(value, key) {
+        Value::String(s) => Some(s.as_ref()),
+        _ => None,
+    }
+}
+
+/// Convert a byte offset into an LSP position.
+///
+/// LSP positions use zero-based lines, and columns in UTF-16 code units.
+fn position(input: &str, offset: usize) -> Value {
+    let before = &input[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    object([
+        ("line", Value::Int(line as i64)),
+        ("character", Value::Int(character as i64)),
+    ])
+}
+
+/// Convert an LSP position into a byte offset, if it lies inside the input.
+fn offset(input: &str, position: &Value) -> Option<usize> {
+    let (line, character) = match (get(position, "line"), get(position, "character")) {
+        (Value::Int(line), Value::Int(character)) => (*line, *character),
+        _ => return None,
+    };
+    let line_start = match line {
+        0 => 0,
+        n => {
+            input
+                .match_indices('\n')
+                .nth(usize::try_from(n - 1).ok()?)?
+                .0
+                + 1
+        }
+    };
+    let mut units = 0;
+    for (i, ch) in input[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return Some(line_start + i);
+        }
+        units += ch.len_utf16() as i64;
+    }
+    Some(input.len())
+}
+
+/// Return LSP completion items for the given position in the document.
+fn completion_items(input: &str, position: &Value) -> Vec<Value> {
+    let cursor = match offset(input, position) {
+        Some(cursor) => cursor,
+        None => return Vec::new(),
+    };
+    completions(input, cursor)
+        .iter()
+        .map(|completion| {
+            let kind = match completion {
+                Completion::Variable(..) => COMPLETION_VARIABLE,
+                Completion::Field(..) => COMPLETION_FIELD,
+                Completion::Method(..) => COMPLETION_METHOD,
+            };
+            object([
+                ("label", completion.name().into()),
+                ("kind", Value::Int(kind)),
+            ])
+        })
+        .collect()
+}
+
+/// Convert an error into an LSP diagnostic for the byte range of the input.
+fn diagnostic(input: &str, (start, end): (usize, usize), error: &Error) -> Value {
+    let cfg = pprint::Config { width: 80 };
+    let mut message = error.message.println(&cfg).to_string_no_markup();
+    for extra in error.body.iter().chain(error.help.iter()) {
+        message.push('\n');
+        message.push_str(&extra.println(&cfg).to_string_no_markup());
+    }
+    object([
+        (
+            "range",
+            object([
+                ("start", position(input, start)),
+                ("end", position(input, end)),
+            ]),
+        ),
+        ("severity", Value::Int(SEVERITY_ERROR)),
+        ("source", "rcl".into()),
+        ("message", message.trim_end().into()),
+    ])
+}
+
+/// Parse and typecheck a document, and return diagnostics for it.
+fn check_document(text: &str) -> Vec<Value> {
+    let mut loader = Loader::new();
+    loader.set_filesystem(Box::new(VoidFilesystem));
+    let id = loader.load_string(text.to_string());
+    let mut type_env = typecheck::prelude();
+    match loader.get_typechecked_ast(&mut type_env, id) {
+        Ok(..) => Vec::new(),
+        // Errors are fatal, so there is at most one diagnostic. The loader may
+        // have normalized the document, but the client has the original text,
+        // so we convert the span to offsets into that.
+        Err(err) => {
+            let range = match err.origin {
+                Some(span) => (
+                    loader.get_original_offset(id, span.start()),
+                    loader.get_original_offset(id, span.end()),
+                ),
+                None => (0, 0),
+            };
+            vec![diagnostic(text, range, &err)]
+        }
+    }
+}
+
+/// The state of the language server.
+pub struct Server<'a> {
+    output: &'a mut dyn Write,
+
+    /// The contents of the documents that the client has open, by uri.
+    documents: BTreeMap<String, String>,
+}
+
+impl<'a> Server<'a> {
+    pub fn new(output: &'a mut dyn Write) -> Server<'a> {
+        Server {
+            output,
+            documents: BTreeMap::new(),
+        }
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(self.output, &message)
+    }
+
+    fn send_result(&mut self, id: Value, result: Value) -> io::Result<()> {
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+
+    fn send_error(&mut self, id: Value, code: i64, message: &str) -> io::Result<()> {
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                object([("code", Value::Int(code)), ("message", message.into())]),
+            ),
+        ]))
+    }
+
+    fn send_notification(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(object([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = match self.documents.get(uri) {
+            Some(text) => check_document(text),
+            None => Vec::new(),
+        };
+        let params = object([
+            ("uri", uri.into()),
+            ("diagnostics", Value::List(Rc::new(diagnostics))),
+        ]);
+        self.send_notification("textDocument/publishDiagnostics", params)
+    }
+
+    /// Handle a request, a message that expects a response.
+    fn handle_request(&mut self, id: Value, method: &str, params: &Value) -> io::Result<()> {
+        match method {
+            "initialize" => {
+                let completion =
+                    object([("triggerCharacters", Value::List(Rc::new(vec![".".into()])))]);
+                // Positions count UTF-16 code units. This is the default, but we
+                // state it, because clients may offer other encodings.
+                let capabilities = object([
+                    ("positionEncoding", "utf-16".into()),
+                    ("textDocumentSync", Value::Int(SYNC_FULL)),
+                    ("completionProvider", completion),
+                ]);
+                let server_info = object([
+                    ("name", "rcl".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                ]);
+                let result = object([("capabilities", capabilities), ("serverInfo", server_info)]);
+                self.send_result(id, result)
+            }
+            "textDocument/completion" => {
+                let uri = get_str(get(params, "textDocument"), "uri").unwrap_or("");
+                let items = match self.documents.get(uri) {
+                    Some(text) => completion_items(text, get(params, "position")),
+                    None => Vec::new(),
+                };
+                self.send_result(id, Value::List(Rc::new(items)))
+            }
+            "shutdown" => self.send_result(id, Value::Null),
+            _ => self.send_error(id, METHOD_NOT_FOUND, "Method not supported."),
+        }
+    }
+
+    /// Handle a notification, a message that does not get a response.
+    fn handle_notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let text_document = get(params, "textDocument");
+        let uri = match get_str(text_document, "uri") {
+            Some(uri) => uri,
+            None => return Ok(()),
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = get_str(text_document, "text").unwrap_or("");
+                self.documents.insert(uri.to_string(), text.to_string());
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didChange" => {
+                // We request full sync, so the last change contains the full
+                // document.
+                let text = match get(params, "contentChanges") {
+                    Value::List(changes) => changes.last().and_then(|c| get_str(c, "text")),
+                    _ => None,
+                };
+                if let Some(text) = text {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didSave" => self.publish_diagnostics(uri),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle one message. Returns false when the server should exit.
+    fn handle_message(&mut self, body: String) -> io::Result<bool> {
+        let message = match parse_json(&body) {
+            Some(message) => message,
+            None => {
+                self.send_error(Value::Null, PARSE_ERROR, "Failed to parse message.")?;
+                return Ok(true);
+            }
+        };
+        let method = get_str(&message, "method").unwrap_or("");
+        let params = get(&message, "params");
+        match get(&message, "id") {
+            _ if method == "exit" => return Ok(false),
+            Value::Null => self.handle_notification(method, params)?,
+            id => self.handle_request(id.clone(), method, params)?,
+        }
+        Ok(true)
+    }
+}
+
+/// Run the language server until the client asks it to exit.
+pub fn run(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+    let mut server = Server::new(output);
+    while let Some(body) = read_message(input)? {
+        if !server.handle_message(body)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    /// Run the server on the given messages, return the messages it sent.
+    fn run_messages(messages: &[&str]) -> Vec<Value> {
+        let input: String = messages.iter().map(|m| frame(m)).collect();
+        let mut output = Vec::new();
+        run(&mut input.as_bytes(), &mut output).unwrap();
+        let mut output = &output[..];
+        let mut result = Vec::new();
+        while let Some(body) = read_message(&mut output).unwrap() {
+            result.push(parse_json(&body).unwrap());
+        }
+        result
+    }
+
+    #[test]
+    fn did_open_did_change_publishes_diagnostics() {
+        let responses = run_messages(&[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "method": "initialized", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": "file:///a.rcl", "languageId": "rcl", "version": 1,
+                "text": "let x = 1;\nx + true"}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": {"uri": "file:///a.rcl", "version": 2},
+                "contentChanges": [{"text": "let x = 1;\nx + 1"}]
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#,
+        ]);
+        assert_eq!(responses.len(), 4);
+
+        let init = &responses[0];
+        assert_eq!(get(init, "id"), &Value::Int(1));
+        let capabilities = get(get(init, "result"), "capabilities");
+        assert_eq!(
+            get(capabilities, "textDocumentSync"),
+            &Value::Int(SYNC_FULL)
+        );
+        assert_eq!(get_str(capabilities, "positionEncoding"), Some("utf-16"));
+
+        let open = &responses[1];
+        assert_eq!(
+            get_str(open, "method"),
+            Some("textDocument/publishDiagnostics")
+        );
+        let params = get(open, "params");
+        assert_eq!(get_str(params, "uri"), Some("file:///a.rcl"));
+        let diagnostics = match get(params, "diagnostics") {
+            Value::List(xs) => xs.clone(),
+            _ => panic!("Expected a list of diagnostics."),
+        };
+        assert_eq!(diagnostics.len(), 1);
+        let range = get(&diagnostics[0], "range");
+        assert_eq!(
+            get(range, "start"),
+            &object([("line", Value::Int(1)), ("character", Value::Int(4))]),
+        );
+        assert_eq!(
+            get(range, "end"),
+            &object([("line", Value::Int(1)), ("character", Value::Int(8))]),
+        );
+        assert!(get_str(&diagnostics[0], "message")
+            .unwrap()
+            .starts_with("Type mismatch."));
+
+        let change = &responses[2];
+        let params = get(change, "params");
+        assert_eq!(
+            get(params, "diagnostics"),
+            &Value::List(Rc::new(Vec::new()))
+        );
+
+        assert_eq!(get(&responses[3], "id"), &Value::Int(2));
+        assert_eq!(get(&responses[3], "result"), &Value::Null);
+    }
+
+    #[test]
+    fn completion_lists_variables() {
+        let responses = run_messages(&[
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": "file:///a.rcl", "text": "let answer = 42;\nan"}
+            }}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "textDocument/completion", "params": {
+                "textDocument": {"uri": "file:///a.rcl"},
+                "position": {"line": 1, "character": 2}
+            }}"#,
+        ]);
+        assert_eq!(responses.len(), 2);
+        let expected = object([
+            ("label", "answer".into()),
+            ("kind", Value::Int(COMPLETION_VARIABLE)),
+        ]);
+        assert_eq!(
+            get(&responses[1], "result"),
+            &Value::List(Rc::new(vec![expected]))
+        );
+    }
+
+    #[test]
+    fn position_counts_utf16_code_units() {
+        let input = "a\n\u{e9}\u{1f980}x";
+        assert_eq!(
+            position(input, 8),
+            object([("line", Value::Int(1)), ("character", Value::Int(3))]),
+        );
+        let pos = object([("line", Value::Int(1)), ("character", Value::Int(3))]);
+        assert_eq!(offset(input, &pos), Some(8));
+        let pos = object([("line", Value::Int(1)), ("character", Value::Int(100))]);
+        assert_eq!(offset(input, &pos), Some(input.len()));
+        let pos = object([("line", Value::Int(2)), ("character", Value::Int(0))]);
+        assert_eq!(offset(input, &pos), None);
+    }
+
+    #[test]
+    fn diagnostics_use_positions_in_the_original_text() {
+        let range = |text: &str| {
+            let diagnostics = check_document(text);
+            assert_eq!(diagnostics.len(), 1);
+            let range = get(&diagnostics[0], "range");
+            (get(range, "start").clone(), get(range, "end").clone())
+        };
+        let at = |line: i64, character: i64| {
+            object([
+                ("line", Value::Int(line)),
+                ("character", Value::Int(character)),
+            ])
+        };
+        // The loader strips the byte order mark, but it is part of the text
+        // that the client has, so it counts as a character on the first line.
+        assert_eq!(range("\u{feff}1 + true"), (at(0, 5), at(0, 9)));
+        assert_eq!(range("\u{feff}\r\n1 + true"), (at(1, 4), at(1, 8)));
+        assert_eq!(range("let x = 1;\r\n\r\nx + true"), (at(2, 4), at(2, 8)));
+        // Characters outside the basic multilingual plane are two code units.
+        assert_eq!(range("[\"\u{1f980}\", 1 + true]"), (at(0, 11), at(0, 15)));
+    }
+
+    #[test]
+    fn read_message_rejects_oversized_content_length() {
+        let input = format!("Content-Length: {}\r\n\r\n{{}}", MAX_MESSAGE_LEN + 1);
+        let err = read_message(&mut input.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_json_maps_values() {
+        let parsed = parse_json(r#" {"a": [1, -2, true, null], "b": {}, "c": "x\ty"} "#);
+        let expected = object([
+            (
+                "a",
+                Value::List(Rc::new(vec![
+                    Value::Int(1),
+                    Value::Int(-2),
+                    Value::Bool(true),
+                    Value::Null,
+                ])),
+            ),
+            // An empty object is an empty dict, not a set.
+            ("b", object([])),
+            ("c", "x\ty".into()),
+        ]);
+        assert_eq!(parsed, Some(expected));
+    }
+
+    #[test]
+    fn parse_json_keeps_non_integer_numbers_as_text() {
+        let parsed = parse_json(r#"[0.5, 1e3, -0, 99999999999999999999]"#);
+        let expected: Vec<Value> = vec![
+            "0.5".into(),
+            "1e3".into(),
+            Value::Int(0),
+            "99999999999999999999".into(),
+        ];
+        assert_eq!(parsed, Some(Value::List(Rc::new(expected))));
+    }
+
+    #[test]
+    fn parse_json_decodes_escapes() {
+        let parsed = parse_json(r#""\"\\\/\né😀""#);
+        assert_eq!(parsed, Some("\"\\/\né😀".into()));
+    }
+
+    #[test]
+    fn parse_json_rejects_invalid_input() {
+        let invalid = [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "{a: 1}",
+            "01",
+            "1.",
+            "[1] [2]",
+            "\"\u{1}\"",
+            r#""\ud83d""#,
+            r#""\x""#,
+            // RCL syntax that is not json.
+            "{1, 2}",
+            "[for x in [1]: x]",
+            "nul",
+        ];
+        for input in invalid {
+            assert_eq!(parse_json(input), None, "Should reject: {input}");
+        }
+        let deep =
+            "[".repeat(MAX_JSON_DEPTH as usize + 1) + &"]".repeat(MAX_JSON_DEPTH as usize + 1);
+        assert_eq!(parse_json(&deep), None);
+        let ok = "[".repeat(MAX_JSON_DEPTH as usize) + &"]".repeat(MAX_JSON_DEPTH as usize);
+        assert!(parse_json(&ok).is_some());
+    }
+}
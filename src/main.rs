@@ -7,16 +7,21 @@
 
 use std::io::Write;
 use std::path::Path;
+use std::rc::Rc;
 
 use rcl::cli::{
-    self, Cmd, EvalOptions, FormatTarget, GlobalOptions, OutputTarget, StyleOptions, Target,
+    self, Cmd, EvalOptions, FormatTarget, GlobalOptions, OutputFormat, OutputTarget, StyleOptions,
+    Target,
 };
+use rcl::cmd_build::BuildMode;
 use rcl::error::{Error, Result};
+use rcl::fmt_json::ListWriter;
+use rcl::highlight::{number_lines, Gutter};
 use rcl::loader::{Loader, SandboxMode};
-use rcl::markup::{MarkupMode, MarkupString};
+use rcl::markup::{Markup, MarkupMode, MarkupString};
 use rcl::pprint::{self, Doc};
 use rcl::runtime::{self, Value};
-use rcl::source::Span;
+use rcl::source::{DocId, Span};
 use rcl::tracer::StderrTracer;
 use rcl::typecheck;
 
@@ -42,12 +47,7 @@ impl App {
         data: MarkupString,
         out_path: &Path,
     ) -> std::io::Result<()> {
-        let f = std::fs::File::create(out_path)?;
-        let mut w = std::io::BufWriter::new(f);
-        data.write_bytes(mode, &mut w)?;
-        // Flush to force errors to materialize. Without this, flush would
-        // happen on drop, which has no opportunity to report errors.
-        w.flush()
+        write_file_atomic(out_path, |w| data.write_bytes(mode, w))
     }
 
     /// Write a string to a file.
@@ -71,14 +71,12 @@ impl App {
         &self,
         output: OutputTarget,
         style_opts: &StyleOptions,
+        trailing_newline: bool,
         doc: Doc,
     ) -> Result<()> {
         let stdout = std::io::stdout();
         let markup = match output {
-            OutputTarget::Stdout => self
-                .opts
-                .markup
-                .unwrap_or_else(|| MarkupMode::default_for_fd(&stdout)),
+            OutputTarget::Stdout => self.opts.markup_for_fd(&stdout),
             // When the output is a file, we don't want to put ANSI escape codes
             // in the file; --output is unaffected by --color.
             OutputTarget::File(..) => MarkupMode::None,
@@ -86,7 +84,10 @@ impl App {
         let cfg = pprint::Config {
             width: style_opts.width,
         };
-        let result = doc.println(&cfg);
+        let mut result = doc.println(&cfg);
+        if !trailing_newline {
+            result.trim_newline_end();
+        }
         match output {
             OutputTarget::Stdout => {
                 let mut out = stdout.lock();
@@ -101,10 +102,7 @@ impl App {
 
     fn print_doc_stderr(&self, doc: Doc) {
         let stderr = std::io::stderr();
-        let markup = self
-            .opts
-            .markup
-            .unwrap_or_else(|| MarkupMode::default_for_fd(&stderr));
+        let markup = self.opts.markup_for_fd(&stderr);
         let cfg = pprint::Config { width: 80 };
         let result = doc.println(&cfg);
         let mut out = stderr.lock();
@@ -119,7 +117,12 @@ impl App {
         value_span: Span,
         value: &Value,
     ) -> Result<()> {
-        let out_doc = rcl::cmd_eval::format_value(eval_opts.format, value_span, value)?;
+        let out_doc = rcl::cmd_eval::format_value(
+            eval_opts.format,
+            eval_opts.rcl_int_base,
+            value_span,
+            value,
+        )?;
 
         // Prepend the banner if the user specified one.
         let out_doc = match eval_opts.banner.as_ref() {
@@ -127,7 +130,297 @@ impl App {
             None => out_doc,
         };
 
-        self.print_doc_target(output, style_opts, out_doc)
+        self.print_doc_target(output, style_opts, eval_opts.trailing_newline, out_doc)
+    }
+
+    /// Evaluate every file in a directory tree, and write the outputs to a tree.
+    ///
+    /// A failure in one file does not stop the build, we report the error and
+    /// continue with the next file, and fail at the end.
+    fn main_build_tree(
+        &mut self,
+        eval_opts: &EvalOptions,
+        style_opts: &StyleOptions,
+        build_mode: BuildMode,
+        input_dir: &str,
+        output_dir: &str,
+    ) -> Result<()> {
+        let input_path = self.loader.resolve_cli_output_path(input_dir);
+        let targets = rcl::cmd_build::list_tree_targets(&input_path, eval_opts.format)?;
+        let mut num_failed = 0;
+
+        for (i, target) in targets.iter().enumerate() {
+            let input = Path::new(input_dir).join(&target.input);
+            let output = Path::new(output_dir).join(&target.output);
+            let output = output.to_string_lossy();
+            println!("[{}/{}] {}", i + 1, targets.len(), output);
+
+            let result = self.build_tree_target(
+                eval_opts,
+                style_opts,
+                build_mode,
+                &input.to_string_lossy(),
+                &output,
+            );
+            if let Err(err) = result {
+                let inputs = self.loader.as_inputs();
+                self.print_doc_stderr(err.report(&inputs));
+                num_failed += 1;
+            }
+        }
+
+        match num_failed {
+            0 => Ok(()),
+            n => Error::new(format!("Failed to build {n} of {} files.", targets.len())).err(),
+        }
+    }
+
+    /// Evaluate multiple input files, and print their results in order.
+    ///
+    /// A failure in one file does not stop evaluation of the others, unless
+    /// `fail_fast` is set. We report the error for every file that fails, and
+    /// print the results only when all files succeed.
+    fn main_eval_many(
+        &mut self,
+        eval_opts: &EvalOptions,
+        style_opts: &StyleOptions,
+        fnames: &[Target],
+        concat: bool,
+        fail_fast: bool,
+        output: OutputTarget,
+    ) -> Result<()> {
+        let mut results = Vec::with_capacity(fnames.len());
+        let mut num_failed = 0;
+
+        for fname in fnames {
+            match self.eval_many_target(eval_opts, fname, concat) {
+                Ok(result) => results.push(result),
+                Err(err) if fail_fast => return Err(err),
+                Err(err) => {
+                    let inputs = self.loader.as_inputs();
+                    self.print_doc_stderr(err.report(&inputs));
+                    num_failed += 1;
+                }
+            }
+        }
+
+        if num_failed > 0 {
+            let msg = format!("Failed to evaluate {num_failed} of {} files.", fnames.len());
+            return Error::new(msg).err();
+        }
+
+        if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
+            self.loader.write_depfile(&output, depfile_path)?;
+        }
+
+        if concat {
+            // There is no single document that the concatenation comes from,
+            // so if formatting fails, point at the first one.
+            let span = results[0].0;
+            let mut elements = Vec::new();
+            for (_span, value) in results {
+                match value {
+                    Value::List(xs) => elements.extend(xs.iter().cloned()),
+                    _ => unreachable!("We checked that every result is a list."),
+                }
+            }
+            let value = Value::List(Rc::new(elements));
+            return self.print_value(eval_opts, style_opts, output, span, &value);
+        }
+
+        // Ndjson already ends every line with a newline, the other formats
+        // need a line break between the documents.
+        let needs_separator = eval_opts.format != OutputFormat::Ndjson;
+        let mut out_doc = Vec::with_capacity(results.len() * 2);
+        for (span, value) in &results {
+            if needs_separator && !out_doc.is_empty() {
+                out_doc.push(Doc::HardBreak);
+            }
+            out_doc.push(rcl::cmd_eval::format_value(
+                eval_opts.format,
+                eval_opts.rcl_int_base,
+                *span,
+                value,
+            )?);
+        }
+        let out_doc = match eval_opts.banner.as_ref() {
+            Some(banner) => Doc::lines(banner) + Doc::HardBreak + Doc::Concat(out_doc),
+            None => Doc::Concat(out_doc),
+        };
+        self.print_doc_target(output, style_opts, eval_opts.trailing_newline, out_doc)
+    }
+
+    /// Evaluate one input of `rcl evaluate` with multiple inputs.
+    fn eval_many_target(
+        &mut self,
+        eval_opts: &EvalOptions,
+        fname: &Target,
+        concat: bool,
+    ) -> Result<(Span, Value)> {
+        let doc = self.loader.load_cli_target(fname)?;
+        let mut tracer = self.get_tracer();
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        let val = self
+            .loader
+            .evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
+
+        let body_span = self.loader.get_span(doc);
+        self.check_schema(eval_opts, body_span, &val)?;
+
+        if concat && !matches!(val, Value::List(..)) {
+            return Err(rcl::error::concat_not_list_error(body_span, &val).into());
+        }
+
+        Ok((body_span, val))
+    }
+
+    /// Check the value against the type in the `--schema` file, if there is one.
+    ///
+    /// This reports every mismatch, not only the first one.
+    fn check_schema(
+        &mut self,
+        eval_opts: &EvalOptions,
+        value_span: Span,
+        value: &Value,
+    ) -> Result<()> {
+        let schema_path = match eval_opts.schema.as_ref() {
+            None => return Ok(()),
+            Some(path) => path,
+        };
+        let schema_doc = self
+            .loader
+            .load_cli_target(&Target::File(schema_path.clone()))?;
+        let schema = self.loader.get_type(schema_doc)?;
+
+        let mut errors = value.type_mismatches(value_span, &schema);
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(Box::new(errors.remove(0))),
+            n => {
+                let inputs = self.loader.as_inputs();
+                for err in errors {
+                    self.print_doc_stderr(err.report(&inputs));
+                }
+                Error::new(format!("Found {n} values that do not match the schema.")).err()
+            }
+        }
+    }
+
+    /// Evaluate one input of `rcl build --tree`, and write its output.
+    fn build_tree_target(
+        &mut self,
+        eval_opts: &EvalOptions,
+        style_opts: &StyleOptions,
+        build_mode: BuildMode,
+        input: &str,
+        output: &str,
+    ) -> Result<()> {
+        let mut tracer = self.get_tracer();
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+        let doc = self
+            .loader
+            .load_cli_target(&Target::File(input.to_string()))?;
+        let val = self
+            .loader
+            .evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
+        let body_span = self.loader.get_span(doc);
+
+        let output_target = match build_mode {
+            BuildMode::DryRun => OutputTarget::Stdout,
+            BuildMode::WriteFilesystem => {
+                let out_path = self.loader.resolve_cli_output_path(output);
+                if let Some(parent) = out_path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|err| {
+                        let parts = vec![
+                            "Failed to create output directory '".into(),
+                            Doc::path(parent),
+                            "': ".into(),
+                            err.to_string().into(),
+                        ];
+                        Error::new(Doc::Concat(parts))
+                    })?;
+                }
+                OutputTarget::File(output.to_string())
+            }
+        };
+        self.print_value(eval_opts, style_opts, output_target, body_span, &val)
+    }
+
+    /// Evaluate a document and print it as json, streaming a top-level list.
+    ///
+    /// The output is the same as for [`App::print_value`], but when the
+    /// evaluation fails halfway, part of the output has already been written.
+    fn main_eval_json_streaming(
+        &mut self,
+        eval_opts: &EvalOptions,
+        style_opts: &StyleOptions,
+        doc: DocId,
+    ) -> Result<()> {
+        let mut tracer = self.get_tracer();
+        let mut type_env = typecheck::prelude();
+        let mut value_env = runtime::prelude();
+
+        let cfg = pprint::Config {
+            width: style_opts.width,
+        };
+        let stdout = std::io::stdout();
+        let markup = self.opts.markup_for_fd(&stdout);
+        let mut out = stdout.lock();
+        let mut banner = eval_opts.banner.as_deref();
+        let mut print = |data: MarkupString| {
+            if let Some(banner) = banner.take() {
+                let banner_doc = Doc::lines(banner);
+                // Same as `print_string`, which we can't call while evaluating.
+                if banner_doc
+                    .println(&cfg)
+                    .write_bytes(markup, &mut out)
+                    .is_err()
+                {
+                    std::process::exit(1);
+                }
+            }
+            if data.write_bytes(markup, &mut out).is_err() {
+                std::process::exit(1);
+            }
+        };
+
+        let mut writer = ListWriter::new(&cfg);
+        let result = self.loader.evaluate_streaming(
+            &mut type_env,
+            &mut value_env,
+            doc,
+            &mut tracer,
+            &mut |span, v| writer.push(span, v, &mut print),
+        );
+        let val = match result {
+            Ok(val) => val,
+            Err(err) => {
+                // Finish the partial output, so the error starts on a new line.
+                if writer.is_printing() {
+                    let mut newline = MarkupString::new();
+                    newline.push("\n", Markup::None);
+                    print(newline);
+                }
+                return Err(err);
+            }
+        };
+
+        if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
+            self.loader
+                .write_depfile(&OutputTarget::Stdout, depfile_path)?;
+        }
+
+        let body_span = self.loader.get_span(doc);
+        match val {
+            None => writer.finish(body_span, eval_opts.trailing_newline, &mut print),
+            Some(val) => {
+                // Release stdout, `print_value` locks it again.
+                drop(out);
+                self.print_value(eval_opts, style_opts, OutputTarget::Stdout, body_span, &val)
+            }
+        }
     }
 
     fn print_fatal_error(&self, err: Error) -> ! {
@@ -140,7 +433,7 @@ impl App {
     }
 
     fn get_tracer(&self) -> StderrTracer {
-        StderrTracer::new(self.opts.markup)
+        StderrTracer::new(self.opts.markup_for_fd(&std::io::stderr()))
     }
 
     fn main_fmt(
@@ -158,7 +451,7 @@ impl App {
                 let cst = self.loader.get_cst(doc)?;
                 let data = self.loader.get_doc(doc).data;
                 let res = rcl::fmt_cst::format_expr(data, &cst);
-                return self.print_doc_target(output, style_opts, res);
+                return self.print_doc_target(output, style_opts, true, res);
             }
             FormatTarget::InPlace { fnames } => (true, fnames),
             FormatTarget::Check { mut fnames } => {
@@ -182,7 +475,9 @@ impl App {
             let fmt_doc = rcl::fmt_cst::format_expr(data, &cst);
             let res = fmt_doc.println(&cfg);
             let formatted = res.to_string_no_markup();
-            let did_change = data != &formatted[..];
+            // The loader normalizes line endings and removes any byte order
+            // mark, so if it did that, the file on disk is not formatted.
+            let did_change = data != &formatted[..] || self.loader.is_normalized(doc);
 
             if is_write_in_place {
                 let fname = match target {
@@ -238,6 +533,63 @@ impl App {
         let (opts, cmd) = cli::parse(std::env::args().collect())?;
         self.opts = opts;
 
+        if self.opts.watch {
+            self.main_watch(cmd)
+        } else {
+            self.main_cmd(cmd)
+        }
+    }
+
+    /// Run the command, and run it again whenever one of its input files changes.
+    ///
+    /// The input files include everything that the inputs import, so an edit to
+    /// an imported file also triggers a new run. Errors do not end the loop, we
+    /// print them, and try again after the next change.
+    #[cfg(feature = "watch")]
+    fn main_watch(&mut self, cmd: Cmd) -> Result<()> {
+        use std::io::IsTerminal;
+        use std::path::PathBuf;
+
+        let workdir = self.opts.workdir.clone();
+        let inputs: Vec<PathBuf> = cmd
+            .watch_targets()
+            .expect("CLI parser only allows --watch for commands that support it.")
+            .into_iter()
+            .filter_map(|target| match target {
+                Target::File(fname) => Some(fname.as_str()),
+                _ => None,
+            })
+            .map(|fname| match workdir.as_ref() {
+                Some(dir) => Path::new(dir).join(fname),
+                None => PathBuf::from(fname),
+            })
+            .collect();
+
+        rcl::watch::watch_loop(&rcl::watch::Config::default(), || {
+            // Clear the output of the previous run, including its errors, and
+            // start with a fresh loader, so we read all files from disk again.
+            if std::io::stdout().is_terminal() {
+                print!("\x1b[2J\x1b[H");
+            }
+            self.loader = Loader::new();
+            if let Err(err) = self.main_cmd(cmd.clone()) {
+                let inputs = self.loader.as_inputs();
+                self.print_doc_stderr(err.report(&inputs));
+            }
+            // Also watch the inputs themselves, in case they failed to load.
+            let mut paths = self.loader.loaded_paths();
+            paths.extend(inputs.iter().cloned());
+            Some(paths)
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "watch"))]
+    fn main_watch(&mut self, _cmd: Cmd) -> Result<()> {
+        Error::new("This build of RCL does not include support for --watch.").err()
+    }
+
+    fn main_cmd(&mut self, cmd: Cmd) -> Result<()> {
         match cmd {
             Cmd::Help { usage } => {
                 println!("{}", usage.trim());
@@ -256,9 +608,16 @@ impl App {
                     return Error::new("Generating depfiles is not supported for 'rcl build'.")
                         .err();
                 }
+                if eval_opts.schema.is_some() {
+                    return Error::new("Checking a schema is not supported for 'rcl build'.").err();
+                }
 
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
+                self.loader.initialize_filesystem(
+                    eval_opts.sandbox,
+                    self.opts.workdir.as_deref(),
+                    &eval_opts.search_paths(),
+                )?;
+                self.loader.set_strict(eval_opts.strict);
 
                 // TODO: We can make these members, then we can share a lot of code between commands!
                 let mut tracer = self.get_tracer();
@@ -276,31 +635,87 @@ impl App {
                 rcl::cmd_build::execute_build(&self.loader, build_mode, doc, full_span, val)
             }
 
+            Cmd::BuildTree {
+                eval_opts,
+                style_opts,
+                build_mode,
+                input_dir,
+                output_dir,
+            } => {
+                if eval_opts.output_depfile.is_some() {
+                    return Error::new("Generating depfiles is not supported for 'rcl build'.")
+                        .err();
+                }
+                if eval_opts.schema.is_some() {
+                    return Error::new("Checking a schema is not supported for 'rcl build'.").err();
+                }
+
+                self.loader.initialize_filesystem(
+                    eval_opts.sandbox,
+                    self.opts.workdir.as_deref(),
+                    &eval_opts.search_paths(),
+                )?;
+                self.loader.set_strict(eval_opts.strict);
+                self.main_build_tree(&eval_opts, &style_opts, build_mode, &input_dir, &output_dir)
+            }
+
             Cmd::Evaluate {
                 eval_opts,
                 style_opts,
                 fname,
                 output,
+                stream,
             } => {
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
+                self.loader.initialize_filesystem(
+                    eval_opts.sandbox,
+                    self.opts.workdir.as_deref(),
+                    &eval_opts.search_paths(),
+                )?;
+                self.loader.set_strict(eval_opts.strict);
+
+                let doc = self.loader.load_cli_target(&fname)?;
+
+                // With --stream, we write a top-level list as we evaluate it, so
+                // we don't need to hold all of it in memory. The command line
+                // parser ensures that this is json to stdout without schema.
+                if stream {
+                    return self.main_eval_json_streaming(&eval_opts, &style_opts, doc);
+                }
 
                 let mut tracer = self.get_tracer();
                 let mut type_env = typecheck::prelude();
                 let mut value_env = runtime::prelude();
-                let doc = self.loader.load_cli_target(&fname)?;
                 let val = self
                     .loader
                     .evaluate(&mut type_env, &mut value_env, doc, &mut tracer)?;
 
+                let body_span = self.loader.get_span(doc);
+                self.check_schema(&eval_opts, body_span, &val)?;
+
                 if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
                     self.loader.write_depfile(&output, depfile_path)?;
                 }
 
-                let body_span = self.loader.get_span(doc);
                 self.print_value(&eval_opts, &style_opts, output, body_span, &val)
             }
 
+            Cmd::EvaluateMany {
+                eval_opts,
+                style_opts,
+                fnames,
+                concat,
+                fail_fast,
+                output,
+            } => {
+                self.loader.initialize_filesystem(
+                    eval_opts.sandbox,
+                    self.opts.workdir.as_deref(),
+                    &eval_opts.search_paths(),
+                )?;
+                self.loader.set_strict(eval_opts.strict);
+                self.main_eval_many(&eval_opts, &style_opts, &fnames, concat, fail_fast, output)
+            }
+
             Cmd::Query {
                 eval_opts,
                 style_opts,
@@ -308,8 +723,12 @@ impl App {
                 query: expr,
                 output,
             } => {
-                self.loader
-                    .initialize_filesystem(eval_opts.sandbox, self.opts.workdir.as_deref())?;
+                self.loader.initialize_filesystem(
+                    eval_opts.sandbox,
+                    self.opts.workdir.as_deref(),
+                    &eval_opts.search_paths(),
+                )?;
+                self.loader.set_strict(eval_opts.strict);
 
                 let input = self.loader.load_cli_target(&fname)?;
                 let query = self.loader.load_string(expr);
@@ -331,11 +750,13 @@ impl App {
                     self.loader
                         .evaluate(&mut type_env, &mut value_env, query, &mut tracer)?;
 
+                let body_span = self.loader.get_span(query);
+                self.check_schema(&eval_opts, body_span, &val_result)?;
+
                 if let Some(depfile_path) = eval_opts.output_depfile.as_ref() {
                     self.loader.write_depfile(&output, depfile_path)?;
                 }
 
-                let body_span = self.loader.get_span(query);
                 self.print_value(&eval_opts, &style_opts, output, body_span, &val_result)
             }
 
@@ -347,24 +768,48 @@ impl App {
                 self.loader.initialize_filesystem(
                     SandboxMode::Unrestricted,
                     self.opts.workdir.as_deref(),
+                    &[],
                 )?;
                 self.main_fmt(output, &style_opts, target)
             }
 
-            Cmd::Highlight { fname } => {
+            Cmd::Highlight {
+                fname,
+                line_numbers,
+            } => {
                 self.loader.initialize_filesystem(
                     SandboxMode::Unrestricted,
                     self.opts.workdir.as_deref(),
+                    &[],
                 )?;
                 let doc = self.loader.load_cli_target(&fname)?;
-                let tokens = self.loader.get_tokens(doc)?;
+                // If the document fails to lex, we still highlight the part
+                // before the error, and report the error afterwards.
+                let (tokens, lex_result) = self.loader.get_tokens_partial(doc);
                 let data = self.loader.get_doc(doc).data;
                 let result = rcl::highlight::highlight(&tokens, data);
-                let mut out = std::io::stdout().lock();
-                self.print_string(MarkupMode::Ansi, result, &mut out);
-                Ok(())
+                let gutter = Gutter::for_input(data);
+                let result = match line_numbers {
+                    true => number_lines(result, &gutter),
+                    false => result,
+                };
+                let stdout = std::io::stdout();
+                let markup = self.opts.markup_for_fd(&stdout);
+                let mut out = stdout.lock();
+                self.print_string(markup, result, &mut out);
+                lex_result
             }
 
+            #[cfg(feature = "lsp")]
+            Cmd::Lsp => {
+                let stdin = std::io::stdin();
+                let stdout = std::io::stdout();
+                rcl::lsp::run(&mut stdin.lock(), &mut stdout.lock())
+                    .map_err(|err| Error::new(format!("Language server failed: {err}.")).into())
+            }
+            #[cfg(not(feature = "lsp"))]
+            Cmd::Lsp => Error::new("This build of RCL does not include the language server.").err(),
+
             Cmd::Version => {
                 println!("RCL version {}", env!("CARGO_PKG_VERSION"));
                 Ok(())
@@ -373,6 +818,68 @@ impl App {
     }
 }
 
+/// Write a file by writing to a temporary file first, and then renaming it.
+///
+/// The temporary file is in the same directory as the target, so the rename
+/// replaces the target atomically. If writing fails halfway, any existing file
+/// at the target path is left untouched, rather than being truncated. When the
+/// target exists, the new file gets its permissions, so that e.g. an executable
+/// script stays executable.
+///
+/// Only a regular file can be replaced this way. When the target is a symlink
+/// or a special file such as `/dev/stdout`, we write to it in place instead,
+/// so the write goes where the target points.
+fn write_file_atomic(
+    out_path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let permissions = match std::fs::symlink_metadata(out_path) {
+        Ok(meta) if meta.is_file() => Some(meta.permissions()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        _ => return write_file_in_place(out_path, write),
+    };
+
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(out_path.file_name().unwrap_or_default());
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = out_path.with_file_name(tmp_name);
+
+    let write_tmp = || {
+        let f = std::fs::File::create(&tmp_path)?;
+        let mut w = std::io::BufWriter::new(f);
+        write(&mut w)?;
+        // Flush to force errors to materialize. Without this, flush would
+        // happen on drop, which has no opportunity to report errors.
+        w.flush()?;
+        if let Some(permissions) = permissions {
+            w.get_ref().set_permissions(permissions)?;
+        }
+        // The data must be on disk before the rename, otherwise a crash can
+        // leave the target replaced by an empty file.
+        w.get_ref().sync_all()?;
+        std::fs::rename(&tmp_path, out_path)
+    };
+
+    let result = write_tmp();
+    if result.is_err() {
+        // Clean up the temporary file, but report the original error; if the
+        // file was never created, then failing to delete it is expected.
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Write a file by truncating it and writing to it directly.
+fn write_file_in_place(
+    out_path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let f = std::fs::File::create(out_path)?;
+    let mut w = std::io::BufWriter::new(f);
+    write(&mut w)?;
+    w.flush()
+}
+
 fn main() {
     let mut app = App {
         opts: GlobalOptions::default(),
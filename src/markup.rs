@@ -28,6 +28,12 @@ pub enum Markup {
     /// to clarify visually where the boundaries of a quotation are.
     Highlight,
 
+    /// The line numbers in the gutter of highlighted output, styled dimmed.
+    LineNumber,
+
+    /// A heading in documentation, styled in bold. Should span a full line.
+    Heading,
+
     // These are meant for syntax highlighting.
     Builtin,
     Comment,
@@ -39,6 +45,30 @@ pub enum Markup {
     Type,
 }
 
+/// How many colors the terminal supports, for ANSI output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorLevel {
+    /// The basic 16 colors, which the terminal can map to its own palette.
+    Ansi16,
+
+    /// The 256-color palette.
+    Ansi256,
+
+    /// 24-bit RGB colors.
+    TrueColor,
+}
+
+/// The color theme to use for ANSI output.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Theme {
+    /// Colors for terminals with a dark background.
+    #[default]
+    Dark,
+
+    /// Colors for terminals with a light background.
+    Light,
+}
+
 /// How to treat color and other markup hints.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MarkupMode {
@@ -46,7 +76,7 @@ pub enum MarkupMode {
     None,
 
     /// Output markup as ANSI escape sequences.
-    Ansi,
+    Ansi(ColorLevel, Theme),
 
     /// Output as html spans in the same style as Pandoc with Pygments style.
     HtmlPandoc,
@@ -69,26 +99,63 @@ fn should_color<T: IsTerminal>(fd: &T) -> bool {
     // coverage:on
 }
 
+impl ColorLevel {
+    /// Detect the color level from the `COLORTERM` and `TERM` environment variables.
+    pub fn from_env(colorterm: Option<&str>, term: Option<&str>) -> ColorLevel {
+        match (colorterm, term) {
+            (Some("truecolor" | "24bit"), _) => ColorLevel::TrueColor,
+            (_, Some(term)) if term.contains("256color") => ColorLevel::Ansi256,
+            _ => ColorLevel::Ansi16,
+        }
+    }
+}
+
 impl MarkupMode {
     /// Get the default markup configuration for a file descriptor.
-    pub fn default_for_fd<T: IsTerminal>(fd: &T) -> Self {
+    pub fn default_for_fd<T: IsTerminal>(fd: &T, theme: Theme) -> Self {
         if should_color(fd) {
-            MarkupMode::Ansi
+            // coverage:off -- Tests never run with a terminal, so this is never covered.
+            let colorterm = std::env::var("COLORTERM").ok();
+            let term = std::env::var("TERM").ok();
+            let level = ColorLevel::from_env(colorterm.as_deref(), term.as_deref());
+            MarkupMode::Ansi(level, theme)
+            // coverage:on
         } else {
             MarkupMode::None
         }
     }
+
+    /// Replace the theme, if this is an ANSI mode.
+    pub fn with_theme(self, theme: Theme) -> Self {
+        match self {
+            MarkupMode::Ansi(level, _) => MarkupMode::Ansi(level, theme),
+            other => other,
+        }
+    }
 }
 
 /// Return the ANSI escape code to switch to style `markup`.
-pub fn switch_ansi(markup: Markup) -> &'static str {
+pub fn switch_ansi(markup: Markup, level: ColorLevel, theme: Theme) -> &'static str {
+    match (level, theme) {
+        (ColorLevel::Ansi16, Theme::Dark) => switch_ansi_16_dark(markup),
+        (ColorLevel::Ansi16, Theme::Light) => switch_ansi_16_light(markup),
+        (ColorLevel::Ansi256, Theme::Dark) => switch_ansi_256_dark(markup),
+        (ColorLevel::Ansi256, Theme::Light) => switch_ansi_256_light(markup),
+        (ColorLevel::TrueColor, Theme::Dark) => switch_ansi_rgb_dark(markup),
+        (ColorLevel::TrueColor, Theme::Light) => switch_ansi_rgb_light(markup),
+    }
+}
+
+fn switch_ansi_16_dark(markup: Markup) -> &'static str {
     let reset = "\x1b[0m";
+    let bold = "\x1b[1m";
     let bold_blue = "\x1b[34;1m";
     let bold_green = "\x1b[32;1m";
     let bold_red = "\x1b[31;1m";
     let bold_yellow = "\x1b[33;1m";
     let blue = "\x1b[34m";
     let cyan = "\x1b[36m";
+    let grey = "\x1b[90m";
     let magenta = "\x1b[35m";
     let red = "\x1b[31m";
     let white = "\x1b[37m";
@@ -108,6 +175,113 @@ pub fn switch_ansi(markup: Markup) -> &'static str {
         Markup::String => red,
         Markup::Escape => yellow,
         Markup::Type => magenta,
+        Markup::LineNumber => grey,
+        Markup::Heading => bold,
+    }
+}
+
+fn switch_ansi_16_light(markup: Markup) -> &'static str {
+    // On a light background, white and yellow are hard to read, so we use
+    // black and the bright black (grey) instead.
+    match markup {
+        Markup::None => "\x1b[0m",
+        Markup::Error => "\x1b[0;31;1m",
+        Markup::Warning => "\x1b[0;33;1m",
+        Markup::Trace => "\x1b[0;34;1m",
+        Markup::Highlight => "\x1b[0;30;1m",
+        Markup::Builtin => "\x1b[0;31m",
+        Markup::Comment => "\x1b[0;90m",
+        Markup::Field => "\x1b[0;34m",
+        Markup::Keyword => "\x1b[0;32;1m",
+        Markup::Number => "\x1b[0;36m",
+        Markup::String => "\x1b[0;31m",
+        Markup::Escape => "\x1b[0;35m",
+        Markup::Type => "\x1b[0;35m",
+        Markup::LineNumber => "\x1b[0;90m",
+        Markup::Heading => "\x1b[0;1m",
+    }
+}
+
+// The 256-color and 24-bit themes start every sequence with a reset, so that
+// boldness does not leak from one style into the next.
+
+fn switch_ansi_256_dark(markup: Markup) -> &'static str {
+    match markup {
+        Markup::None => "\x1b[0m",
+        Markup::Error => "\x1b[0;1;38;5;203m",
+        Markup::Warning => "\x1b[0;1;38;5;214m",
+        Markup::Trace => "\x1b[0;1;38;5;75m",
+        Markup::Highlight => "\x1b[0;38;5;255m",
+        Markup::Builtin => "\x1b[0;38;5;209m",
+        Markup::Comment => "\x1b[0;38;5;245m",
+        Markup::Field => "\x1b[0;38;5;111m",
+        Markup::Keyword => "\x1b[0;1;38;5;114m",
+        Markup::Number => "\x1b[0;38;5;80m",
+        Markup::String => "\x1b[0;38;5;216m",
+        Markup::Escape => "\x1b[0;38;5;221m",
+        Markup::Type => "\x1b[0;38;5;176m",
+        Markup::LineNumber => "\x1b[0;38;5;240m",
+        Markup::Heading => "\x1b[0;1m",
+    }
+}
+
+fn switch_ansi_256_light(markup: Markup) -> &'static str {
+    match markup {
+        Markup::None => "\x1b[0m",
+        Markup::Error => "\x1b[0;1;38;5;160m",
+        Markup::Warning => "\x1b[0;1;38;5;130m",
+        Markup::Trace => "\x1b[0;1;38;5;25m",
+        Markup::Highlight => "\x1b[0;38;5;235m",
+        Markup::Builtin => "\x1b[0;38;5;124m",
+        Markup::Comment => "\x1b[0;38;5;244m",
+        Markup::Field => "\x1b[0;38;5;25m",
+        Markup::Keyword => "\x1b[0;1;38;5;28m",
+        Markup::Number => "\x1b[0;38;5;30m",
+        Markup::String => "\x1b[0;38;5;130m",
+        Markup::Escape => "\x1b[0;38;5;136m",
+        Markup::Type => "\x1b[0;38;5;90m",
+        Markup::LineNumber => "\x1b[0;38;5;248m",
+        Markup::Heading => "\x1b[0;1m",
+    }
+}
+
+fn switch_ansi_rgb_dark(markup: Markup) -> &'static str {
+    match markup {
+        Markup::None => "\x1b[0m",
+        Markup::Error => "\x1b[0;1;38;2;255;123;114m",
+        Markup::Warning => "\x1b[0;1;38;2;227;179;65m",
+        Markup::Trace => "\x1b[0;1;38;2;121;192;255m",
+        Markup::Highlight => "\x1b[0;38;2;240;246;252m",
+        Markup::Builtin => "\x1b[0;38;2;255;123;114m",
+        Markup::Comment => "\x1b[0;38;2;139;148;158m",
+        Markup::Field => "\x1b[0;38;2;121;192;255m",
+        Markup::Keyword => "\x1b[0;1;38;2;126;231;135m",
+        Markup::Number => "\x1b[0;38;2;86;212;221m",
+        Markup::String => "\x1b[0;38;2;255;166;87m",
+        Markup::Escape => "\x1b[0;38;2;227;179;65m",
+        Markup::Type => "\x1b[0;38;2;210;168;255m",
+        Markup::LineNumber => "\x1b[0;38;2;110;118;129m",
+        Markup::Heading => "\x1b[0;1m",
+    }
+}
+
+fn switch_ansi_rgb_light(markup: Markup) -> &'static str {
+    match markup {
+        Markup::None => "\x1b[0m",
+        Markup::Error => "\x1b[0;1;38;2;207;34;46m",
+        Markup::Warning => "\x1b[0;1;38;2;154;103;0m",
+        Markup::Trace => "\x1b[0;1;38;2;5;80;174m",
+        Markup::Highlight => "\x1b[0;38;2;36;41;47m",
+        Markup::Builtin => "\x1b[0;38;2;207;34;46m",
+        Markup::Comment => "\x1b[0;38;2;110;119;129m",
+        Markup::Field => "\x1b[0;38;2;5;80;174m",
+        Markup::Keyword => "\x1b[0;1;38;2;17;99;41m",
+        Markup::Number => "\x1b[0;38;2;27;124;131m",
+        Markup::String => "\x1b[0;38;2;149;56;0m",
+        Markup::Escape => "\x1b[0;38;2;154;103;0m",
+        Markup::Type => "\x1b[0;38;2;130;80;223m",
+        Markup::LineNumber => "\x1b[0;38;2;140;149;159m",
+        Markup::Heading => "\x1b[0;1m",
     }
 }
 
@@ -121,6 +295,8 @@ pub fn html_class_pandoc(markup: Markup) -> &'static str {
         Markup::Warning => "warn",
         Markup::Trace => "trace",
         Markup::Highlight => "highlight",
+        Markup::LineNumber => "ln",
+        Markup::Heading => "heading",
 
         Markup::Builtin => "fu",
         Markup::Comment => "co",
@@ -133,6 +309,37 @@ pub fn html_class_pandoc(markup: Markup) -> &'static str {
     }
 }
 
+/// Escape the characters that have a special meaning in Markdown.
+fn escape_markdown(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if matches!(ch, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '#') {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+/// Write a Markdown code span, with enough backticks to enclose the contents.
+fn write_markdown_code_span(s: &str, out: &mut dyn Write) -> std::io::Result<()> {
+    let mut longest_run = 0;
+    let mut run = 0;
+    for ch in s.chars() {
+        run = if ch == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    let fence = "`".repeat(longest_run + 1);
+    // A code span strips one space on both sides, so when the contents start
+    // or end with a backtick, we can pad it to keep it apart from the fence.
+    let pad = if s.starts_with('`') || s.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    write!(out, "{fence}{pad}{s}{pad}{fence}")
+}
+
 /// A string pieced together from fragments that have markup.
 pub struct MarkupString<'a> {
     pub fragments: Vec<(&'a str, Markup)>,
@@ -181,6 +388,19 @@ impl<'a> MarkupString<'a> {
         }
     }
 
+    /// Remove a single newline at the end, if there is one.
+    pub fn trim_newline_end(&mut self) {
+        if let Some((fragment, _markup)) = self.fragments.last_mut() {
+            match fragment.strip_suffix('\n') {
+                Some("") => {
+                    self.fragments.pop();
+                }
+                Some(f_trimmed) => *fragment = f_trimmed,
+                None => {}
+            }
+        }
+    }
+
     /// Append the string to a regular `String`, discarding all markup.
     #[inline]
     pub fn write_string_no_markup(&self, out: &mut String) {
@@ -205,12 +425,17 @@ impl<'a> MarkupString<'a> {
     }
 
     /// Write the string to a writer, using ANSI escape codes for markup.
-    pub fn write_bytes_ansi(&self, out: &mut dyn Write) -> std::io::Result<()> {
+    pub fn write_bytes_ansi(
+        &self,
+        level: ColorLevel,
+        theme: Theme,
+        out: &mut dyn Write,
+    ) -> std::io::Result<()> {
         let mut markup = Markup::None;
 
         for (frag_str, frag_markup) in self.fragments.iter() {
             if markup != *frag_markup {
-                out.write_all(switch_ansi(*frag_markup).as_bytes())?;
+                out.write_all(switch_ansi(*frag_markup, level, theme).as_bytes())?;
                 markup = *frag_markup;
             }
             out.write_all(frag_str.as_bytes())?;
@@ -254,12 +479,159 @@ impl<'a> MarkupString<'a> {
         writeln!(out, "</code></pre>")
     }
 
+    /// Write the string to a writer as Markdown, for publishing documentation.
+    ///
+    /// Fragments with [`Markup::Heading`] become level-two headings, and
+    /// [`Markup::Highlight`] fragments become code spans, in the same role
+    /// that they play in error messages. Other markup is dropped. Plain text
+    /// is escaped, except for `-`, so that lists written as `- item` lines are
+    /// Markdown lists as-is.
+    pub fn write_bytes_markdown(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut i = 0;
+        while i < self.fragments.len() {
+            // Group consecutive fragments with the same markup, because a code
+            // span needs to know its full contents before we can open it.
+            let markup = self.fragments[i].1;
+            let mut run = String::new();
+            while let Some((frag_str, frag_markup)) = self.fragments.get(i) {
+                if *frag_markup != markup {
+                    break;
+                }
+                run.push_str(frag_str);
+                i += 1;
+            }
+
+            match markup {
+                Markup::Highlight => write_markdown_code_span(&run, out)?,
+                Markup::Heading => {
+                    out.write_all(b"## ")?;
+                    out.write_all(escape_markdown(&run).as_bytes())?;
+                }
+                _ => out.write_all(escape_markdown(&run).as_bytes())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the string as Markdown, see [`MarkupString::write_bytes_markdown`].
+    pub fn to_string_markdown(&self) -> String {
+        let mut out = Vec::new();
+        self.write_bytes_markdown(&mut out)
+            .expect("Writing to a Vec does not fail.");
+        String::from_utf8(out).expect("Fragments are valid UTF-8.")
+    }
+
     /// Write the string to a write with the given markup mode.
     pub fn write_bytes(&self, mode: MarkupMode, out: &mut dyn Write) -> std::io::Result<()> {
         match mode {
             MarkupMode::None => self.write_bytes_no_markup(out),
-            MarkupMode::Ansi => self.write_bytes_ansi(out),
+            MarkupMode::Ansi(level, theme) => self.write_bytes_ansi(level, theme, out),
             MarkupMode::HtmlPandoc => self.write_bytes_html_pandoc(out),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ColorLevel, Markup, MarkupMode, MarkupString, Theme};
+    use crate::highlight::highlight;
+    use crate::lexer::lex;
+    use crate::source::DocId;
+
+    fn highlight_as(input: &str, mode: MarkupMode) -> String {
+        let tokens = lex(DocId(0), input).unwrap();
+        let mut out = Vec::new();
+        highlight(&tokens, input)
+            .write_bytes(mode, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn color_level_from_env() {
+        let f = ColorLevel::from_env;
+        assert_eq!(f(None, None), ColorLevel::Ansi16);
+        assert_eq!(f(None, Some("xterm")), ColorLevel::Ansi16);
+        assert_eq!(f(None, Some("xterm-256color")), ColorLevel::Ansi256);
+        assert_eq!(f(Some("truecolor"), Some("xterm")), ColorLevel::TrueColor);
+        assert_eq!(f(Some("24bit"), None), ColorLevel::TrueColor);
+        assert_eq!(f(Some("yes"), Some("screen-256color")), ColorLevel::Ansi256);
+    }
+
+    #[test]
+    fn highlight_uses_theme_and_level() {
+        let input = "let n = 1; // Comment";
+        let ansi = |level, theme| highlight_as(input, MarkupMode::Ansi(level, theme));
+
+        assert_eq!(
+            ansi(ColorLevel::Ansi16, Theme::Dark),
+            "\x1b[32;1mlet\x1b[0m \x1b[34mn\x1b[0m = \x1b[36m1\x1b[0m; \x1b[37m// Comment",
+        );
+        assert_eq!(
+            ansi(ColorLevel::Ansi16, Theme::Light),
+            "\x1b[0;32;1mlet\x1b[0m \x1b[0;34mn\x1b[0m = \x1b[0;36m1\x1b[0m; \x1b[0;90m// Comment",
+        );
+        assert_eq!(
+            ansi(ColorLevel::Ansi256, Theme::Dark),
+            "\x1b[0;1;38;5;114mlet\x1b[0m \x1b[0;38;5;111mn\x1b[0m = \x1b[0;38;5;80m1\x1b[0m; \
+            \x1b[0;38;5;245m// Comment",
+        );
+        assert_eq!(
+            ansi(ColorLevel::Ansi256, Theme::Light),
+            "\x1b[0;1;38;5;28mlet\x1b[0m \x1b[0;38;5;25mn\x1b[0m = \x1b[0;38;5;30m1\x1b[0m; \
+            \x1b[0;38;5;244m// Comment",
+        );
+        assert_eq!(
+            ansi(ColorLevel::TrueColor, Theme::Dark),
+            "\x1b[0;1;38;2;126;231;135mlet\x1b[0m \x1b[0;38;2;121;192;255mn\x1b[0m = \x1b[0;38;2;86;212;221m1\x1b[0m; \
+            \x1b[0;38;2;139;148;158m// Comment",
+        );
+        assert_eq!(
+            ansi(ColorLevel::TrueColor, Theme::Light),
+            "\x1b[0;1;38;2;17;99;41mlet\x1b[0m \x1b[0;38;2;5;80;174mn\x1b[0m = \x1b[0;38;2;27;124;131m1\x1b[0m; \
+            \x1b[0;38;2;110;119;129m// Comment",
+        );
+        assert_eq!(
+            highlight_as(input, MarkupMode::None),
+            "let n = 1; // Comment"
+        );
+    }
+
+    /// A small documentation page, in the style of a stdlib entry.
+    fn example_doc() -> MarkupString<'static> {
+        let mut doc = MarkupString::new();
+        doc.push("std.range", Markup::Heading);
+        doc.push("\n\nReturn the integers from ", Markup::None);
+        doc.push("lower", Markup::Highlight);
+        doc.push(" up to *excluding* ", Markup::None);
+        doc.push("upper", Markup::Highlight);
+        doc.push(".\n\n- Both bounds must be ", Markup::None);
+        doc.push("Int", Markup::Highlight);
+        doc.push(".\n- The code span ", Markup::None);
+        doc.push("`", Markup::Highlight);
+        doc.push(" needs a longer fence.\n", Markup::None);
+        doc
+    }
+
+    #[test]
+    fn doc_renders_to_ansi_and_markdown() {
+        let doc = example_doc();
+
+        let mut ansi = Vec::new();
+        doc.write_bytes_ansi(ColorLevel::Ansi16, Theme::Dark, &mut ansi)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(ansi).unwrap(),
+            "\x1b[1mstd.range\x1b[0m\n\nReturn the integers from \x1b[37mlower\x1b[0m \
+            up to *excluding* \x1b[37mupper\x1b[0m.\n\n- Both bounds must be \x1b[37mInt\x1b[0m.\n\
+            - The code span \x1b[37m`\x1b[0m needs a longer fence.\n",
+        );
+
+        assert_eq!(
+            doc.to_string_markdown(),
+            "## std.range\n\nReturn the integers from `lower` up to \\*excluding\\* `upper`.\n\n\
+            - Both bounds must be `Int`.\n\
+            - The code span `` ` `` needs a longer fence.\n",
+        );
+    }
+}
@@ -7,15 +7,35 @@
 
 //! The parser converts a sequence of tokens into a Concrete Syntax Tree.
 
-use crate::cst::{BinOp, Chain, Expr, List, NonCode, Prefixed, Seq, Stmt, StringPart, Type, UnOp};
+use crate::cst::{
+    BinOp, Binding, Chain, Expr, List, MatchArm, NonCode, Pattern, Prefixed, Seq, Stmt, StringPart,
+    Type, UnOp,
+};
 use crate::error::{Error, IntoError, Result};
 use crate::lexer::{Lexeme, QuoteStyle, StringPrefix, Token};
 use crate::pprint::{concat, Doc};
 use crate::source::{DocId, Span};
 
+/// The maximum nesting depth of brackets and statements that [`parse`] accepts.
+///
+/// The parser is recursive descent, so without a limit, deeply nested input
+/// would overflow the native stack.
+pub const DEFAULT_MAX_DEPTH: u32 = 100;
+
 /// Parse an input document into a concrete syntax tree.
 pub fn parse(doc: DocId, input: &str, tokens: &[Lexeme]) -> Result<(Span, Expr)> {
+    parse_with_max_depth(doc, input, tokens, DEFAULT_MAX_DEPTH)
+}
+
+/// Parse an input document, with a custom limit on the nesting depth.
+pub fn parse_with_max_depth(
+    doc: DocId,
+    input: &str,
+    tokens: &[Lexeme],
+    max_depth: u32,
+) -> Result<(Span, Expr)> {
     let mut parser = Parser::new(doc, input, tokens);
+    parser.max_depth = max_depth;
 
     // Comments at the start of the document are allowed, but the document
     // should not start with blank lines, those we drop.
@@ -26,6 +46,21 @@ pub fn parse(doc: DocId, input: &str, tokens: &[Lexeme]) -> Result<(Span, Expr)>
     Ok((span, result))
 }
 
+/// Parse an input document that consists of a single type expression.
+pub fn parse_type(doc: DocId, input: &str, tokens: &[Lexeme]) -> Result<(Span, Type)> {
+    let mut parser = Parser::new(doc, input, tokens);
+
+    // Unlike in expressions, there is no place to keep comments in a type, but
+    // we do allow them around the type.
+    let _ = parser.parse_non_code();
+    let begin = parser.peek_span();
+    let result = parser.parse_type_expr()?;
+    let span = parser.span_from(begin);
+    let _ = parser.parse_non_code();
+    parser.parse_eof()?;
+    Ok((span, result))
+}
+
 fn to_unop(token: Token) -> Option<UnOp> {
     match token {
         Token::KwNot => Some(UnOp::Not),
@@ -38,10 +73,16 @@ fn to_binop(token: Token) -> Option<BinOp> {
     match token {
         Token::KwAnd => Some(BinOp::And),
         Token::KwOr => Some(BinOp::Or),
+        Token::KwXor => Some(BinOp::Xor),
         Token::Pipe => Some(BinOp::Union),
+        Token::Amp => Some(BinOp::BitAnd),
+        Token::Caret => Some(BinOp::BitXor),
+        Token::Lt2 => Some(BinOp::Shl),
+        Token::Gt2 => Some(BinOp::Shr),
         Token::Plus => Some(BinOp::Add),
         Token::Minus => Some(BinOp::Sub),
         Token::Star => Some(BinOp::Mul),
+        Token::Star2 => Some(BinOp::Pow),
         Token::Slash => Some(BinOp::Div),
         Token::Lt => Some(BinOp::Lt),
         Token::Gt => Some(BinOp::Gt),
@@ -49,6 +90,8 @@ fn to_binop(token: Token) -> Option<BinOp> {
         Token::GtEq => Some(BinOp::GtEq),
         Token::Eq2 => Some(BinOp::Eq),
         Token::Neq => Some(BinOp::Neq),
+        Token::DotDot => Some(BinOp::Range),
+        Token::DotDotEq => Some(BinOp::RangeInclusive),
         _ => None,
     }
 }
@@ -71,6 +114,9 @@ struct Parser<'a> {
     /// The depth of parsing expressions and sequences, to prevent stack
     /// overflow.
     depth: u32,
+
+    /// The depth at which we stop parsing, see [`DEFAULT_MAX_DEPTH`].
+    max_depth: u32,
 }
 
 impl<'a> Parser<'a> {
@@ -83,6 +129,7 @@ impl<'a> Parser<'a> {
             bracket_stack: Vec::new(),
             comment_anchor: Span::new(doc, 0, 0),
             depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
@@ -95,7 +142,12 @@ impl<'a> Parser<'a> {
     fn peek_past_non_code(&self) -> Token {
         self.tokens[self.cursor..]
             .iter()
-            .filter(|t| !matches!(t.0, Token::Blank | Token::LineComment | Token::Shebang))
+            .filter(|t| {
+                !matches!(
+                    t.0,
+                    Token::Blank | Token::LineComment | Token::BlockComment | Token::Shebang
+                )
+            })
             .map(|t| t.0)
             .next()
             .unwrap_or(Token::Eof)
@@ -142,9 +194,23 @@ impl<'a> Parser<'a> {
     fn increase_depth(&mut self) -> Result<()> {
         self.depth += 1;
 
-        if self.depth >= 100 {
-            return self
-                .error("Parser recursion limit reached, please reduce nesting.")
+        if self.depth > self.max_depth {
+            // Point at the bracket that opens the nesting level that is one
+            // too many. When we are not at a bracket, that is the innermost
+            // bracket that is still open.
+            let at = match self.peek() {
+                Token::LBrace | Token::LParen | Token::LBracket => self.peek_span(),
+                _ => match self.bracket_stack.last() {
+                    Some((_token, span)) => *span,
+                    None => self.peek_span(),
+                },
+            };
+            return at
+                .error(concat! {
+                    "Maximum nesting depth of "
+                    self.max_depth.to_string()
+                    " exceeded, please reduce nesting."
+                })
                 .err();
         }
 
@@ -161,7 +227,7 @@ impl<'a> Parser<'a> {
         let end = self.tokens[..self.cursor]
             .iter()
             .rev()
-            .filter(|t| !matches!(t.0, Token::Blank | Token::LineComment))
+            .filter(|t| !matches!(t.0, Token::Blank | Token::LineComment | Token::BlockComment))
             .map(|t| t.1.end())
             .next()
             .expect("If we pushed a start, we should find at least that.");
@@ -233,6 +299,7 @@ impl<'a> Parser<'a> {
         loop {
             match self.peek() {
                 Token::LineComment => result.push(NonCode::LineComment(self.consume())),
+                Token::BlockComment => result.push(NonCode::BlockComment(self.consume())),
                 Token::Shebang => result.push(NonCode::Shebang(self.consume())),
                 Token::Blank => result.push(NonCode::Blank(self.consume())),
                 _ => {
@@ -262,7 +329,7 @@ impl<'a> Parser<'a> {
                 Token::Blank => {
                     self.consume();
                 }
-                Token::LineComment => {
+                Token::LineComment | Token::BlockComment => {
                     return self
                         .error("A comment is not allowed here.")
                         .with_note(
@@ -373,6 +440,7 @@ impl<'a> Parser<'a> {
     fn parse_expr_no_stmt(&mut self) -> Result<Expr> {
         match self.peek() {
             Token::KwIf => self.parse_expr_if(),
+            Token::KwMatch => self.parse_expr_match(),
             _ => Ok(self.parse_expr_op()?.1),
         }
     }
@@ -424,6 +492,142 @@ impl<'a> Parser<'a> {
         Ok(result)
     }
 
+    fn parse_expr_match(&mut self) -> Result<Expr> {
+        // Consume the `match` keyword.
+        let match_span = self.consume();
+
+        // As for the condition of an `if`, we do not allow non-code, if, or
+        // statements in the scrutinee. Parens are still possible.
+        self.skip_non_code()?;
+        let (scrutinee_span, scrutinee) = self.parse_expr_op()?;
+
+        self.skip_non_code()?;
+        if self.peek() != Token::LBrace {
+            return self
+                .error("Expected '{' here.")
+                .with_note(match_span, "To open the arms of this 'match'.")
+                .err();
+        }
+        self.push_bracket()?;
+        let arms = self.parse_match_arms()?;
+        let close = self.pop_bracket()?;
+
+        if arms.elements.is_empty() {
+            return close
+                .error("Expected at least one arm in this match expression.")
+                .with_note(match_span, "Match started here.")
+                .with_help("The wildcard pattern '_' matches any value, as in '_ => null'.")
+                .err();
+        }
+
+        let result = Expr::Match {
+            match_span,
+            scrutinee_span,
+            scrutinee: Box::new(scrutinee),
+            arms,
+        };
+        Ok(result)
+    }
+
+    /// Parse the arms of a match expression, up to the closing brace.
+    fn parse_match_arms(&mut self) -> Result<List<Prefixed<MatchArm>>> {
+        let mut result = Vec::new();
+        let mut trailing_comma = false;
+
+        loop {
+            let prefix = self.parse_non_code();
+            if self.peek() == Token::RBrace {
+                let final_result = List {
+                    elements: result.into_boxed_slice(),
+                    suffix: prefix,
+                    trailing_comma,
+                };
+                return Ok(final_result);
+            }
+
+            let pattern = self.parse_pattern()?;
+            self.skip_non_code()?;
+            self.parse_token(Token::FatArrow, "Expected '=>' after the pattern.")?;
+            let (body_span, body) = self.parse_expr()?;
+            let arm = MatchArm {
+                pattern,
+                body_span,
+                body,
+            };
+            result.push(Prefixed { prefix, inner: arm });
+            trailing_comma = false;
+
+            self.skip_non_code()?;
+            match self.peek() {
+                Token::RBrace => continue,
+                Token::Comma => {
+                    self.consume();
+                    trailing_comma = true;
+                    continue;
+                }
+                _ => {
+                    // If we don't find a separator, nor the end of the arms,
+                    // that's an error. We can report an unmatched bracket
+                    // as the problem, because it is.
+                    self.pop_bracket()?;
+                    unreachable!("pop_bracket should have failed.");
+                }
+            }
+        }
+    }
+
+    /// Parse the pattern of a match arm.
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        let begin = self.peek_span();
+        match self.peek() {
+            Token::Ident if begin.resolve(self.input) == "_" => {
+                Ok(Pattern::Wildcard(self.consume()))
+            }
+            Token::KwNull
+            | Token::KwTrue
+            | Token::KwFalse
+            | Token::NumHexadecimal
+            | Token::NumBinary
+            | Token::NumDecimal
+            | Token::QuoteOpen(..) => {
+                let value = self.parse_expr_term()?;
+                let result = Pattern::Literal {
+                    span: self.span_from(begin),
+                    value: Box::new(value),
+                };
+                Ok(result)
+            }
+            Token::Minus
+                if matches!(
+                    self.peek_n(1),
+                    Token::NumHexadecimal | Token::NumBinary | Token::NumDecimal
+                ) =>
+            {
+                let op_span = self.consume();
+                let body_span = self.peek_span();
+                let body = self.parse_expr_term()?;
+                let value = Expr::UnOp {
+                    op_span,
+                    op: UnOp::Neg,
+                    body_span,
+                    body: Box::new(body),
+                };
+                let result = Pattern::Literal {
+                    span: self.span_from(begin),
+                    value: Box::new(value),
+                };
+                Ok(result)
+            }
+            _ => self
+                .error("Expected a pattern here.")
+                .with_help(
+                    "A pattern is a literal such as '42' or '\"text\"', \
+                    or the wildcard '_' that matches any value.",
+                )
+                .err(),
+        }
+    }
+
     fn parse_expr_import(&mut self) -> Result<(Span, Expr)> {
         // Consume the `import` keyword.
         let import_span = self.consume();
@@ -500,7 +704,7 @@ impl<'a> Parser<'a> {
         let let_ = self.consume();
 
         self.skip_non_code()?;
-        let ident = self.parse_ident()?;
+        let binding = self.parse_binding()?;
 
         // Parse the optional type signature, and then the '='.
         self.skip_non_code()?;
@@ -552,7 +756,7 @@ impl<'a> Parser<'a> {
         )?;
 
         let result = Stmt::Let {
-            ident,
+            binding,
             type_,
             value_span,
             value: Box::new(value),
@@ -561,6 +765,39 @@ impl<'a> Parser<'a> {
         Ok(result)
     }
 
+    /// Parse the left-hand side of a let-binding or a loop variable.
+    fn parse_binding(&mut self) -> Result<Binding> {
+        if self.peek() != Token::LBracket {
+            return self.parse_ident().map(Binding::Ident);
+        }
+
+        let begin = self.peek_span();
+        self.push_bracket()?;
+        let mut elements = Vec::new();
+        // As in list literals, a trailing comma is allowed.
+        loop {
+            self.skip_non_code()?;
+            if self.peek() == Token::RBracket {
+                break;
+            }
+            elements.push(self.parse_binding()?);
+            self.skip_non_code()?;
+            match self.peek() {
+                Token::Comma => {
+                    self.consume();
+                }
+                _ => break,
+            }
+        }
+        self.pop_bracket()?;
+
+        let result = Binding::List {
+            span: self.span_from(begin),
+            elements: elements.into_boxed_slice(),
+        };
+        Ok(result)
+    }
+
     fn parse_stmt_trace(&mut self) -> Result<Stmt> {
         // Consume the `trace` keyword.
         let trace_span = self.consume();
@@ -639,6 +876,7 @@ impl<'a> Parser<'a> {
         for i in offset.. {
             match self.peek_n(i) {
                 Token::LineComment => continue,
+                Token::BlockComment => continue,
                 Token::Blank => continue,
                 Token::FatArrow => return true,
                 _ => return false,
@@ -701,42 +939,66 @@ impl<'a> Parser<'a> {
             return self.parse_expr_import();
         }
 
-        let (mut lhs_span, mut result) = self.parse_expr_not_op()?;
+        let mut operands = vec![self.parse_expr_not_op()?];
+        let mut op_spans = Vec::new();
 
         // We might have binary operators following. If we find one, then
         // all the other ones must be of the same type, to avoid unclear
         // situations like whether "a and b or c" means "(a and b) or c"
         // or "a and (b or c)".
         let mut allowed_op = None;
-        let mut allowed_span = None;
         loop {
             self.skip_non_code()?;
             match to_binop(self.peek()) {
                 Some(op) if allowed_op.is_none() || allowed_op == Some(op) => {
-                    let span = self.consume();
+                    op_spans.push(self.consume());
                     self.skip_non_code()?;
-                    let (rhs_span, rhs) = self.parse_expr_not_op()?;
-                    allowed_span = Some(span);
+                    operands.push(self.parse_expr_not_op()?);
                     allowed_op = Some(op);
-                    result = Expr::BinOp {
-                        op,
-                        op_span: span,
-                        lhs_span,
-                        lhs: Box::new(result),
-                        rhs_span,
-                        rhs: Box::new(rhs),
-                    };
-                    lhs_span = lhs_span.union(rhs_span);
                 }
                 Some(_op) => {
                     return self.error(
                         "Parentheses are needed to clarify the precedence of this operator.",
                     ).with_note(
-                        allowed_span.expect("If we are here, allowed_span must be set."),
+                        *op_spans.last().expect("If we are here, we saw an operator before."),
                         "Without parentheses, it is not clear whether this operator should take precedence.",
                     ).err();
                 }
-                _ => return Ok((lhs_span, result)),
+                _ => break,
+            }
+        }
+
+        let make_binop =
+            |op, op_span, (lhs_span, lhs): (Span, Expr), (rhs_span, rhs): (Span, Expr)| {
+                let expr = Expr::BinOp {
+                    op,
+                    op_span,
+                    lhs_span,
+                    lhs: Box::new(lhs),
+                    rhs_span,
+                    rhs: Box::new(rhs),
+                };
+                (lhs_span.union(rhs_span), expr)
+            };
+
+        match allowed_op {
+            None => Ok(operands.pop().expect("We parsed one operand.")),
+            // Exponentiation is right-associative, `a ** b ** c` means `a ** (b ** c)`.
+            Some(op @ BinOp::Pow) => {
+                let mut result = operands.pop().expect("We parsed one operand.");
+                for (lhs, op_span) in operands.into_iter().zip(op_spans).rev() {
+                    result = make_binop(op, op_span, lhs, result);
+                }
+                Ok(result)
+            }
+            // All other operators are left-associative.
+            Some(op) => {
+                let mut operands = operands.into_iter();
+                let mut result = operands.next().expect("We parsed one operand.");
+                for (rhs, op_span) in operands.zip(op_spans) {
+                    result = make_binop(op, op_span, result, rhs);
+                }
+                Ok(result)
             }
         }
     }
@@ -805,13 +1067,36 @@ impl<'a> Parser<'a> {
                 }
                 Token::LBracket => {
                     let open = self.push_bracket()?;
-                    let (index_span, index) = self.parse_expr()?;
-                    let close = self.pop_bracket()?;
-                    let chain_expr = Chain::Index {
-                        open,
-                        close,
-                        index_span,
-                        index: Box::new(index),
+                    let start = match self.peek() {
+                        Token::Colon => None,
+                        _ => Some(self.parse_expr()?),
+                    };
+                    let chain_expr = match (self.peek(), start) {
+                        (Token::Colon, start) => {
+                            self.consume();
+                            self.skip_non_code()?;
+                            let end = match self.peek() {
+                                Token::RBracket => None,
+                                _ => Some(self.parse_expr()?),
+                            };
+                            let close = self.pop_bracket()?;
+                            Chain::Slice {
+                                open,
+                                close,
+                                start: start.map(|(span, expr)| (span, Box::new(expr))),
+                                end: end.map(|(span, expr)| (span, Box::new(expr))),
+                            }
+                        }
+                        (_, Some((index_span, index))) => {
+                            let close = self.pop_bracket()?;
+                            Chain::Index {
+                                open,
+                                close,
+                                index_span,
+                                index: Box::new(index),
+                            }
+                        }
+                        (_, None) => unreachable!("Without a start, we are at a ':'."),
                     };
                     chain.push((inner_span, chain_expr));
                 }
@@ -842,6 +1127,12 @@ impl<'a> Parser<'a> {
 
     fn parse_expr_term(&mut self) -> Result<Expr> {
         match self.peek() {
+            Token::LBrace if self.peek_n(1) == Token::Colon && self.peek_n(2) == Token::RBrace => {
+                let open = self.push_bracket()?;
+                self.consume();
+                let close = self.pop_bracket()?;
+                Ok(Expr::EmptyDictLit { open, close })
+            }
             Token::LBrace => {
                 let open = self.push_bracket()?;
                 let elements = self.parse_seqs()?;
@@ -888,7 +1179,7 @@ impl<'a> Parser<'a> {
             // Some tokens are valid starts of an expression, but just not at
             // the term level. For those, we can recommend the user to wrap
             // everything in parens, because then it would be allowed.
-            Token::KwLet | Token::KwAssert | Token::KwTrace | Token::KwIf => self
+            Token::KwLet | Token::KwAssert | Token::KwTrace | Token::KwIf | Token::KwMatch => self
                 .error("Expected a term here.")
                 .with_help("If this should be an expression, try wrapping it in parentheses.")
                 .err(),
@@ -1238,11 +1529,14 @@ impl<'a> Parser<'a> {
         let _for = self.consume();
 
         // Parse the loop variables. Here a trailing comma is not allowed.
-        let mut idents = Vec::new();
+        let mut bindings = Vec::new();
         loop {
             self.skip_non_code()?;
-            let ident = self.parse_token(Token::Ident, "Expected identifier here.")?;
-            idents.push(ident);
+            let binding = match self.peek() {
+                Token::LBracket => self.parse_binding()?,
+                _ => Binding::Ident(self.parse_token(Token::Ident, "Expected identifier here.")?),
+            };
+            bindings.push(binding);
 
             self.skip_non_code()?;
             match self.peek() {
@@ -1266,7 +1560,7 @@ impl<'a> Parser<'a> {
         let (_body_span, body) = self.parse_prefixed_seq()?;
 
         let result = Seq::For {
-            idents: idents.into_boxed_slice(),
+            bindings: bindings.into_boxed_slice(),
             collection_span,
             collection: Box::new(collection),
             body: Box::new(body),
@@ -1305,7 +1599,30 @@ impl<'a> Parser<'a> {
             return self.parse_type_function();
         }
 
-        // Otherwise, we definitely start with a term.
+        // Otherwise, we have one or more terms, separated by `|`.
+        let begin = self.peek_span();
+        let first = self.parse_type_apply()?;
+        self.skip_non_code()?;
+        if self.peek() != Token::Pipe {
+            return Ok(first);
+        }
+
+        let mut members = vec![first];
+        while self.peek() == Token::Pipe {
+            self.consume();
+            self.skip_non_code()?;
+            members.push(self.parse_type_apply()?);
+            self.skip_non_code()?;
+        }
+        let union = Type::Union {
+            span: self.span_from(begin),
+            members: members.into_boxed_slice(),
+        };
+        Ok(union)
+    }
+
+    /// Parse a type term, optionally followed by type arguments.
+    fn parse_type_apply(&mut self) -> Result<Type> {
         let begin = self.peek_span();
         let term = self.parse_type_term()?;
 
@@ -1411,3 +1728,46 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_with_max_depth;
+    use crate::lexer::lex;
+    use crate::pprint::Config;
+    use crate::source::{DocId, Span};
+
+    fn parse_nested(input: &str, max_depth: u32) -> Result<(), (Span, String)> {
+        let doc = DocId(0);
+        let tokens = lex(doc, input).expect("Test input should lex.");
+        match parse_with_max_depth(doc, input, &tokens, max_depth) {
+            Ok(..) => Ok(()),
+            Err(err) => {
+                let cfg = Config { width: 80 };
+                let message = err.message.println(&cfg).to_string_no_markup();
+                Err((err.origin.expect("Parse errors have a span."), message))
+            }
+        }
+    }
+
+    #[test]
+    fn parse_respects_max_depth() {
+        // The expression inside parens counts towards the depth too, when it
+        // exceeds the limit, the error points at the innermost open bracket.
+        assert_eq!(
+            parse_nested("[(1)]", 3),
+            Err((
+                Span::new(DocId(0), 1, 2),
+                "Maximum nesting depth of 3 exceeded, please reduce nesting.\n".to_string()
+            )),
+        );
+        // Depth 3 is allowed with a maximum of 3, only depth 4 exceeds it.
+        assert_eq!(parse_nested("[[1]]", 3), Ok(()));
+        assert_eq!(
+            parse_nested("[[[1]]]", 3),
+            Err((
+                Span::new(DocId(0), 2, 3),
+                "Maximum nesting depth of 3 exceeded, please reduce nesting.\n".to_string()
+            )),
+        );
+    }
+}
@@ -218,6 +218,22 @@ impl<'a> Doc<'a> {
         }
     }
 
+    /// Construct a new document fragment from an owned string that may contain newlines.
+    ///
+    /// Like [`Doc::lines`], the line breaks are converted into hard breaks.
+    pub fn string_lines(value: String) -> Doc<'a> {
+        let mut result = Vec::new();
+        for (i, line) in value.split('\n').enumerate() {
+            if i > 0 {
+                result.push(Doc::HardBreak);
+            }
+            if !line.is_empty() {
+                result.push(Doc::string(line.to_string()));
+            }
+        }
+        Doc::Concat(result)
+    }
+
     /// Construct a highlighted document fragment for a file path.
     ///
     /// This is expected to be used only in error messages, therefore it's okay
@@ -384,6 +400,18 @@ impl<'a> Doc<'a> {
         }
     }
 
+    /// Pretty-print the document, without a newline at the end.
+    ///
+    /// This is useful for printing a document in pieces, for streaming output.
+    pub fn print<'s>(&'s self, config: &'s Config) -> MarkupString<'a>
+    where
+        's: 'a,
+    {
+        let mut printer: Printer<'a> = Printer::new(config);
+        self.print_to(&mut printer, Mode::Tall);
+        printer.into_inner()
+    }
+
     /// Pretty-print the document. Ensure the document ends in a newline.
     pub fn println<'s>(&'s self, config: &'s Config) -> MarkupString<'a>
     where
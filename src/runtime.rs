@@ -12,9 +12,9 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 
 use crate::ast::{CallArg, Expr};
-use crate::error::{IntoError, PathElement, Result};
+use crate::error::{Error, IntoError, PathElement, Result};
 use crate::eval::Evaluator;
-use crate::fmt_rcl::format_rcl;
+use crate::fmt_rcl::format_rcl_short;
 use crate::fmt_type::format_type;
 use crate::pprint::{concat, indent, Doc};
 use crate::source::Span;
@@ -55,7 +55,10 @@ pub struct MethodCall<'a> {
 }
 
 /// A built-in function.
-#[derive(Eq, Ord, PartialEq, PartialOrd)]
+///
+/// Builtins are identified by their name, which is unique. Equality and
+/// ordering are defined on the name, so they do not depend on the addresses
+/// of the implementing functions.
 pub struct BuiltinFunction {
     pub name: &'static str,
     pub type_: fn() -> types::Function,
@@ -63,7 +66,9 @@ pub struct BuiltinFunction {
 }
 
 /// A built-in method.
-#[derive(Eq, Ord, PartialEq, PartialOrd)]
+///
+/// Like [`BuiltinFunction`], methods are identified by their name, which
+/// includes the type they belong to, e.g. `String.len`.
 pub struct BuiltinMethod {
     pub name: &'static str,
     pub type_: fn() -> types::Function,
@@ -83,6 +88,50 @@ pub struct MethodInstance {
     pub receiver: Value,
 }
 
+impl PartialEq for BuiltinFunction {
+    fn eq(&self, other: &BuiltinFunction) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for BuiltinFunction {}
+
+impl PartialOrd for BuiltinFunction {
+    // coverage:off -- All callers use `Ord`, not `PartialOrd`.
+    fn partial_cmp(&self, other: &BuiltinFunction) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+    // coverage:on
+}
+
+impl Ord for BuiltinFunction {
+    fn cmp(&self, other: &BuiltinFunction) -> Ordering {
+        self.name.cmp(other.name)
+    }
+}
+
+impl PartialEq for BuiltinMethod {
+    fn eq(&self, other: &BuiltinMethod) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for BuiltinMethod {}
+
+impl PartialOrd for BuiltinMethod {
+    // coverage:off -- All callers use `Ord`, not `PartialOrd`.
+    fn partial_cmp(&self, other: &BuiltinMethod) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+    // coverage:on
+}
+
+impl Ord for BuiltinMethod {
+    fn cmp(&self, other: &BuiltinMethod) -> Ordering {
+        self.name.cmp(other.name)
+    }
+}
+
 impl std::fmt::Debug for BuiltinFunction {
     // coverage:off -- Debug is needed for assert, not expected to be called.
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -174,6 +223,24 @@ pub enum Value {
 }
 
 impl Value {
+    /// Return the name of the type of this value, excluding generic arguments.
+    ///
+    /// The names match [`Type::short_name`](crate::types::Type::short_name).
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "Null",
+            Value::Bool(..) => "Bool",
+            Value::Int(..) => "Int",
+            Value::String(..) => "String",
+            Value::List(..) => "List",
+            Value::Set(..) => "Set",
+            Value::Dict(..) => "Dict",
+            Value::Function(..) => "Function",
+            Value::BuiltinFunction(..) => "Function",
+            Value::BuiltinMethod(..) => "Function",
+        }
+    }
+
     /// Extract the dict if it is one, panic otherwise.
     #[inline]
     pub fn expect_dict(&self) -> &BTreeMap<Value, Value> {
@@ -219,6 +286,49 @@ impl Value {
         }
     }
 
+    /// Dynamically check the value against the type, and report all mismatches.
+    ///
+    /// Unlike [`is_instance_of`], which stops at the first mismatch, this
+    /// descends into every element of lists, sets, and dicts, and returns an
+    /// error for each element that does not fit. For unions and functions
+    /// there is no element to blame, so those are checked as a whole.
+    pub fn type_mismatches(&self, at: Span, type_: &SourcedType) -> Vec<Error> {
+        let mut result = Vec::new();
+        match (&type_.type_, self) {
+            (Type::List(elem_type), Value::List(elems)) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    for err in elem.type_mismatches(at, elem_type) {
+                        result.push(err.with_path_element(PathElement::Index(i)));
+                    }
+                }
+            }
+            (Type::Set(elem_type), Value::Set(elems)) => {
+                for (i, elem) in elems.iter().enumerate() {
+                    for err in elem.type_mismatches(at, elem_type) {
+                        result.push(err.with_path_element(PathElement::Index(i)));
+                    }
+                }
+            }
+            (Type::Dict(dict), Value::Dict(kvs)) => {
+                for (k, v) in kvs.iter() {
+                    let errors = k
+                        .type_mismatches(at, &dict.key)
+                        .into_iter()
+                        .chain(v.type_mismatches(at, &dict.value));
+                    for err in errors {
+                        result.push(err.with_path_element(PathElement::Key(k.clone())));
+                    }
+                }
+            }
+            _ => {
+                if let Err(err) = self.is_instance_of(at, type_) {
+                    result.push(*err);
+                }
+            }
+        }
+        result
+    }
+
     /// Dynamically check that the value fits the required type.
     pub fn is_instance_of(&self, at: Span, type_: &SourcedType) -> Result<()> {
         let req_type = match &type_.type_ {
@@ -304,7 +414,7 @@ impl Value {
             Doc::HardBreak Doc::HardBreak
             "But got this value:"
             Doc::HardBreak Doc::HardBreak
-            indent! { format_rcl(self).into_owned() }
+            indent! { format_rcl_short(self) }
         });
         type_.explain_error(Side::Expected, &mut error);
         error.err()
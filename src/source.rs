@@ -56,6 +56,20 @@ pub struct Span {
     data: u128,
 }
 
+/// A 1-based line and column in a document.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LineCol {
+    pub line: usize,
+    /// The column, counted in Unicode scalar values, not in bytes.
+    pub column: usize,
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 impl fmt::Debug for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "d{}[{}..{}]", self.doc().0, self.start(), self.end())
@@ -111,6 +125,31 @@ impl Span {
         input.resolve(*self)
     }
 
+    /// Return the line and column of the start and end of the span.
+    ///
+    /// The end is exclusive, like the end offset: it is the position directly
+    /// after the last character of the span. Like [`Span::resolve`], this
+    /// assumes that `input` is the document that the span belongs to.
+    pub fn line_col(&self, input: &str) -> (LineCol, LineCol) {
+        let mut pos = LineCol { line: 1, column: 1 };
+        let mut start = pos;
+        for (i, c) in input[..self.end()].char_indices() {
+            if i == self.start() {
+                start = pos;
+            }
+            if c == '\n' {
+                pos.line += 1;
+                pos.column = 1;
+            } else {
+                pos.column += 1;
+            }
+        }
+        if self.start() == self.end() {
+            start = pos;
+        }
+        (start, pos)
+    }
+
     /// Delete n bytes from the start of the span.
     pub fn trim_start(&self, n: usize) -> Span {
         let n_trim = self.len().min(n);
@@ -158,6 +197,29 @@ mod test {
         assert_eq!(std::mem::align_of::<Span>(), std::mem::align_of::<usize>());
     }
 
+    #[test]
+    fn span_line_col() {
+        let input = "abc\ndéf\n🕴︎x\n";
+        let line_col = |start, end| {
+            let (start, end) = Span::new(DocId(0), start, end).line_col(input);
+            (start.to_string(), end.to_string())
+        };
+        // On the first line.
+        assert_eq!(line_col(0, 1), ("1:1".into(), "1:2".into()));
+        assert_eq!(line_col(1, 3), ("1:2".into(), "1:4".into()));
+        // On a later line.
+        assert_eq!(line_col(4, 5), ("2:1".into(), "2:2".into()));
+        // After a multibyte character. 'é' is two bytes, but one column.
+        assert_eq!(line_col(7, 8), ("2:3".into(), "2:4".into()));
+        // The emoji with variation selector is two scalar values, 7 bytes.
+        assert_eq!(line_col(9, 16), ("3:1".into(), "3:3".into()));
+        assert_eq!(line_col(16, 17), ("3:3".into(), "3:4".into()));
+        // Spanning a line break, and an empty span.
+        assert_eq!(line_col(2, 7), ("1:3".into(), "2:3".into()));
+        assert_eq!(line_col(3, 3), ("1:4".into(), "1:4".into()));
+        assert_eq!(line_col(18, 18), ("4:1".into(), "4:1".into()));
+    }
+
     #[test]
     fn span_roundtrips() {
         let offsets = [
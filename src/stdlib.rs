@@ -13,12 +13,108 @@ use std::rc::Rc;
 use crate::ast::CallArg;
 use crate::error::{IntoError, Result};
 use crate::eval::Evaluator;
-use crate::fmt_rcl::format_rcl;
+use crate::fmt_rcl::{format_rcl, format_rcl_short};
 use crate::markup::Markup;
-use crate::pprint::{concat, indent, Doc};
-use crate::runtime::{builtin_function, builtin_method, FunctionCall, MethodCall, Value};
+use crate::pprint::{concat, indent, Config, Doc};
+use crate::runtime::{
+    builtin_function, builtin_method, BuiltinMethod, FunctionCall, MethodCall, Value,
+};
+use crate::source::Span;
 use crate::types::AsTypeName;
 
+builtin_function!(
+    "std.abs",
+    (x: Int) -> Int,
+    const STD_ABS,
+    builtin_std_abs
+);
+fn builtin_std_abs(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg_span = call.args[0].span;
+    let x: i64 = match &call.args[0].value {
+        Value::Int(i) => *i,
+        _not_int => {
+            // TODO: Add proper typechecking and a proper type error.
+            return arg_span
+                .error("Expected an Int here, but got a different type.")
+                .err();
+        }
+    };
+    match x.checked_abs() {
+        Some(ax) => Ok(Value::Int(ax)),
+        None => {
+            let err = concat! {
+                "Absolute value of "
+                format_rcl_short(&Value::Int(x))
+                " would overflow."
+            };
+            arg_span.error(err).err()
+        }
+    }
+}
+
+builtin_function!(
+    "std.concat",
+    (lists: [[Any]]) -> [Any],
+    const STD_CONCAT,
+    builtin_std_concat
+);
+fn builtin_std_concat(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg_span = call.args[0].span;
+    let lists = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return arg_span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let mut result = Vec::new();
+    for list in lists.iter() {
+        match list {
+            Value::List(xs) => result.extend(xs.iter().cloned()),
+            not_list => {
+                let err = concat! {
+                    "Expected lists to concatenate, but found "
+                    format_rcl_short(not_list)
+                    "."
+                };
+                return arg_span.error(err).err();
+            }
+        }
+    }
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.glob",
+    (pattern: String) -> [String],
+    const STD_GLOB,
+    builtin_std_glob
+);
+fn builtin_std_glob(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let arg_span = call.args[0].span;
+    let pattern = match &call.args[0].value {
+        Value::String(s) => s.as_ref(),
+        _not_string => {
+            // TODO: Add proper typechecking and a proper type error.
+            return arg_span
+                .error("Expected a String here, but got a different type.")
+                .err();
+        }
+    };
+    let from = eval.loader.current_doc();
+    let matches = eval
+        .loader
+        .glob(pattern, from)
+        .map_err(|err| err.with_origin(arg_span))?;
+    let result = matches
+        .into_iter()
+        .map(|m| Value::String(m.into()))
+        .collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
 builtin_function!(
     "std.read_file_utf8",
     (path: String) -> String,
@@ -36,12 +132,324 @@ fn builtin_std_read_file_utf8(eval: &mut Evaluator, call: FunctionCall) -> Resul
                 .err();
         }
     };
-    let from = eval.import_stack.last().map(|ctx| ctx.doc);
+    let from = eval.loader.current_doc();
     let doc = eval
         .loader
         .load_path(path, from)
         .map_err(|err| err.with_origin(arg_span))?;
-    Ok(eval.loader.get_doc(doc).data.into())
+    Ok(eval.loader.get_original_data(doc).into())
+}
+
+builtin_function!(
+    "std.count",
+    (list: [Any], predicate: (fn (element: Any) -> Bool)) -> Int,
+    const STD_COUNT,
+    builtin_std_count
+);
+fn builtin_std_count(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let list = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let mut n: i64 = 0;
+    builtin_predicate_impl(
+        eval,
+        &call.args[1],
+        "std.count",
+        list.iter(),
+        |_v, is_match| {
+            n += is_match as i64;
+        },
+    )?;
+    Ok(Value::Int(n))
+}
+
+builtin_function!(
+    "std.flat_map",
+    // TODO: Add type variables so we can describe this more accurately.
+    (list: [Any], map_element: (fn (element: Any) -> [Any])) -> [Any],
+    const STD_FLAT_MAP,
+    builtin_std_flat_map
+);
+fn builtin_std_flat_map(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let list = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let mut result = Vec::with_capacity(list.len());
+    builtin_flat_map_impl(eval, &call.args[1], "std.flat_map", list.iter(), |v| {
+        result.push(v)
+    })?;
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.format_int",
+    (n: Int, options: {String: Any}) -> String,
+    const STD_FORMAT_INT,
+    builtin_std_format_int
+);
+fn builtin_std_format_int(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let n: i64 = match &call.args[0].value {
+        Value::Int(i) => *i,
+        _not_int => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected an Int here, but got a different type.")
+                .err();
+        }
+    };
+    let opts_span = call.args[1].span;
+    let opts = match &call.args[1].value {
+        Value::Dict(opts) => opts,
+        _not_dict => {
+            // TODO: Add proper typechecking and a proper type error.
+            return opts_span
+                .error("Expected a Dict here, but got a different type.")
+                .err();
+        }
+    };
+
+    let invalid_option = |name: &str, expected: &'static str, value: &Value| {
+        opts_span
+            .error(concat! {
+                "Invalid value for option '"
+                Doc::highlight(name).into_owned()
+                "'. Expected "
+                expected
+                ", but got "
+                format_rcl_short(value)
+                "."
+            })
+            .err()
+    };
+
+    // The width is a minimum, but we do put an upper bound on it, to prevent
+    // accidentally allocating huge strings.
+    let max_width = 256;
+    let mut base: u32 = 10;
+    let mut prefix = false;
+    let mut width: usize = 0;
+    let mut zero_pad = false;
+
+    for (key, value) in opts.iter() {
+        let name = match key {
+            Value::String(name) => name.as_ref(),
+            _ => "",
+        };
+        match (name, value) {
+            ("base", Value::Int(b @ (2 | 8 | 10 | 16))) => base = *b as u32,
+            ("base", v) => return invalid_option(name, "2, 8, 10, or 16", v),
+            ("prefix", Value::Bool(b)) => prefix = *b,
+            ("prefix", v) => return invalid_option(name, "a Bool", v),
+            ("width", Value::Int(w)) if (0..=max_width).contains(w) => width = *w as usize,
+            ("width", v) => return invalid_option(name, "an Int between 0 and 256", v),
+            ("zero_pad", Value::Bool(b)) => zero_pad = *b,
+            ("zero_pad", v) => return invalid_option(name, "a Bool", v),
+            _ => {
+                return opts_span
+                    .error(concat! {
+                        "Unknown option "
+                        format_rcl_short(key)
+                        "."
+                    })
+                    .with_help(concat! {
+                        "The options are "
+                        Doc::highlight("base") ", "
+                        Doc::highlight("prefix") ", "
+                        Doc::highlight("width") ", and "
+                        Doc::highlight("zero_pad") "."
+                    })
+                    .err()
+            }
+        }
+    }
+
+    if prefix && base == 10 {
+        return opts_span
+            .error(concat! {
+                "Option '"
+                Doc::highlight("prefix")
+                "' requires a base of 2, 8, or 16, decimal numbers have no prefix."
+            })
+            .err();
+    }
+
+    let magnitude = n.unsigned_abs();
+    let (radix_prefix, digits) = match base {
+        2 => ("0b", format!("{magnitude:b}")),
+        8 => ("0o", format!("{magnitude:o}")),
+        16 => ("0x", format!("{magnitude:x}")),
+        _ => ("", magnitude.to_string()),
+    };
+    let mut head = String::new();
+    if n < 0 {
+        head.push('-');
+    }
+    if prefix {
+        head.push_str(radix_prefix);
+    }
+
+    let len = head.len() + digits.len();
+    let padding = width.saturating_sub(len);
+    let result = if zero_pad {
+        format!("{head}{}{digits}", "0".repeat(padding))
+    } else {
+        format!("{}{head}{digits}", " ".repeat(padding))
+    };
+    Ok(Value::String(result.into()))
+}
+
+builtin_function!(
+    "std.group_by",
+    // TODO: Add type variables so we can describe this more accurately.
+    (list: [Any], get_key: (fn (element: Any) -> Any)) -> {Any: [Any]},
+    const STD_GROUP_BY,
+    builtin_std_group_by
+);
+fn builtin_std_group_by(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let list = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let result = builtin_group_by_impl(eval, &call.args[1], "std.group_by", list.iter())?
+        .into_iter()
+        .map(|(k, vs)| (k, Value::List(Rc::new(vs))))
+        .collect();
+    Ok(Value::Dict(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.get_path",
+    (root: Any, path: [Any], default: Any) -> Any,
+    const STD_GET_PATH,
+    builtin_std_get_path
+);
+fn builtin_std_get_path(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let path = match &call.args[1].value {
+        Value::List(path) => path,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[1]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let default = &call.args[2].value;
+
+    let mut current = &call.args[0].value;
+    for key in path.iter() {
+        let next = match (current, key) {
+            (Value::Dict(dict), key) => dict.get(key),
+            (Value::List(list), Value::Int(i)) => {
+                // Negative indices count from the end, like regular indexing.
+                let i = if *i < 0 { *i + list.len() as i64 } else { *i };
+                usize::try_from(i).ok().and_then(|i| list.get(i))
+            }
+            _ => None,
+        };
+        match next {
+            Some(v) => current = v,
+            None => return Ok(default.clone()),
+        }
+    }
+
+    Ok(current.clone())
+}
+
+builtin_function!(
+    "std.keys_sorted_by",
+    // TODO: Add type variables so we can describe this more accurately.
+    (dict: {Any: Any}, get_key: (fn (key: Any) -> Any)) -> [Any],
+    const STD_KEYS_SORTED_BY,
+    builtin_std_keys_sorted_by
+);
+fn builtin_std_keys_sorted_by(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let dict = match &call.args[0].value {
+        Value::Dict(d) => d,
+        _not_dict => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a Dict here, but got a different type.")
+                .err();
+        }
+    };
+    // Grouping by the sort key orders the groups by sort key, and because we
+    // visit the keys in order, keys with the same sort key stay in key order.
+    let groups = builtin_group_by_impl(eval, &call.args[1], "std.keys_sorted_by", dict.keys())?;
+    let result = groups.into_values().flatten().collect();
+    Ok(Value::List(Rc::new(result)))
+}
+
+builtin_function!(
+    "std.or_default",
+    (value: Any, default: Any) -> Any,
+    const STD_OR_DEFAULT,
+    builtin_std_or_default
+);
+fn builtin_std_or_default(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    match &call.args[0].value {
+        Value::Null => Ok(call.args[1].value.clone()),
+        value => Ok(value.clone()),
+    }
+}
+
+builtin_function!(
+    "std.partition",
+    // TODO: Add type variables so we can describe this more accurately.
+    (list: [Any], predicate: (fn (element: Any) -> Bool)) -> [[Any]],
+    const STD_PARTITION,
+    builtin_std_partition
+);
+fn builtin_std_partition(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let list = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    let mut matching = Vec::new();
+    let mut non_matching = Vec::new();
+    builtin_predicate_impl(
+        eval,
+        &call.args[1],
+        "std.partition",
+        list.iter(),
+        |v, is_match| match is_match {
+            true => matching.push(v.clone()),
+            false => non_matching.push(v.clone()),
+        },
+    )?;
+    let result = vec![
+        Value::List(Rc::new(matching)),
+        Value::List(Rc::new(non_matching)),
+    ];
+    Ok(Value::List(Rc::new(result)))
 }
 
 builtin_function!(
@@ -72,8 +480,14 @@ fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value>
         }
     };
 
-    let range = lower..upper;
+    let args_span = call.args[0].span.union(call.args[1].span);
+    range_impl(args_span, lower, upper, false)
+}
 
+/// Materialize the range `lower..upper` or `lower..=upper` as a list.
+///
+/// This is shared between `std.range` and the range operators.
+pub fn range_impl(span: Span, lower: i64, upper: i64, inclusive: bool) -> Result<Value> {
     // Because we materialize the entire list, it's easy to cause out of memory
     // with a single call to `range`. To prevent that, put an upper limit on the
     // size. We use a lower limit when fuzzing because it runs with less memory,
@@ -85,13 +499,15 @@ fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value>
     #[cfg(not(fuzzing))]
     let max_len = 1_000_000;
 
-    if upper.saturating_sub(lower) > max_len {
-        let args_span = call.args[0].span.union(call.args[1].span);
-        return args_span
+    // Compute the length in i128, so an inclusive upper bound of i64::MAX
+    // does not overflow.
+    let len = upper as i128 - lower as i128 + inclusive as i128;
+    if len > max_len {
+        return span
             .error(concat! {
                 "Range "
                 Doc::string(lower.to_string()).with_markup(Markup::Number)
-                ".."
+                if inclusive { "..=" } else { ".." }
                 Doc::string(upper.to_string()).with_markup(Markup::Number)
                 " exceeds the maximum length of "
                 Doc::string(max_len.to_string()).with_markup(Markup::Number)
@@ -100,19 +516,193 @@ fn builtin_std_range(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value>
             .err();
     }
 
-    let values: Vec<_> = range.map(Value::Int).collect();
+    let values: Vec<_> = if inclusive {
+        (lower..=upper).map(Value::Int).collect()
+    } else {
+        (lower..upper).map(Value::Int).collect()
+    };
     Ok(Value::List(Rc::new(values)))
 }
 
+builtin_function!(
+    "std.reduce",
+    (
+        list: [Any],
+        seed: Any,
+        reduce: (fn (accumulator: Any, element: Any) -> Any)
+    ) -> Any,
+    const STD_REDUCE,
+    builtin_std_reduce
+);
+fn builtin_std_reduce(eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let list = match &call.args[0].value {
+        Value::List(xs) => xs,
+        _not_list => {
+            // TODO: Add proper typechecking and a proper type error.
+            return call.args[0]
+                .span
+                .error("Expected a List here, but got a different type.")
+                .err();
+        }
+    };
+    builtin_fold_impl(eval, "std.reduce", list, &call.args[1], &call.args[2])
+}
+
+builtin_function!(
+    "std.to_string",
+    (value: Any) -> String,
+    const STD_TO_STRING,
+    builtin_std_to_string
+);
+fn builtin_std_to_string(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let value = &call.args[0].value;
+    match value {
+        // Strings we return as-is, they are not quoted.
+        Value::String(..) => Ok(value.clone()),
+        // Scalars we format the same way as an f-string would.
+        Value::Bool(..) | Value::Int(..) | Value::Null => {
+            let mut fragments = Vec::with_capacity(1);
+            Evaluator::push_format_fragment(&mut fragments, call.args[0].span, value)?;
+            Ok(Evaluator::join_format_fragments(fragments))
+        }
+        // Anything else we format as RCL, on a single line.
+        _ => {
+            let cfg = Config { width: u32::MAX };
+            let result = format_rcl(value).print(&cfg).to_string_no_markup();
+            Ok(Value::String(result.into()))
+        }
+    }
+}
+
+builtin_function!(
+    "std.zip",
+    (xs: [Any], ys: [Any]) -> [[Any]],
+    const STD_ZIP,
+    builtin_std_zip
+);
+fn builtin_std_zip(_eval: &mut Evaluator, call: FunctionCall) -> Result<Value> {
+    let mut lists = Vec::with_capacity(2);
+    for arg in call.args.iter() {
+        match &arg.value {
+            Value::List(xs) => lists.push(xs),
+            _not_list => {
+                // TODO: Add proper typechecking and a proper type error.
+                return arg
+                    .span
+                    .error("Expected a List here, but got a different type.")
+                    .err();
+            }
+        }
+    }
+
+    Ok(zip_impl(lists[0], lists[1]))
+}
+
+/// Zip two lists into a list of pairs, shared by `std.zip` and `List.zip`.
+fn zip_impl(xs: &[Value], ys: &[Value]) -> Value {
+    // When the lists have different lengths, the result is as long as the
+    // shortest list, the excess elements of the longer list are dropped.
+    let result: Vec<Value> = xs
+        .iter()
+        .zip(ys.iter())
+        .map(|(x, y)| Value::List(Rc::new(vec![x.clone(), y.clone()])))
+        .collect();
+    Value::List(Rc::new(result))
+}
+
+/// The builtin methods on dicts.
+///
+/// Method lookup happens in the evaluator, these lists are for enumerating the
+/// methods, e.g. for autocompletion.
+pub const DICT_METHODS: &[&BuiltinMethod] = &[
+    &DICT_CONTAINS,
+    &DICT_EXCEPT,
+    &DICT_FETCH,
+    &DICT_GET,
+    &DICT_GET_OR_ELSE,
+    &DICT_HAS,
+    &DICT_KEYS,
+    &DICT_LEN,
+    &DICT_MAP_VALUES,
+    &DICT_VALUES,
+];
+
+/// The builtin methods on lists.
+pub const LIST_METHODS: &[&BuiltinMethod] = &[
+    &LIST_CONTAINS,
+    &LIST_ENUMERATE,
+    &LIST_FILTER,
+    &LIST_FLAT_MAP,
+    &LIST_FOLD,
+    &LIST_GROUP_BY,
+    &LIST_JOIN,
+    &LIST_KEY_BY,
+    &LIST_LEN,
+    &LIST_MAP,
+    &LIST_REVERSE,
+    &LIST_SUM,
+    &LIST_UNIQUE,
+    &LIST_ZIP,
+];
+
+/// The builtin methods on sets.
+pub const SET_METHODS: &[&BuiltinMethod] = &[
+    &SET_CONTAINS,
+    &SET_EXCEPT,
+    &SET_FILTER,
+    &SET_FLAT_MAP,
+    &SET_GROUP_BY,
+    &SET_KEY_BY,
+    &SET_LEN,
+    &SET_MAP,
+    &SET_SUM,
+];
+
+/// The builtin methods on strings.
+pub const STRING_METHODS: &[&BuiltinMethod] = &[
+    &STRING_CHARS,
+    &STRING_CONTAINS,
+    &STRING_ENDS_WITH,
+    &STRING_INDEX_OF,
+    &STRING_LEN,
+    &STRING_PARSE_INT,
+    &STRING_REMOVE_PREFIX,
+    &STRING_REMOVE_SUFFIX,
+    &STRING_REPEAT,
+    &STRING_REPLACE,
+    &STRING_SPLIT,
+    &STRING_SPLIT_LINES,
+    &STRING_STARTS_WITH,
+    &STRING_TO_LOWERCASE,
+    &STRING_TO_UPPERCASE,
+];
+
 /// Initialize the standard library.
 pub fn initialize() -> Value {
     let mut builtins: BTreeMap<Value, Value> = BTreeMap::new();
 
+    builtins.insert("abs".into(), Value::BuiltinFunction(&STD_ABS));
+    builtins.insert("concat".into(), Value::BuiltinFunction(&STD_CONCAT));
+    builtins.insert("count".into(), Value::BuiltinFunction(&STD_COUNT));
+    builtins.insert("flat_map".into(), Value::BuiltinFunction(&STD_FLAT_MAP));
+    builtins.insert("format_int".into(), Value::BuiltinFunction(&STD_FORMAT_INT));
+    builtins.insert("get_path".into(), Value::BuiltinFunction(&STD_GET_PATH));
+    builtins.insert("glob".into(), Value::BuiltinFunction(&STD_GLOB));
+    builtins.insert("group_by".into(), Value::BuiltinFunction(&STD_GROUP_BY));
+    builtins.insert(
+        "keys_sorted_by".into(),
+        Value::BuiltinFunction(&STD_KEYS_SORTED_BY),
+    );
+    builtins.insert("or_default".into(), Value::BuiltinFunction(&STD_OR_DEFAULT));
+    builtins.insert("partition".into(), Value::BuiltinFunction(&STD_PARTITION));
     builtins.insert("range".into(), Value::BuiltinFunction(&STD_RANGE));
     builtins.insert(
         "read_file_utf8".into(),
         Value::BuiltinFunction(&STD_READ_FILE_UTF8),
     );
+    builtins.insert("reduce".into(), Value::BuiltinFunction(&STD_REDUCE));
+    builtins.insert("to_string".into(), Value::BuiltinFunction(&STD_TO_STRING));
+    builtins.insert("zip".into(), Value::BuiltinFunction(&STD_ZIP));
 
     Value::Dict(Rc::new(builtins))
 }
@@ -153,6 +743,21 @@ fn builtin_dict_contains(_eval: &mut Evaluator, call: MethodCall) -> Result<Valu
     Ok(Value::Bool(dict.contains_key(needle)))
 }
 
+builtin_method!(
+    "Dict.has",
+    (field: String) -> Bool,
+    const DICT_HAS,
+    builtin_dict_has
+);
+fn builtin_dict_has(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let dict = call.receiver.expect_dict();
+    let field_arg = &call.call.args[0];
+    match &field_arg.value {
+        field @ Value::String(..) => Ok(Value::Bool(dict.contains_key(field))),
+        _ => field_arg.span.error("Field name must be a string.").err(),
+    }
+}
+
 builtin_method!(
     "List.contains",
     (element: Any) -> Bool,
@@ -193,6 +798,64 @@ fn builtin_dict_get(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     }
 }
 
+builtin_method!(
+    "Dict.fetch",
+    (key: Any) -> Any,
+    const DICT_FETCH,
+    builtin_dict_fetch
+);
+fn builtin_dict_fetch(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let dict = call.receiver.expect_dict();
+    let key_arg = &call.call.args[0];
+    if let Some(v) = dict.get(&key_arg.value) {
+        return Ok(v.clone());
+    }
+
+    let note = if dict.is_empty() {
+        "The dict is empty.".into()
+    } else {
+        let keys = Value::Set(Rc::new(dict.keys().cloned().collect()));
+        concat! { "Available keys: " format_rcl_short(&keys) }
+    };
+    key_arg
+        .span
+        .error(concat! { "No such key " format_rcl_short(&key_arg.value) "." })
+        .with_note(call.receiver_span, note)
+        .err()
+}
+
+builtin_method!(
+    "Dict.get_or_else",
+    (key: Any, default: (fn () -> Any)) -> Any,
+    const DICT_GET_OR_ELSE,
+    builtin_dict_get_or_else
+);
+fn builtin_dict_get_or_else(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let dict = call.receiver.expect_dict();
+    let key = &call.call.args[0].value;
+    let default = &call.call.args[1];
+    if let Some(v) = dict.get(key) {
+        return Ok(v.clone());
+    }
+
+    // The call that we construct here is internal, there is no span in the
+    // source code that we could point at. Point at the argument so we still
+    // have something to highlight.
+    let call = FunctionCall {
+        call_open: default.span,
+        call_close: default.span,
+        args: &[],
+    };
+    eval.eval_call(default.span, &default.value, call)
+        .map_err(|mut err| {
+            err.replace_call_frame(
+                default.span,
+                concat! { "In internal call to default function from '" Doc::highlight("Dict.get_or_else") "'." },
+            );
+            err
+        })
+}
+
 builtin_method!(
     "Dict.keys",
     () -> {Any},
@@ -215,6 +878,54 @@ fn builtin_dict_values(_eval: &mut Evaluator, call: MethodCall) -> Result<Value>
     Ok(Value::List(Rc::new(result)))
 }
 
+builtin_method!(
+    "Dict.map_values",
+    // TODO: Add type variables so we can describe this more accurately.
+    (map_value: (fn (value: Any) -> Any)) -> {Any: Any},
+    const DICT_MAP_VALUES,
+    builtin_dict_map_values
+);
+fn builtin_dict_map_values(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let dict = call.receiver.expect_dict();
+    let map_value = &call.call.args[0];
+    let mut result = BTreeMap::new();
+
+    for (k, v) in dict.iter() {
+        // The call that we construct here is internal, there is no span in the
+        // source code that we could point at. Point at the argument so we still
+        // have something to highlight.
+        let args = [CallArg {
+            span: map_value.span,
+            value: v.clone(),
+        }];
+        let call = FunctionCall {
+            call_open: map_value.span,
+            call_close: map_value.span,
+            args: &args,
+        };
+        let mapped_value = eval
+            .eval_call(map_value.span, &map_value.value, call)
+            .map_err(|mut err| {
+                // Replace the call frame with one that mentions the key, so it
+                // is clear which value the mapping function failed on.
+                err.replace_call_frame(
+                    map_value.span,
+                    concat! {
+                        "In internal call to mapping function from '"
+                        Doc::highlight("Dict.map_values")
+                        "' for key "
+                        format_rcl_short(k)
+                        "."
+                    },
+                );
+                err
+            })?;
+        result.insert(k.clone(), mapped_value);
+    }
+
+    Ok(Value::Dict(Rc::new(result)))
+}
+
 builtin_method!(
     "Dict.except",
     (key: Any) -> {Any: Any},
@@ -243,12 +954,12 @@ fn builtin_set_except(_eval: &mut Evaluator, call: MethodCall) -> Result<Value>
 
 fn builtin_group_by_impl<'a, I: IntoIterator<Item = &'a Value>>(
     eval: &mut Evaluator,
-    call: MethodCall,
+    get_key: &CallArg<Value>,
     name: &'static str,
     elements: I,
 ) -> Result<BTreeMap<Value, Vec<Value>>> {
-    let get_key = &call.call.args[0].value;
-    let get_key_span = call.call.args[0].span;
+    let get_key_span = get_key.span;
+    let get_key = &get_key.value;
 
     let mut groups: BTreeMap<Value, Vec<Value>> = BTreeMap::new();
 
@@ -292,7 +1003,7 @@ builtin_method!(
 );
 fn builtin_list_group_by(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
-    let result = builtin_group_by_impl(eval, call, "List.group_by", list)?
+    let result = builtin_group_by_impl(eval, &call.call.args[0], "List.group_by", list)?
         .into_iter()
         .map(|(k, vs)| (k, Value::List(Rc::new(vs))))
         .collect();
@@ -308,7 +1019,7 @@ builtin_method!(
 );
 fn builtin_set_group_by(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let set = call.receiver.expect_set();
-    let result = builtin_group_by_impl(eval, call, "Set.group_by", set)?
+    let result = builtin_group_by_impl(eval, &call.call.args[0], "Set.group_by", set)?
         .into_iter()
         .map(|(k, vs)| (k, Value::Set(Rc::new(vs.into_iter().collect()))))
         .collect();
@@ -322,13 +1033,13 @@ fn builtin_key_by_impl<'a, I: IntoIterator<Item = &'a Value>>(
     elements: I,
 ) -> Result<Value> {
     let method_span = call.method_span;
-    let groups = builtin_group_by_impl(eval, call, name, elements)?;
+    let groups = builtin_group_by_impl(eval, &call.call.args[0], name, elements)?;
     let mut result = BTreeMap::new();
     for (k, mut vs) in groups.into_iter() {
         if vs.len() > 1 {
             return method_span
                 .error(concat! {
-                    "The key " format_rcl(&k).into_owned() " is not unique."
+                    "The key " format_rcl_short(&k) " is not unique."
                 })
                 .with_body(concat! {
                     "The following values use this key:"
@@ -336,7 +1047,7 @@ fn builtin_key_by_impl<'a, I: IntoIterator<Item = &'a Value>>(
                     Doc::HardBreak
                     indent! {
                         Doc::join(
-                            vs.iter().map(|v| format_rcl(v).into_owned()),
+                            vs.iter().map(format_rcl_short),
                             Doc::HardBreak,
                         )
                     }
@@ -382,14 +1093,14 @@ fn builtin_generic_map_impl<
     F: FnMut(&Value, Value) -> Result<()>,
 >(
     eval: &mut Evaluator,
-    call: MethodCall,
+    map_element: &CallArg<Value>,
     fn_description: &'static str,
     name: &'static str,
     elements: I,
     mut accept: F,
 ) -> Result<()> {
-    let map_element = &call.call.args[0].value;
-    let map_element_span = call.call.args[0].span;
+    let map_element_span = map_element.span;
+    let map_element = &map_element.value;
 
     for x in elements {
         // The call that we construct here is internal, there is no span in the
@@ -423,14 +1134,14 @@ fn builtin_generic_map_impl<
 
 fn builtin_map_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(Value)>(
     eval: &mut Evaluator,
-    call: MethodCall,
+    map_element: &CallArg<Value>,
     name: &'static str,
     elements: I,
     mut accept: F,
 ) -> Result<()> {
     builtin_generic_map_impl(
         eval,
-        call,
+        map_element,
         "mapping function",
         name,
         elements,
@@ -443,44 +1154,65 @@ fn builtin_map_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(Value)>(
 
 fn builtin_filter_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(Value)>(
     eval: &mut Evaluator,
-    call: MethodCall,
+    map_element: &CallArg<Value>,
     name: &'static str,
     elements: I,
     mut accept: F,
 ) -> Result<()> {
-    let predicate_span = call.call.args[0].span;
-    builtin_generic_map_impl(eval, call, "predicate", name, elements, |orig, result| {
-        match result {
-            Value::Bool(true) => accept(orig.clone()),
-            Value::Bool(false) => {}
-            not_bool => {
-                return predicate_span
-                    .error("Type mismatch.")
-                    .with_body(concat! {
-                        "Expected the predicate to return "
-                        "Bool".format_type()
-                        ", but it returned "
-                        format_rcl(&not_bool).into_owned()
-                        "."
-                    })
-                    .err();
-            }
+    builtin_predicate_impl(eval, map_element, name, elements, |orig, is_match| {
+        if is_match {
+            accept(orig.clone());
         }
-        Ok(())
     })
 }
 
+/// Apply a predicate to all elements, and report for each whether it matched.
+fn builtin_predicate_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(&Value, bool)>(
+    eval: &mut Evaluator,
+    map_element: &CallArg<Value>,
+    name: &'static str,
+    elements: I,
+    mut accept: F,
+) -> Result<()> {
+    let predicate_span = map_element.span;
+    builtin_generic_map_impl(
+        eval,
+        map_element,
+        "predicate",
+        name,
+        elements,
+        |orig, result| {
+            match result {
+                Value::Bool(b) => accept(orig, b),
+                not_bool => {
+                    return predicate_span
+                        .error("Type mismatch.")
+                        .with_body(concat! {
+                            "Expected the predicate to return "
+                            "Bool".format_type()
+                            ", but it returned "
+                            format_rcl_short(&not_bool)
+                            "."
+                        })
+                        .err();
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
 fn builtin_flat_map_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(Value)>(
     eval: &mut Evaluator,
-    call: MethodCall,
+    map_element: &CallArg<Value>,
     name: &'static str,
     elements: I,
     mut accept: F,
 ) -> Result<()> {
-    let predicate_span = call.call.args[0].span;
+    let predicate_span = map_element.span;
     builtin_generic_map_impl(
         eval,
-        call,
+        map_element,
         "mapping function",
         name,
         elements,
@@ -501,7 +1233,7 @@ fn builtin_flat_map_impl<'a, I: IntoIterator<Item = &'a Value>, F: FnMut(Value)>
                         .error("Type mismatch.")
                         .with_body(concat! {
                         "Expected the mapping function to return a list or set, but it returned "
-                        format_rcl(&not_collection).into_owned()
+                        format_rcl_short(&not_collection)
                         "."
                     })
                         .err();
@@ -522,7 +1254,9 @@ builtin_method!(
 fn builtin_list_map(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
     let mut result = Vec::with_capacity(list.len());
-    builtin_map_impl(eval, call, "List.map", list, |v| result.push(v))?;
+    builtin_map_impl(eval, &call.call.args[0], "List.map", list, |v| {
+        result.push(v)
+    })?;
     Ok(Value::List(Rc::new(result)))
 }
 
@@ -536,7 +1270,9 @@ builtin_method!(
 fn builtin_list_flat_map(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
     let mut result = Vec::with_capacity(list.len());
-    builtin_flat_map_impl(eval, call, "List.flat_map", list, |v| result.push(v))?;
+    builtin_flat_map_impl(eval, &call.call.args[0], "List.flat_map", list, |v| {
+        result.push(v)
+    })?;
     Ok(Value::List(Rc::new(result)))
 }
 
@@ -550,7 +1286,9 @@ builtin_method!(
 fn builtin_list_filter(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
     let mut result = Vec::new();
-    builtin_filter_impl(eval, call, "List.filter", list, |v| result.push(v))?;
+    builtin_filter_impl(eval, &call.call.args[0], "List.filter", list, |v| {
+        result.push(v)
+    })?;
     Ok(Value::List(Rc::new(result)))
 }
 
@@ -564,7 +1302,7 @@ builtin_method!(
 fn builtin_set_map(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let set = call.receiver.expect_set();
     let mut result = BTreeSet::new();
-    builtin_map_impl(eval, call, "Set.map", set, |v| {
+    builtin_map_impl(eval, &call.call.args[0], "Set.map", set, |v| {
         result.insert(v);
     })?;
     Ok(Value::Set(Rc::new(result)))
@@ -580,7 +1318,7 @@ builtin_method!(
 fn builtin_set_flat_map(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let set = call.receiver.expect_set();
     let mut result = BTreeSet::new();
-    builtin_flat_map_impl(eval, call, "Set.flat_map", set, |v| {
+    builtin_flat_map_impl(eval, &call.call.args[0], "Set.flat_map", set, |v| {
         result.insert(v);
     })?;
     Ok(Value::Set(Rc::new(result)))
@@ -596,7 +1334,7 @@ builtin_method!(
 fn builtin_set_filter(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let set = call.receiver.expect_set();
     let mut result = BTreeSet::new();
-    builtin_filter_impl(eval, call, "Set.filter", set, |v| {
+    builtin_filter_impl(eval, &call.call.args[0], "Set.filter", set, |v| {
         result.insert(v);
     })?;
     Ok(Value::Set(Rc::new(result)))
@@ -613,14 +1351,18 @@ fn builtin_sum_impl<'a>(
                 Some(m) => acc = m,
                 None => {
                     let err = concat! {
-                        "Addition " acc.to_string() " + " n.to_string() " would overflow."
+                        "Addition "
+                        format_rcl_short(&Value::Int(acc))
+                        " + "
+                        format_rcl_short(x)
+                        " would overflow."
                     };
                     return call.method_span.error(err).err();
                 }
             },
             not_int => {
                 let err = concat! {
-                    "Expected integers to add, but found " format_rcl(not_int).into_owned() "."
+                    "Expected integers to add, but found " format_rcl_short(not_int) "."
                 };
                 return call.receiver_span.error(err).err();
             }
@@ -749,6 +1491,29 @@ fn builtin_string_contains(_eval: &mut Evaluator, call: MethodCall) -> Result<Va
     Ok(Value::Bool(string.contains(needle)))
 }
 
+builtin_method!(
+    "String.index_of",
+    // TODO: The result type should be `Union[Int, Null]`, but the type eDSL
+    // does not support unions yet.
+    (needle: String) -> Any,
+    const STRING_INDEX_OF,
+    builtin_string_index_of
+);
+fn builtin_string_index_of(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let string = call.receiver.expect_string();
+    let needle_arg = &call.call.args[0];
+    let needle = match &needle_arg.value {
+        Value::String(s) => s.as_ref(),
+        _ => return needle_arg.span.error("Needle must be a string.").err(),
+    };
+    match string.find(needle) {
+        // The index we report is in code points, not in bytes, so it agrees
+        // with `String.chars`.
+        Some(i) => Ok(Value::Int(string[..i].chars().count() as i64)),
+        None => Ok(Value::Null),
+    }
+}
+
 builtin_method!(
     "String.chars",
     () -> [String],
@@ -774,6 +1539,56 @@ fn builtin_string_chars(_eval: &mut Evaluator, call: MethodCall) -> Result<Value
     Ok(Value::List(Rc::new(result)))
 }
 
+builtin_method!(
+    "String.repeat",
+    (count: Int) -> String,
+    const STRING_REPEAT,
+    builtin_string_repeat
+);
+fn builtin_string_repeat(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let string = call.receiver.expect_string();
+    let count_arg = &call.call.args[0];
+    let count = match &count_arg.value {
+        Value::Int(n) => *n,
+        _ => return count_arg.span.error("Count must be an integer.").err(),
+    };
+    if count < 0 {
+        return count_arg
+            .span
+            .error(concat! {
+                "Count must not be negative, but got "
+                format_rcl_short(&count_arg.value)
+                "."
+            })
+            .err();
+    }
+
+    // Like for `std.range`, we put an upper limit on the size of the result,
+    // so a single call cannot cause out of memory.
+    #[cfg(fuzzing)]
+    let max_len = 500;
+    #[cfg(not(fuzzing))]
+    let max_len = 10_000_000;
+
+    let len = string.len() as i128 * count as i128;
+    if len > max_len {
+        return count_arg
+            .span
+            .error(concat! {
+                "Repeating a string of "
+                Doc::string(string.len().to_string()).with_markup(Markup::Number)
+                " bytes "
+                Doc::string(count.to_string()).with_markup(Markup::Number)
+                " times exceeds the maximum length of "
+                Doc::string(max_len.to_string()).with_markup(Markup::Number)
+                " bytes."
+            })
+            .err();
+    }
+
+    Ok(Value::String(string.repeat(count as usize).into()))
+}
+
 builtin_method!(
     "String.replace",
     (needle: String, replacement: String) -> String,
@@ -895,7 +1710,16 @@ fn builtin_list_fold(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
     let list = call.receiver.expect_list();
     let seed = &call.call.args[0];
     let reduce = &call.call.args[1];
+    builtin_fold_impl(eval, "List.fold", list, seed, reduce)
+}
 
+fn builtin_fold_impl(
+    eval: &mut Evaluator,
+    name: &'static str,
+    list: &[Value],
+    seed: &CallArg<Value>,
+    reduce: &CallArg<Value>,
+) -> Result<Value> {
     let mut acc = seed.value.clone();
 
     for element in list.iter() {
@@ -917,16 +1741,18 @@ fn builtin_list_fold(eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
             call_close: reduce.span,
             args: &args,
         };
-        acc = eval.eval_call(reduce.span, &reduce.value, call).map_err(|mut err| {
-            // If the call includes a call frame for this call, then replace
-            // it with a more descriptive message, since the span is a bit
-            // misleading.
-            err.replace_call_frame(
+        acc = eval
+            .eval_call(reduce.span, &reduce.value, call)
+            .map_err(|mut err| {
+                // If the call includes a call frame for this call, then replace
+                // it with a more descriptive message, since the span is a bit
+                // misleading.
+                err.replace_call_frame(
                 reduce.span,
-                concat! { "In internal call to reduce function from '" Doc::highlight("List.fold") "'." }
+                concat! { "In internal call to reduce function from '" Doc::highlight(name) "'." }
             );
-            err
-        })?;
+                err
+            })?;
     }
 
     Ok(acc)
@@ -969,6 +1795,37 @@ fn builtin_list_reverse(_eval: &mut Evaluator, call: MethodCall) -> Result<Value
     Ok(Value::List(Rc::new(reversed)))
 }
 
+builtin_method!(
+    "List.unique",
+    () -> [Any],
+    const LIST_UNIQUE,
+    builtin_list_unique
+);
+fn builtin_list_unique(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let list = call.receiver.expect_list();
+    let mut seen = BTreeSet::new();
+    let unique = list.iter().filter(|v| seen.insert(*v)).cloned().collect();
+    Ok(Value::List(Rc::new(unique)))
+}
+
+builtin_method!(
+    "List.zip",
+    (other: [Any]) -> [[Any]],
+    const LIST_ZIP,
+    builtin_list_zip
+);
+fn builtin_list_zip(_eval: &mut Evaluator, call: MethodCall) -> Result<Value> {
+    let list = call.receiver.expect_list();
+    let other_arg = &call.call.args[0];
+    match &other_arg.value {
+        Value::List(other) => Ok(zip_impl(list, other)),
+        _not_list => other_arg
+            .span
+            .error("Expected a List here, but got a different type.")
+            .err(),
+    }
+}
+
 builtin_method!(
     "List.enumerate",
     () -> {Int: Any},
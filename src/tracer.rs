@@ -36,11 +36,10 @@ pub struct StderrTracer {
 }
 
 impl StderrTracer {
-    pub fn new(markup: Option<MarkupMode>) -> StderrTracer {
-        let stderr = std::io::stderr();
+    pub fn new(markup: MarkupMode) -> StderrTracer {
         StderrTracer {
             config: pprint::Config { width: 80 },
-            markup: markup.unwrap_or_else(|| MarkupMode::default_for_fd(&stderr)),
+            markup,
         }
     }
 }
@@ -40,6 +40,9 @@ pub enum Source {
     /// An integer is required due to indexing into a list.
     IndexList,
 
+    /// An integer is required for the bounds of a slice.
+    SliceBound,
+
     /// The type is part of the expected type for build files for `rcl build`.
     BuildFile(&'static str),
 }
@@ -58,6 +61,7 @@ impl Source {
             Source::Builtin => None,
             Source::Condition => None,
             Source::IndexList => None,
+            Source::SliceBound => None,
             Source::BuildFile(..) => None,
         }
     }
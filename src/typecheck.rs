@@ -14,8 +14,8 @@
 
 use std::rc::Rc;
 
-use crate::ast::{BinOp, Expr, Ident, Seq, Stmt, Type as AType, UnOp, Yield};
-use crate::error::{IntoError, Result};
+use crate::ast::{BinOp, Binding, Expr, Ident, Pattern, Seq, Stmt, Type as AType, UnOp, Yield};
+use crate::error::{bool_ordering_help, iteration_arity_error, IntoError, Result};
 use crate::fmt_type::format_type;
 use crate::pprint::{concat, indent, Doc};
 use crate::source::Span;
@@ -47,7 +47,7 @@ fn get_primitive_type(name: &str) -> Option<Type> {
 }
 
 /// Parse a type expression.
-fn eval_type_expr(expr: &AType) -> Result<SourcedType> {
+pub fn eval_type_expr(expr: &AType) -> Result<SourcedType> {
     match expr {
         AType::Term { span, name } => {
             if let Some(prim) = get_primitive_type(name.as_ref()) {
@@ -203,6 +203,34 @@ fn eval_type_apply(name_span: Span, name: &str, args: &[SourcedType]) -> Result<
             Ok(Type::Union(Rc::new(union)))
         }
 
+        "Optional" => match args {
+            [te] => {
+                // `Optional[T]` is a shorthand for `Union[T, Null]`. If `T` is
+                // a union itself, we add `Null` to it, to keep unions flat.
+                let null = SourcedType {
+                    type_: Type::Null,
+                    source: Source::Annotation(name_span),
+                };
+                let mut members = match &te.type_ {
+                    Type::Union(union) => union.members.clone(),
+                    _ => vec![te.clone()],
+                };
+                if !members.iter().any(|m| matches!(m.type_, Type::Null)) {
+                    members.push(null);
+                }
+                match members.len() {
+                    1 => Ok(Type::Null),
+                    _ => Ok(Type::Union(Rc::new(Union { members }))),
+                }
+            }
+            // TODO: As above for dict, we can do a better job of the error.
+            _ => name_span
+                .error(concat! {
+                    "Type 'Optional' takes one type parameter (the non-null type), but got "
+                    args.len().to_string() "."
+                })
+                .err(),
+        },
         _ => name_span.error("Unknown generic type.").err(),
     }
 }
@@ -239,6 +267,14 @@ fn type_int_index() -> &'static SourcedType {
     }
 }
 
+/// Construct a `SourcedType` for a `Int` for a slice bound.
+fn type_int_slice_bound() -> &'static SourcedType {
+    &SourcedType {
+        type_: Type::Int,
+        source: Source::SliceBound,
+    }
+}
+
 /// Construct a `SourcedType` for an operator.
 fn type_operator(at: Span, type_: Type) -> SourcedType {
     SourcedType {
@@ -247,15 +283,54 @@ fn type_operator(at: Span, type_: Type) -> SourcedType {
     }
 }
 
+/// Return the inferred type, and insert a runtime type check if needed.
+///
+/// If we couldn't check statically, then we have to insert a runtime type check
+/// around the node. We have to sacrifice a temporary NullLit to the borrow
+/// checker to swap the node into place.
+fn insert_runtime_check(
+    expected: &SourcedType,
+    expr_span: Span,
+    expr: &mut Expr,
+    expr_type: Typed<SourcedType>,
+) -> SourcedType {
+    match expr_type {
+        Typed::Type(t) => t,
+        Typed::Defer(t) => {
+            let mut tmp = Expr::NullLit;
+            std::mem::swap(&mut tmp, expr);
+            *expr = Expr::CheckType {
+                span: expr_span,
+                type_: expected.clone(),
+                body: Box::new(tmp),
+            };
+            t
+        }
+    }
+}
+
 pub struct TypeChecker<'a> {
     // TODO: Do I really need to borrow it?
     // Could also move it into and out of the checker.
     env: &'a mut Env,
+
+    /// Whether to reject constructs that are accepted but often a mistake.
+    strict: bool,
 }
 
 impl<'a> TypeChecker<'a> {
     pub fn new(env: &'a mut Env) -> TypeChecker<'a> {
-        TypeChecker { env }
+        TypeChecker { env, strict: false }
+    }
+
+    /// Enable or disable strict mode.
+    ///
+    /// In strict mode, an empty `{}` without an expected type is an error,
+    /// rather than an empty dict. An empty dict can be written as `{:}`, and
+    /// a type annotation can make `{}` an empty set. Without an expected type,
+    /// if-else branches with incompatible types are an error too.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 
     /// Check that an expression fits the type requirements.
@@ -269,6 +344,34 @@ impl<'a> TypeChecker<'a> {
         expr_span: Span,
         expr: &mut Expr,
     ) -> Result<SourcedType> {
+        let expr_type = self.check_expr_typed(expected, expr_span, expr)?;
+        Ok(insert_runtime_check(expected, expr_span, expr, expr_type))
+    }
+
+    /// Typecheck an expression whose value the evaluator inspects anyway.
+    ///
+    /// This reports the same static errors as [`check_expr`], but when the
+    /// check has to be deferred, it does not insert a runtime check around the
+    /// expression. For conditions and operands of unary operators, the
+    /// evaluator checks the value itself, and reports a more specific error
+    /// than a generic type mismatch.
+    fn check_expr_evaluator_checked(
+        &mut self,
+        expected: &SourcedType,
+        expr_span: Span,
+        expr: &mut Expr,
+    ) -> Result<()> {
+        self.check_expr_typed(expected, expr_span, expr)?;
+        Ok(())
+    }
+
+    /// Typecheck an expression, but leave inserting a runtime check to the caller.
+    fn check_expr_typed(
+        &mut self,
+        expected: &SourcedType,
+        expr_span: Span,
+        expr: &mut Expr,
+    ) -> Result<Typed<SourcedType>> {
         let expr_type = match expr {
             Expr::Stmt {
                 stmt,
@@ -321,6 +424,22 @@ impl<'a> TypeChecker<'a> {
                     }
                 };
 
+                // Without an expected type, an empty `{}` defaults to a dict.
+                // In strict mode we want the author to be explicit about it.
+                let is_ambiguous = matches!(seq_type, SeqType::SetOrDict) && !is_error;
+                if self.strict && is_ambiguous && seqs.is_empty() {
+                    return expr_span
+                        .error("Ambiguous empty collection.")
+                        .with_body(concat! {
+                            "In strict mode, an empty "
+                            Doc::highlight("{}")
+                            " must be either a dict or a set. Write "
+                            Doc::highlight("{:}")
+                            " for an empty dict, or add a type annotation for an empty set."
+                        })
+                        .err();
+                }
+
                 // Typecheck all the elements, and enforce the element
                 // requirement if we have one. This at the same time infers the
                 // element type.
@@ -348,6 +467,12 @@ impl<'a> TypeChecker<'a> {
                 }
             }
 
+            // Dict literals are normally inserted by the typechecker, but the
+            // empty dict `{:}` is one already before checking.
+            Expr::DictLit { elements, .. } if elements.is_empty() => {
+                SeqType::SetOrDict.into_type(expr_span).is_subtype_of(expected).check(expr_span)?
+            }
+
             Expr::BracketLit { elements: seqs, .. } => {
                 // This follows the same structure as `BraceLit`, see comments above.
                 let mut is_error = false;
@@ -399,14 +524,81 @@ impl<'a> TypeChecker<'a> {
                 span_else,
                 ..
             } => {
-                self.check_expr(type_bool_condition(), *condition_span, condition)?;
+                self.check_expr_evaluator_checked(type_bool_condition(), *condition_span, condition)?;
 
                 let type_then = self.check_expr(expected, *span_then, body_then)?;
                 let type_else = self.check_expr(expected, *span_else, body_else)?;
 
                 // The inferred type is the meet of the two sides, which may be
                 // more specific than the requirement (which they satisfy).
-                Typed::Type(type_then.meet(&type_else))
+                let type_meet = type_then.meet(&type_else);
+
+                // When there is a requirement, both sides already satisfy it.
+                // Without one, in strict mode we want the author to be explicit
+                // when the branches have nothing in common, because usually
+                // that is an accident.
+                let is_divergent = match (&type_then.type_, &type_else.type_) {
+                    (Type::Any | Type::Void, _) | (_, Type::Any | Type::Void) => false,
+                    // A null on one side makes an optional value, which is common.
+                    (Type::Null, _) | (_, Type::Null) => false,
+                    // Meeting functions is not supported, so we can't tell.
+                    (Type::Function(..), Type::Function(..)) => false,
+                    _ => type_meet.type_ == Type::Any,
+                };
+                if self.strict && expected.type_ == Type::Any && is_divergent {
+                    let union = Type::Union(Rc::new(Union {
+                        members: vec![type_then.clone(), type_else.clone()],
+                    }));
+                    return expr_span
+                        .error("The branches of this if-else have incompatible types.")
+                        .with_note(
+                            *span_then,
+                            concat! {
+                                "The then branch has type "
+                                format_type(&type_then.type_).into_owned()
+                                "."
+                            },
+                        )
+                        .with_note(
+                            *span_else,
+                            concat! {
+                                "The else branch has type "
+                                format_type(&type_else.type_).into_owned()
+                                "."
+                            },
+                        )
+                        .with_help(concat! {
+                            "If this is intended, add a type annotation, for example "
+                            format_type(&union).into_owned()
+                            "."
+                        })
+                        .err();
+                }
+
+                Typed::Type(type_meet)
+            }
+
+            Expr::Match {
+                scrutinee_span,
+                scrutinee,
+                arms,
+                ..
+            } => {
+                self.check_expr(type_any(), *scrutinee_span, scrutinee)?;
+
+                // Like for if-else, the inferred type is the meet of all arms.
+                let mut result: Option<SourcedType> = None;
+                for arm in arms.iter_mut() {
+                    if let Pattern::Literal(pattern) = &mut arm.pattern {
+                        self.check_expr(type_any(), arm.pattern_span, pattern)?;
+                    }
+                    let type_arm = self.check_expr(expected, arm.body_span, &mut arm.body)?;
+                    result = Some(match result {
+                        None => type_arm,
+                        Some(t) => t.meet(&type_arm),
+                    });
+                }
+                Typed::Type(result.expect("The parser ensures there is at least one arm."))
             }
 
             Expr::Var { span, ident } => match self.env.lookup(ident) {
@@ -516,6 +708,28 @@ impl<'a> TypeChecker<'a> {
                 result_type.is_subtype_of(expected).check(expr_span)?
             }
 
+            Expr::Slice { open, collection_span, collection, start, end, .. } => {
+                let collection_type = self.check_expr(type_any(), *collection_span, collection)?;
+                let result_type = match &collection_type.type_ {
+                    Type::List(..) | Type::String | Type::Any => collection_type.clone(),
+                    not_sliceable => {
+                        let mut error = open
+                            .error("Slicing is not supported here.")
+                            .with_body(concat!{
+                                "Expected a list or string, but got:"
+                                Doc::HardBreak Doc::HardBreak
+                                indent! { format_type(not_sliceable).into_owned() }
+                            });
+                        collection_type.explain_error(Side::Actual, &mut error);
+                        return error.err();
+                    }
+                };
+                for (bound_span, bound) in start.iter_mut().chain(end.iter_mut()) {
+                    self.check_expr(type_int_slice_bound(), *bound_span, bound)?;
+                }
+                result_type.is_subtype_of(expected).check(expr_span)?
+            }
+
             Expr::UnOp { op_span, op, body_span, body, .. } => {
                 self.check_unop(*op_span, *op, *body_span, body)?
                     .is_subtype_of(expected)
@@ -534,24 +748,7 @@ impl<'a> TypeChecker<'a> {
             ),
             // coverage:on
         };
-        match expr_type {
-            // If the type check passed, great, we now know the inferred type.
-            Typed::Type(t) => Ok(t),
-
-            // If we couldn't check statically, then we have to insert a runtime
-            // type check around this node. We have to sacrifice a temporary
-            // NullLit to the borrow checker to swap the node into place.
-            Typed::Defer(t) => {
-                let mut tmp = Expr::NullLit;
-                std::mem::swap(&mut tmp, expr);
-                *expr = Expr::CheckType {
-                    span: expr_span,
-                    type_: expected.clone(),
-                    body: Box::new(tmp),
-                };
-                Ok(t)
-            }
-        }
+        Ok(expr_type)
     }
 
     /// Typecheck a function definition.
@@ -650,7 +847,7 @@ impl<'a> TypeChecker<'a> {
             UnOp::Neg => (Type::Int, Type::Int),
             UnOp::Not => (Type::Bool, Type::Bool),
         };
-        self.check_expr(&type_operator(op_span, body_type), body_span, body)?;
+        self.check_expr_evaluator_checked(&type_operator(op_span, body_type), body_span, body)?;
         Ok(type_operator(op_span, result_type))
     }
 
@@ -665,11 +862,19 @@ impl<'a> TypeChecker<'a> {
     ) -> Result<SourcedType> {
         let (arg_type, result_type) = match op {
             BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => (Type::Int, Type::Int),
-            BinOp::And | BinOp::Or => (Type::Bool, Type::Bool),
+            BinOp::Pow | BinOp::Shl | BinOp::Shr => (Type::Int, Type::Int),
+            BinOp::BitAnd | BinOp::BitXor => (Type::Int, Type::Int),
+            BinOp::And | BinOp::Or | BinOp::Xor => (Type::Bool, Type::Bool),
             // For now we allow comparison only on integers. It should probably
             // be allowed on strings as well.
-            BinOp::Lt | BinOp::LtEq | BinOp::Gt | BinOp::GtEq => (Type::Int, Type::Bool),
+            BinOp::Lt | BinOp::LtEq | BinOp::Gt | BinOp::GtEq => {
+                return self.check_binop_ordering(op_span, op, lhs_span, rhs_span, lhs, rhs)
+            }
             BinOp::Eq | BinOp::Neq => (Type::Any, Type::Bool),
+            BinOp::Range | BinOp::RangeInclusive => (
+                Type::Int,
+                Type::List(Rc::new(type_operator(op_span, Type::Int))),
+            ),
             BinOp::Union => return self.check_binop_union(op_span, lhs_span, rhs_span, lhs, rhs),
         };
         let arg_type = type_operator(op_span, arg_type);
@@ -679,6 +884,37 @@ impl<'a> TypeChecker<'a> {
         Ok(type_operator(op_span, result_type))
     }
 
+    fn check_binop_ordering(
+        &mut self,
+        op_span: Span,
+        op: BinOp,
+        lhs_span: Span,
+        rhs_span: Span,
+        lhs: &mut Expr,
+        rhs: &mut Expr,
+    ) -> Result<SourcedType> {
+        // We infer both sides rather than checking them against Int directly,
+        // so that when one is a Bool, we can suggest to test for equality
+        // instead. When the check has to be deferred, we don't insert a
+        // runtime check: the evaluator checks the operands, and it can make the
+        // same suggestion.
+        let int_type = type_operator(op_span, Type::Int);
+        for (side_span, side) in [(lhs_span, lhs), (rhs_span, rhs)] {
+            let side_type = self.check_expr(type_any(), side_span, side)?;
+            side_type
+                .is_subtype_of(&int_type)
+                .check(side_span)
+                .map_err(|mut err| {
+                    if let Type::Bool = side_type.type_ {
+                        err.set_help(bool_ordering_help(op));
+                    }
+                    err
+                })?;
+        }
+
+        Ok(type_operator(op_span, Type::Bool))
+    }
+
     fn check_binop_union(
         &mut self,
         op_span: Span,
@@ -688,6 +924,13 @@ impl<'a> TypeChecker<'a> {
         rhs: &mut Expr,
     ) -> Result<SourcedType> {
         let lhs_type = self.check_expr(type_any(), lhs_span, lhs)?;
+
+        // On integers, `|` is bitwise OR rather than union.
+        if let Type::Int = lhs_type.type_ {
+            self.check_expr(&type_operator(op_span, Type::Int), rhs_span, rhs)?;
+            return Ok(type_operator(op_span, Type::Int));
+        }
+
         let rhs_type = self.check_expr(type_any(), rhs_span, rhs)?;
         let result_type = match (&lhs_type.type_, &rhs_type.type_) {
             // TODO: There rules are a bit ad-hoc. Maybe don't allow | with
@@ -705,7 +948,7 @@ impl<'a> TypeChecker<'a> {
             (Type::Any | Type::Dict(..) | Type::Set(..), _) => type_any().clone(),
             (not_collection, _) => {
                 let mut error = op_span.error(concat! {
-                    "Expected Dict or Set as the left-hand side of "
+                    "Expected Dict, Set, or Int as the left-hand side of "
                     Doc::highlight("|")
                     " operator, but found this:"
                     Doc::HardBreak Doc::HardBreak
@@ -730,8 +973,8 @@ impl<'a> TypeChecker<'a> {
                 Ok(t)
             }
             Seq::For {
-                idents_span,
-                idents,
+                bindings_span,
+                bindings,
                 collection_span,
                 collection,
                 body,
@@ -744,54 +987,68 @@ impl<'a> TypeChecker<'a> {
                     // If we don't know the type, we can't verify the number of
                     // loop variables, and we don't know their types.
                     Type::Any => {
-                        for ident in idents {
-                            self.env.push(ident.clone(), type_any().clone());
+                        for binding in bindings.iter() {
+                            self.push_binding(binding, type_any().clone())?;
                         }
                     }
                     Type::Dict(dict) => {
-                        if idents.len() != 2 {
-                            // TODO: Deduplicate runtime error. Make it a method
-                            // on the type? Same for functions?
-                            return idents_span
-                                .error("Expected two variables in dict iteration.")
-                                .with_note(
-                                    *collection_span,
-                                    "This is a dict, it yields a key and value per iteration.",
-                                )
-                                .err();
+                        if bindings.len() != 2 {
+                            return iteration_arity_error(
+                                *bindings_span,
+                                bindings.len(),
+                                *collection_span,
+                                "Dict",
+                            )
+                            .err();
                         }
-                        self.env.push(idents[0].clone(), dict.key.clone());
-                        self.env.push(idents[1].clone(), dict.value.clone());
+                        self.push_binding(&bindings[0], dict.key.clone())?;
+                        self.push_binding(&bindings[1], dict.value.clone())?;
                     }
                     Type::List(element_type) => {
-                        if idents.len() != 1 {
-                            return idents_span
-                                .error("Expected a single variable.")
-                                .with_note(
-                                    *collection_span,
-                                    "This is a list, it yields one element per iteration.",
-                                )
-                                .err();
+                        if bindings.len() != 1 {
+                            return iteration_arity_error(
+                                *bindings_span,
+                                bindings.len(),
+                                *collection_span,
+                                "List",
+                            )
+                            .err();
                         }
-                        self.env.push(idents[0].clone(), (**element_type).clone());
+                        self.push_binding(&bindings[0], (**element_type).clone())?;
                     }
                     Type::Set(element_type) => {
-                        if idents.len() != 1 {
-                            return idents_span
-                                .error("Expected a single variable.")
-                                .with_note(
-                                    *collection_span,
-                                    "This is a set, it yields one element per iteration.",
-                                )
-                                .err();
+                        if bindings.len() != 1 {
+                            return iteration_arity_error(
+                                *bindings_span,
+                                bindings.len(),
+                                *collection_span,
+                                "Set",
+                            )
+                            .err();
+                        }
+                        self.push_binding(&bindings[0], (**element_type).clone())?;
+                    }
+                    Type::String => {
+                        if bindings.len() != 1 {
+                            return iteration_arity_error(
+                                *bindings_span,
+                                bindings.len(),
+                                *collection_span,
+                                "String",
+                            )
+                            .err();
                         }
-                        self.env.push(idents[0].clone(), (**element_type).clone());
+                        let char_type = SourcedType {
+                            type_: Type::String,
+                            source: Source::None,
+                        };
+                        self.push_binding(&bindings[0], char_type)?;
                     }
                     not_collection => {
                         return collection_span
                             .error("This is not iterable.")
                             .with_body(concat! {
-                                "Expected a collection, but got:"
+                                "Expected a collection or string, but got:"
                                 Doc::HardBreak Doc::HardBreak
                                 indent! { format_type(not_collection).into_owned() }
                             })
@@ -808,7 +1065,11 @@ impl<'a> TypeChecker<'a> {
                 condition,
                 body,
             } => {
-                self.check_expr(type_bool_condition(), *condition_span, condition)?;
+                self.check_expr_evaluator_checked(
+                    type_bool_condition(),
+                    *condition_span,
+                    condition,
+                )?;
                 self.check_seq(body, seq_type)
             }
         }
@@ -906,11 +1167,48 @@ impl<'a> TypeChecker<'a> {
         }
     }
 
+    /// Bring the names of a binding in scope, with the type of the value they bind to.
+    fn push_binding(&mut self, binding: &Binding, type_: SourcedType) -> Result<()> {
+        match binding {
+            Binding::Ident { ident, .. } => {
+                self.env.push(ident.clone(), type_);
+            }
+            Binding::List { span, elements } => {
+                let element_type = match &type_.type_ {
+                    Type::List(element_type) => (**element_type).clone(),
+                    // For values that are definitely not lists, we can report
+                    // the error statically. The length of a list we can only
+                    // check at runtime.
+                    Type::Null
+                    | Type::Bool
+                    | Type::Int
+                    | Type::String
+                    | Type::Dict(..)
+                    | Type::Set(..)
+                    | Type::Function(..) => {
+                        return span
+                            .error("Only lists can be unpacked.")
+                            .with_body(concat! {
+                                "Expected a list, but got:"
+                                Doc::HardBreak Doc::HardBreak
+                                indent! { format_type(&type_.type_).into_owned() }
+                            })
+                            .err();
+                    }
+                    _ => type_any().clone(),
+                };
+                for element in elements {
+                    self.push_binding(element, element_type.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn check_stmt(&mut self, stmt: &mut Stmt) -> Result<()> {
         match stmt {
             Stmt::Let {
-                ident_span: _,
-                ident,
+                binding,
                 type_,
                 value_span,
                 value,
@@ -929,9 +1227,7 @@ impl<'a> TypeChecker<'a> {
                     None => inferred,
                     Some(_) => required_type,
                 };
-                self.env.push(ident.clone(), bound_type);
-
-                Ok(())
+                self.push_binding(binding, bound_type)
             }
             Stmt::Assert {
                 condition_span,
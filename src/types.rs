@@ -673,6 +673,7 @@ impl SourcedType {
             // can only be one help per error. Either extend that, but probably
             // better, add spans to the sources?
             Source::IndexList => error.set_help("List indices must be integers."),
+            Source::SliceBound => error.set_help("Slice bounds must be integers."),
 
             Source::BuildFile(reason) => error.set_help(*reason),
         }
@@ -0,0 +1,228 @@
+// RCL -- A reasonable configuration language.
+// Copyright 2024 Ruud van Asseldonk
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+
+//! Watch files for changes, to re-run a command when its inputs change.
+//!
+//! Rather than using the notification APIs of the operating system, which
+//! differ per platform, we poll the modification time and size of the files.
+//! The number of files involved in one evaluation is small, so polling them a
+//! few times per second is cheap.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often to check for changes, and how long to wait for them to settle.
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// The time between two checks for changes.
+    pub poll_interval: Duration,
+
+    /// How long the files must be unchanged before we report a change.
+    ///
+    /// Editors often save a file in multiple steps, and a formatter may touch
+    /// several files at once. Waiting until the changes settle avoids running
+    /// the command for every intermediate state.
+    pub debounce: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(200),
+            debounce: Duration::from_millis(100),
+        }
+    }
+}
+
+/// What we observe about a file to detect changes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+/// Return the stamp of the file, or `None` if it cannot be accessed.
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    let stamp = Stamp {
+        modified: meta.modified().ok()?,
+        len: meta.len(),
+    };
+    Some(stamp)
+}
+
+/// The state of a set of files at some point in time.
+pub struct Snapshot {
+    files: Vec<(PathBuf, Option<Stamp>)>,
+}
+
+impl Snapshot {
+    /// Record the current state of the files.
+    ///
+    /// Files that do not exist are included too, creating them counts as a
+    /// change.
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Snapshot {
+        let paths: BTreeSet<PathBuf> = paths.into_iter().collect();
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let s = stamp(&path);
+                (path, s)
+            })
+            .collect();
+        Snapshot { files }
+    }
+
+    /// Return the files that changed since the last poll, and update the state.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, old) in self.files.iter_mut() {
+            let new = stamp(path);
+            if new != *old {
+                *old = new;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Block until a file changes, and then until the changes settle.
+    ///
+    /// Returns the files that changed, in sorted order.
+    pub fn wait_for_change(&mut self, config: &Config) -> Vec<PathBuf> {
+        let mut changed = loop {
+            std::thread::sleep(config.poll_interval);
+            let changed = self.poll();
+            if !changed.is_empty() {
+                break changed;
+            }
+        };
+        loop {
+            std::thread::sleep(config.debounce);
+            let more = self.poll();
+            if more.is_empty() {
+                break;
+            }
+            changed.extend(more);
+        }
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// Call `run`, and call it again every time one of the files it returns changes.
+///
+/// `run` returns the files that it depends on. When it returns `None`, we stop
+/// watching and return.
+pub fn watch_loop<F>(config: &Config, mut run: F)
+where
+    F: FnMut() -> Option<Vec<PathBuf>>,
+{
+    while let Some(paths) = run() {
+        let mut snapshot = Snapshot::new(paths);
+        snapshot.wait_for_change(config);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{watch_loop, Config, Snapshot};
+    use crate::cli::Target;
+    use crate::loader::{Loader, SandboxMode};
+    use crate::runtime::Value;
+    use crate::tracer::VoidTracer;
+    use std::path::PathBuf;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::Duration;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rcl-test-watch-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn snapshot_poll_reports_changed_and_created_files() {
+        let dir = temp_dir("poll");
+        let a = dir.join("a.rcl");
+        let b = dir.join("b.rcl");
+        std::fs::write(&a, "1").unwrap();
+
+        let mut snapshot = Snapshot::new([a.clone(), b.clone(), a.clone()]);
+        assert!(snapshot.poll().is_empty());
+
+        std::fs::write(&a, "12").unwrap();
+        assert_eq!(snapshot.poll(), [a]);
+        assert!(snapshot.poll().is_empty());
+
+        std::fs::write(&b, "2").unwrap();
+        assert_eq!(snapshot.poll(), [b]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn watch_loop_reevaluates_when_an_import_changes() {
+        let dir = temp_dir("loop");
+        std::fs::write(dir.join("main.rcl"), "(import \"port.rcl\") + 1").unwrap();
+        std::fs::write(dir.join("port.rcl"), "80").unwrap();
+
+        // Simulate an edit to the imported file while we are watching. The
+        // editor waits for the first run, so the first run sees the old
+        // contents. The watch loop takes its snapshot only after the run, so
+        // a single write could happen before the snapshot and go unnoticed.
+        // Therefore the editor keeps writing until the second run happens.
+        let port_path = dir.join("port.rcl");
+        let (runs_tx, runs_rx) = mpsc::channel();
+        let editor = std::thread::spawn(move || {
+            runs_rx.recv().unwrap();
+            loop {
+                std::fs::write(&port_path, "8080").unwrap();
+                match runs_rx.recv_timeout(Duration::from_millis(20)) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+
+        let config = Config {
+            poll_interval: Duration::from_millis(10),
+            debounce: Duration::from_millis(10),
+        };
+        let workdir = dir.to_str().unwrap();
+        let mut results = Vec::new();
+        watch_loop(&config, || {
+            let mut loader = Loader::new();
+            loader
+                .initialize_filesystem(SandboxMode::Workdir, Some(workdir), &[])
+                .unwrap();
+            let doc = loader
+                .load_cli_target(&Target::File("main.rcl".into()))
+                .unwrap();
+            let mut type_env = crate::typecheck::prelude();
+            let mut value_env = crate::runtime::prelude();
+            let value = loader
+                .evaluate(&mut type_env, &mut value_env, doc, &mut VoidTracer)
+                .unwrap();
+            results.push(value);
+            runs_tx.send(()).unwrap();
+            match results.len() {
+                1 => Some(loader.loaded_paths()),
+                _ => None,
+            }
+        });
+        editor.join().unwrap();
+
+        assert_eq!(results, [Value::Int(81), Value::Int(8081)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
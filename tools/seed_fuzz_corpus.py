@@ -48,11 +48,18 @@ def seed_one(fname: str) -> int:
         f.write(input_bytes)
         print(f"{shasum} {len(input_bytes):4} {fname}")
 
+    # The parser fuzzer does not take a mode line, it gets the source as-is.
+    parse_bytes = "".join(input_lines[1:]).strip().encode("utf-8")
+    parse_shasum = sha1(parse_bytes).hexdigest()
+    with open(f"fuzz/corpus/fuzz_parse/{parse_shasum}", "wb") as f:
+        f.write(parse_bytes)
+
     return len(input_bytes)
 
 
 def main() -> None:
-    corpus_dir = "fuzz/corpus/main"
+    os.makedirs("fuzz/corpus/main", exist_ok=True)
+    os.makedirs("fuzz/corpus/fuzz_parse", exist_ok=True)
 
     lens: List[int] = []
     for root, _dirs, files in os.walk("golden"):
@@ -36,8 +36,10 @@ fn markup_class(markup: Markup) -> &'static str {
         Markup::Error => "error",
         Markup::Escape => "escape",
         Markup::Field => "field",
+        Markup::Heading => "heading",
         Markup::Highlight => "highlight",
         Markup::Keyword => "keyword",
+        Markup::LineNumber => "line-number",
         Markup::None => "text",
         Markup::Number => "number",
         Markup::String => "string",
@@ -125,14 +127,44 @@ pub fn rcl_evaluate_json(input: &str, out_node: &Node, out_width: u32, max_len:
 
 fn rcl_evaluate_value_impl(input: &str) -> Result<Value> {
     let mut loader = Loader::new();
+    loader.evaluate_str("input", input)
+}
+
+fn eval_to_json_impl(loader: &mut Loader, source: &str) -> Result<String> {
     loader.set_filesystem(Box::new(VoidFilesystem));
-    let id = loader.load_string(input.to_string());
+    let id = loader.load_string(source.to_string());
     let mut tracer = VoidTracer;
-    let mut evaluator = Evaluator::new(&mut loader, &mut tracer);
+    let mut evaluator = Evaluator::new(loader, &mut tracer);
     let mut type_env = rcl::typecheck::prelude();
     let mut value_env = rcl::runtime::prelude();
     let value = evaluator.eval_doc(&mut type_env, &mut value_env, id)?;
-    Ok(value)
+    let body_span = loader.get_span(id);
+    let doc = rcl::fmt_json::format_json(body_span, &value)?;
+    let cfg = pprint::Config { width: 80 };
+    // Unlike the CLI, which writes files, we return the value to embed in a
+    // larger context, so we omit the trailing newline.
+    Ok(doc.print(&cfg).to_string_no_markup())
+}
+
+/// Evaluate a document and format the result as JSON.
+///
+/// This does not touch the DOM, so it can also be used outside of a browser.
+/// The document cannot import other files. On error, the error is returned as
+/// a plain-text report, in the same form as the CLI would print it.
+///
+/// There is no feature flag that keeps `std::fs` out of the wasm build. The
+/// loader reaches the filesystem only through its `Filesystem`, and here that
+/// is the [`VoidFilesystem`], so evaluation never calls into `std::fs`. The
+/// code that does is still linked in. On `wasm32-unknown-unknown`, it only
+/// returns errors.
+#[wasm_bindgen]
+pub fn eval_to_json(source: &str) -> std::result::Result<String, String> {
+    let mut loader = Loader::new();
+    eval_to_json_impl(&mut loader, source).map_err(|err| {
+        let inputs = loader.as_inputs();
+        let cfg = pprint::Config { width: 80 };
+        err.report(&inputs).println(&cfg).to_string_no_markup()
+    })
 }
 
 #[wasm_bindgen]
@@ -337,7 +369,7 @@ pub fn rcl_highlight(input: &str, good_input: &str, out_node: &Node) -> bool {
 
 #[cfg(test)]
 mod wasm_test {
-    use super::{get_edit, Edit};
+    use super::{eval_to_json, get_edit, Edit};
     use rcl::source::{DocId, Span};
     const D: DocId = DocId(0);
 
@@ -446,4 +478,35 @@ mod wasm_test {
     }
 
     // TODO: Write a fuzz test for this part.
+
+    #[test]
+    fn eval_to_json_formats_value() {
+        let result = eval_to_json("{ name = \"rcl\", xs = [for x in [1, 2]: x * 2] }");
+        assert_eq!(
+            result,
+            Ok("{\"name\": \"rcl\", \"xs\": [2, 4]}".to_string())
+        );
+    }
+
+    #[test]
+    fn eval_to_json_reports_errors() {
+        let err = eval_to_json("let x = 1;\nx + true").unwrap_err();
+        assert_eq!(
+            err,
+            "input:2:5\n  ╷\n2 │ x + true\n  ╵     ^~~~\n\
+            Error: Type mismatch. Expected Int but found Bool.\n\n\
+            input:2:3\n  ╷\n2 │ x + true\n  ╵   ^\n\
+            Note: Expected Int because of this operator.\n"
+        );
+    }
+
+    #[test]
+    fn eval_to_json_does_not_load_files() {
+        let err = eval_to_json("import \"other.rcl\"").unwrap_err();
+        assert_eq!(
+            err,
+            "input:1:8\n  ╷\n1 │ import \"other.rcl\"\n  ╵        ^~~~~~~~~~~\n\
+            Error: Void filesystem does not load files.\n"
+        );
+    }
 }